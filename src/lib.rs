@@ -0,0 +1,3 @@
+pub mod nodes;
+pub mod parser;
+pub mod site;
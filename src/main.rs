@@ -1,56 +1,322 @@
-use std::ops::Index;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 
-pub mod nodes;
+use chrono::Utc;
+use rust_ssg::nodes::format::OutputMode;
+use rust_ssg::parser::markdown::{format_markdown, MarkdownStyle};
+use rust_ssg::parser::markdown_to_html_node;
+use rust_ssg::site::check::check;
+use rust_ssg::site::config::Emit as PageEmit;
+use rust_ssg::site::external::{check_external, LinkCache, UreqClient, CACHE_FILE};
+use rust_ssg::site::{BuildOptions, Site, OUTPUT_DIR};
+
+const USAGE: &str =
+    "usage: rust-ssg [--pretty | --minify] [--emit html | ast-json | text] <file.md>
+       rust-ssg fmt [--check] [--wrap <columns> | --no-wrap] <file.md>...
+       rust-ssg build [--out <dir>] [--emit html | ast-json | both] [--drafts] [--future] [<site dir>]
+       rust-ssg check [--external] [--drafts] [--future] [<site dir>]";
+
+// What gets written for the page.
+enum Emit {
+    Html,
+    // The parsed node tree as json, for rendering the content somewhere else.
+    AstJson,
+    // Only the text, without any tags.
+    Text,
+}
 
 fn main() {
-    // Use this to spring borad to split a value bas
-    let test = vec!["Hello **world** this __is__ a test"];
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("fmt") => {
+            args.next();
+            fmt(args);
+        }
+        Some("build") => {
+            args.next();
+            build(args);
+        }
+        Some("check") => {
+            args.next();
+            check_links(args);
+        }
+        _ => {}
+    }
+
+    let mut mode = OutputMode::Compact;
+    let mut emit = Emit::Html;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pretty" => mode = OutputMode::Pretty { indent: 2 },
+            "--minify" => mode = OutputMode::Minified,
+            "--emit" => {
+                emit = match args.next().as_deref() {
+                    Some("html") => Emit::Html,
+                    Some("ast-json") => Emit::AstJson,
+                    Some("text") => Emit::Text,
+                    other => {
+                        eprintln!(
+                            "error: unknown --emit value {:?}\n{}",
+                            other.unwrap_or(""),
+                            USAGE
+                        );
+                        process::exit(1);
+                    }
+                }
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("error: unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    };
+
+    let markdown = match fs::read_to_string(&path) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let document = markdown_to_html_node(&markdown);
+
+    for warning in &document.warnings {
+        eprintln!("warning: {}: {}", path, warning);
+    }
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let written = match emit {
+        Emit::Html => document.root.write_html_with(&mode, &mut out),
+        Emit::AstJson => {
+            serde_json::to_writer_pretty(&mut out, &document.root).map_err(io::Error::from)
+        }
+        Emit::Text => out.write_all(document.root.to_plain_text().as_bytes()),
+    }
+    .and_then(|_| writeln!(out))
+    .and_then(|_| out.flush());
+
+    if let Err(e) = written {
+        eprintln!("error: could not write output: {}", e);
+        process::exit(1);
+    }
+}
+
+// Rewrites markdown files in the normalized style. With `--check` nothing is
+// written, the files that would change are listed and the exit code is 1.
+fn fmt(mut args: impl Iterator<Item = String>) -> ! {
+    let mut style = MarkdownStyle::default();
+    let mut check = false;
+    let mut paths = Vec::new();
 
-    let delm = ["**", "__"];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--no-wrap" => style.wrap = None,
+            "--wrap" => match args.next().and_then(|w| w.parse().ok()) {
+                Some(columns) => style.wrap = Some(columns),
+                None => {
+                    eprintln!("error: --wrap needs a number of columns\n{}", USAGE);
+                    process::exit(1);
+                }
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("error: unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => paths.push(arg),
+        }
+    }
 
-    let split = delm.into_iter().fold(test, |acc, d| {
-        println!("{:?}", acc);
-        acc.iter()
-            .flat_map(|&x| x.split(d).collect::<Vec<_>>())
-            .collect()
-    });
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
 
-    // JOSH use this to expand with the text node
-    let manual_test = vec!["Hello **world** this __is__ a **test**"];
+    let mut unformatted = false;
+    for path in &paths {
+        let markdown = match fs::read_to_string(path) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("error: could not read {}: {}", path, e);
+                process::exit(1);
+            }
+        };
 
-    let another_test = manual_test
-        .iter()
-        .map(|x| {
-            let delm = ["**", "__"];
+        let formatted = format_markdown(&markdown, &style);
+        if formatted == markdown {
+            continue;
+        }
 
-            delm.iter()
-                .map(|y| {
-                    let indx = &x.match_indices(y).collect::<Vec<_>>();
+        if check {
+            println!("{}", path);
+            unformatted = true;
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("error: could not write {}: {}", path, e);
+            process::exit(1);
+        }
+    }
 
-                    let mut indx_groups = Vec::new();
-                    for i in (0..indx.len()).step_by(2) {
-                        indx_groups.push((indx[i], indx[i + 1]))
+    process::exit(if unformatted { 1 } else { 0 });
+}
+
+// Builds the site in the given directory, the current one by default, into
+// its `public` directory. `--emit` beats the `emit` in the config.
+fn build(mut args: impl Iterator<Item = String>) -> ! {
+    let mut root = None;
+    let mut out = None;
+    let mut emit = None;
+    let mut options = BuildOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--drafts" => options.drafts = true,
+            "--future" => options.future = true,
+            "--out" => match args.next() {
+                Some(dir) => out = Some(dir),
+                None => {
+                    eprintln!("error: --out needs a directory\n{}", USAGE);
+                    process::exit(1);
+                }
+            },
+            "--emit" => {
+                emit = match args.next().as_deref() {
+                    Some("html") => Some(PageEmit::Html),
+                    Some("ast-json") => Some(PageEmit::AstJson),
+                    Some("both") => Some(PageEmit::Both),
+                    other => {
+                        eprintln!(
+                            "error: unknown --emit value {:?}\n{}",
+                            other.unwrap_or(""),
+                            USAGE
+                        );
+                        process::exit(1);
                     }
+                }
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("error: unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => root = Some(arg),
+        }
+    }
+
+    let root = Path::new(root.as_deref().unwrap_or("."));
+    let out = out
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join(OUTPUT_DIR));
+
+    let mut site = match Site::load(root, &options) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(emit) = emit {
+        site.config.emit = emit;
+    }
+
+    for page in &site.pages {
+        for warning in &page.document.warnings {
+            eprintln!("warning: {}: {}", page.source, warning);
+        }
+    }
+
+    match site.build(&out) {
+        Ok(files) => {
+            println!(
+                "wrote {} files to {}",
+                files.len() + site.assets.len(),
+                out.display()
+            );
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Reports every broken link in the content of a site, exiting with 1 when
+// there are any.
+fn check_links(args: impl Iterator<Item = String>) -> ! {
+    let mut root = None;
+    let mut options = BuildOptions::default();
+    let mut external = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--external" => external = true,
+            "--drafts" => options.drafts = true,
+            "--future" => options.future = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("error: unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => root = Some(arg),
+        }
+    }
+
+    let root = Path::new(root.as_deref().unwrap_or("."));
+    let (site, broken) = match Site::load(root, &options).and_then(|site| {
+        let broken = check(&site)?;
+        Ok((site, broken))
+    }) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    for link in &broken {
+        println!("{}", link);
+    }
+    println!(
+        "checked {} pages, found {} broken links",
+        site.pages.len(),
+        broken.len()
+    );
+    let mut failed = !broken.is_empty();
+
+    if external {
+        // Links that worked last time are kept next to config.yml.
+        let cache_path = root.join(CACHE_FILE);
+        let report = LinkCache::load(&cache_path).and_then(|mut cache| {
+            let client = UreqClient::new(&site.config.external_links);
+            let report = check_external(&site, &client, &mut cache, Utc::now());
+            cache.save(&cache_path)?;
+            Ok(report)
+        });
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        };
+
+        let count: usize = report.broken.values().map(Vec::len).sum();
+        print!("{}", report);
+        println!(
+            "checked {} external links ({} cached), found {} broken",
+            report.links, report.cached, count
+        );
+        failed |= count > 0;
+    }
 
-                    let test = indx_groups
-                        .iter()
-                        .map(|z| {
-                            let start = z.0 .0;
-                            let end = z.1 .0;
-                            let split = &x[start..end];
-                            let new_node = split.split(z.0 .1).collect::<Vec<_>>();
-
-                            let remaining_nodes = x.split(split).collect::<Vec<_>>();
-                            (new_node[1], remaining_nodes)
-                        })
-                        .collect::<Vec<_>>();
-
-                    println!("{:?}", test);
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-
-    // println!("{:?}", split);
-    // println!("{:?}", another_test);
+    process::exit(if failed { 1 } else { 0 });
 }
@@ -0,0 +1,611 @@
+use core::fmt;
+use std::io;
+
+use crate::parser::html::CLOSES_PARAGRAPH;
+
+use super::html_node::{
+    escape_html, write_escaped, write_io, HTMLAttributes, HTMLChildNode, HTMLNode, ToHtmlString,
+};
+use super::leaf_node::VOID_ELEMENTS;
+use super::parent_node::ParentNode;
+
+// Elements that flow with the text around them, pretty printing keeps them on
+// the same line as their siblings.
+const INLINE_ELEMENTS: [&str; 26] = [
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "del", "dfn", "em", "i", "img", "input",
+    "ins", "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup",
+];
+
+// Elements where whitespace matters, their content is never reformatted.
+const PRESERVE_WHITESPACE: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+// How the html string is laid out.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutputMode {
+    // Everything on one line exactly as `ToHtmlString` produces it.
+    #[default]
+    Compact,
+    // One block element per line, indented by `indent` spaces per level.
+    Pretty {
+        indent: usize,
+    },
+    // Whitespace collapsed, quotes and closing tags left out where the html
+    // spec allows it.
+    Minified,
+}
+
+pub fn format_parent(node: &ParentNode, mode: &OutputMode) -> String {
+    let mut out = String::new();
+    write_view(&View::from_parent(node), mode, &mut out).expect("writing to a String can't fail");
+
+    out
+}
+
+pub fn format_node(node: &HTMLChildNode, mode: &OutputMode) -> String {
+    let mut out = String::new();
+    write_view(&View::from_child(node), mode, &mut out).expect("writing to a String can't fail");
+
+    out
+}
+
+fn write_view<W: fmt::Write + ?Sized>(view: &View, mode: &OutputMode, out: &mut W) -> fmt::Result {
+    match mode {
+        OutputMode::Compact => compact(view, out),
+        OutputMode::Pretty { indent } => pretty(view, 0, *indent, out),
+        OutputMode::Minified => minify(view, None, None, None, false, out),
+    }
+}
+
+// A uniform view over the different node types so every output mode only has
+// to deal with elements, text and raw html.
+enum View<'a> {
+    Element {
+        tag: &'a str,
+        attributes: Option<&'a HTMLAttributes>,
+        children: Vec<View<'a>>,
+    },
+    Text(&'a str),
+    Raw(&'a str),
+    // The value and children of an html node without a tag. Inside of an
+    // element they are spliced into its children.
+    Fragment(Vec<View<'a>>),
+}
+
+// Adds the view to a list of children, spreading a fragment out so its
+// children sit next to their siblings.
+fn push_child<'a>(children: &mut Vec<View<'a>>, view: View<'a>) {
+    match view {
+        View::Fragment(views) => children.extend(views),
+        view => children.push(view),
+    }
+}
+
+impl<'a> View<'a> {
+    fn from_parent(node: &'a ParentNode) -> View<'a> {
+        let mut children = Vec::new();
+        for child in node.children() {
+            push_child(&mut children, View::from_child(child));
+        }
+
+        View::Element {
+            tag: node.tag(),
+            attributes: node.attributes(),
+            children,
+        }
+    }
+
+    fn from_child(node: &'a HTMLChildNode) -> View<'a> {
+        match node {
+            HTMLChildNode::Parent(p) => View::from_parent(p),
+            HTMLChildNode::Leaf(l) => match &l.tag {
+                Some(tag) => View::Element {
+                    tag,
+                    attributes: l.attributes.as_ref(),
+                    children: if l.value.is_empty() {
+                        vec![]
+                    } else {
+                        vec![View::Text(&l.value)]
+                    },
+                },
+                None => View::Text(&l.value),
+            },
+            HTMLChildNode::HTML(h) => View::from_html_node(h),
+            HTMLChildNode::Raw(r) => View::Raw(r),
+        }
+    }
+
+    fn from_html_node(node: &'a HTMLNode) -> View<'a> {
+        let mut children = Vec::new();
+        if let Some(value) = &node.value {
+            children.push(View::Text(value));
+        }
+        for child in node.children.iter().flatten() {
+            push_child(&mut children, View::from_html_node(child));
+        }
+
+        match &node.tag {
+            Some(tag) => View::Element {
+                tag,
+                attributes: node.attributes.as_ref(),
+                children,
+            },
+            None => View::Fragment(children),
+        }
+    }
+
+    // Text, raw html and inline elements are laid out on a single line.
+    fn is_inline(&self) -> bool {
+        match self {
+            View::Element { tag, .. } => INLINE_ELEMENTS.contains(tag),
+            View::Fragment(views) => views.iter().all(View::is_inline),
+            _ => true,
+        }
+    }
+}
+
+fn open_tag<W: fmt::Write + ?Sized>(
+    tag: &str,
+    attributes: Option<&HTMLAttributes>,
+    out: &mut W,
+) -> fmt::Result {
+    write!(out, "<{}", tag)?;
+    if let Some(a) = attributes.filter(|a| !a.is_empty()) {
+        out.write_char(' ')?;
+        a.render_to(out)?;
+    }
+    out.write_char('>')
+}
+
+fn compact<W: fmt::Write + ?Sized>(view: &View, out: &mut W) -> fmt::Result {
+    match view {
+        View::Text(t) => write_escaped(out, t),
+        View::Raw(r) => out.write_str(r),
+        View::Fragment(views) => views.iter().try_for_each(|v| compact(v, out)),
+        View::Element {
+            tag,
+            attributes,
+            children,
+        } => {
+            open_tag(tag, *attributes, out)?;
+            if VOID_ELEMENTS.contains(tag) {
+                return Ok(());
+            }
+            for child in children {
+                compact(child, out)?;
+            }
+            write!(out, "</{}>", tag)
+        }
+    }
+}
+
+fn pretty<W: fmt::Write + ?Sized>(
+    view: &View,
+    depth: usize,
+    indent: usize,
+    out: &mut W,
+) -> fmt::Result {
+    let padding = " ".repeat(depth * indent);
+
+    if let View::Fragment(views) = view {
+        if !view.is_inline() {
+            return views.iter().try_for_each(|v| pretty(v, depth, indent, out));
+        }
+    }
+
+    let View::Element {
+        tag,
+        attributes,
+        children,
+    } = view
+    else {
+        let mut line = String::new();
+        inline(view, &mut line)?;
+        return flush_line(&mut line, depth, indent, out);
+    };
+
+    // Elements whose whitespace can't be touched are written as they are.
+    if PRESERVE_WHITESPACE.contains(tag) {
+        out.write_str(&padding)?;
+        compact(view, out)?;
+        return out.write_char('\n');
+    }
+
+    // Elements holding only inline content stay on one line, without the
+    // whitespace at the start and end of it.
+    if children.iter().all(|c| c.is_inline()) {
+        let mut line = String::new();
+        open_tag(tag, *attributes, &mut line)?;
+        if !VOID_ELEMENTS.contains(tag) {
+            let mut content = String::new();
+            for child in children {
+                inline(child, &mut content)?;
+            }
+            line.push_str(&format!("{}</{}>", content.trim(), tag));
+        }
+        return flush_line(&mut line, depth, indent, out);
+    }
+
+    out.write_str(&padding)?;
+    open_tag(tag, *attributes, out)?;
+    out.write_char('\n')?;
+
+    // Runs of inline children share a line.
+    let mut line = String::new();
+    for child in children {
+        if child.is_inline() {
+            inline(child, &mut line)?;
+            continue;
+        }
+        flush_line(&mut line, depth + 1, indent, out)?;
+        pretty(child, depth + 1, indent, out)?;
+    }
+    flush_line(&mut line, depth + 1, indent, out)?;
+
+    writeln!(out, "{}</{}>", padding, tag)
+}
+
+// Like `compact`, but runs of whitespace in text are collapsed to a single
+// space so the content of a line doesn't spill over several.
+fn inline<W: fmt::Write + ?Sized>(view: &View, out: &mut W) -> fmt::Result {
+    match view {
+        View::Text(t) => write_escaped(out, &collapse_whitespace(t)),
+        View::Fragment(views) => views.iter().try_for_each(|v| inline(v, out)),
+        View::Element {
+            tag,
+            attributes,
+            children,
+        } if !PRESERVE_WHITESPACE.contains(tag) => {
+            open_tag(tag, *attributes, out)?;
+            if VOID_ELEMENTS.contains(tag) {
+                return Ok(());
+            }
+            for child in children {
+                inline(child, out)?;
+            }
+            write!(out, "</{}>", tag)
+        }
+        _ => compact(view, out),
+    }
+}
+
+fn flush_line<W: fmt::Write + ?Sized>(
+    line: &mut String,
+    depth: usize,
+    indent: usize,
+    out: &mut W,
+) -> fmt::Result {
+    if !line.trim().is_empty() {
+        writeln!(out, "{}{}", " ".repeat(depth * indent), line.trim())?;
+    }
+    line.clear();
+
+    Ok(())
+}
+
+fn minify<W: fmt::Write + ?Sized>(
+    view: &View,
+    parent: Option<&str>,
+    previous: Option<&View>,
+    next: Option<&View>,
+    preserve: bool,
+    out: &mut W,
+) -> fmt::Result {
+    match view {
+        View::Raw(r) => out.write_str(r),
+        View::Fragment(views) => {
+            for (i, v) in views.iter().enumerate() {
+                let previous = if i > 0 { views.get(i - 1) } else { previous };
+                let next = views.get(i + 1).or(next);
+                minify(v, parent, previous, next, preserve, out)?;
+            }
+            Ok(())
+        }
+        View::Text(t) if preserve => write_escaped(out, t),
+        View::Text(t) => {
+            let collapsed = collapse_whitespace(t);
+            // Whitespace between block elements doesn't show up on the page.
+            let between_blocks = previous.map(|p| !p.is_inline()).unwrap_or(true)
+                && next.map(|n| !n.is_inline()).unwrap_or(true)
+                && !parent
+                    .map(|p| INLINE_ELEMENTS.contains(&p))
+                    .unwrap_or(false);
+            if collapsed == " " && between_blocks {
+                return Ok(());
+            }
+            write_escaped(out, &collapsed)
+        }
+        View::Element {
+            tag,
+            attributes,
+            children,
+        } => {
+            write!(out, "<{}", tag)?;
+            if let Some(a) = attributes {
+                for (name, value) in a.iter() {
+                    out.write_char(' ')?;
+                    match value {
+                        Some(value) => out.write_str(&minify_attribute(name, value))?,
+                        None => out.write_str(name)?,
+                    }
+                }
+            }
+            out.write_char('>')?;
+
+            if VOID_ELEMENTS.contains(tag) {
+                return Ok(());
+            }
+
+            let preserve = preserve || PRESERVE_WHITESPACE.contains(tag);
+            for (i, child) in children.iter().enumerate() {
+                let previous = if i > 0 { children.get(i - 1) } else { None };
+                minify(
+                    child,
+                    Some(tag),
+                    previous,
+                    children.get(i + 1),
+                    preserve,
+                    out,
+                )?;
+            }
+
+            if !can_omit_closing_tag(tag, parent, next) {
+                write!(out, "</{}>", tag)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+
+    collapsed
+}
+
+// Quotes are only needed when the value has characters that would end it.
+fn minify_attribute(name: &str, value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || "\"'=<>`".contains(c));
+
+    if needs_quotes {
+        format!("{}=\"{}\"", name, escape_html(value))
+    } else {
+        format!("{}={}", name, value.replace('&', "&amp;"))
+    }
+}
+
+// Follows the html spec's rules for optional end tags, only covering the
+// cases that can't change how the page is parsed.
+fn can_omit_closing_tag(tag: &str, parent: Option<&str>, next: Option<&View>) -> bool {
+    let next_tag = match next {
+        Some(View::Element { tag, .. }) => Some(*tag),
+        Some(_) => return false,
+        None => None,
+    };
+    let next_is = |tags: &[&str]| next_tag.map(|t| tags.contains(&t)).unwrap_or(true);
+
+    match tag {
+        "li" => next_is(&["li"]),
+        "dt" => next_tag.map(|t| t == "dt" || t == "dd").unwrap_or(false),
+        "dd" => next_is(&["dt", "dd"]),
+        "tr" => next_is(&["tr"]),
+        "td" | "th" => next_is(&["td", "th"]),
+        "option" => next_is(&["option", "optgroup"]),
+        "thead" => next_tag
+            .map(|t| t == "tbody" || t == "tfoot")
+            .unwrap_or(false),
+        "tbody" => next_is(&["tbody", "tfoot"]),
+        "p" => match next_tag {
+            // The same elements the parser closes an open <p> for.
+            Some(t) => CLOSES_PARAGRAPH.contains(&t),
+            None => {
+                next.is_none()
+                    && parent
+                        .map(|p| {
+                            !["a", "audio", "del", "ins", "map", "noscript", "video"].contains(&p)
+                        })
+                        .unwrap_or(false)
+            }
+        },
+        _ => false,
+    }
+}
+
+impl ParentNode {
+    pub fn into_html_with(&self, mode: &OutputMode) -> String {
+        match mode {
+            OutputMode::Compact => self.into_html(),
+            _ => format_parent(self, mode),
+        }
+    }
+
+    // Streams the node in the given mode, so whole pages can go straight to a
+    // file or stdout.
+    pub fn write_html_with<W: io::Write + ?Sized>(
+        &self,
+        mode: &OutputMode,
+        w: &mut W,
+    ) -> io::Result<()> {
+        write_io(w, |writer| {
+            write_view(&View::from_parent(self), mode, writer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::test_nodes::{page, parent, text};
+
+    use super::*;
+
+    #[test]
+    fn test_format_parent() {
+        let test_cases = vec![
+            (
+                "Test compact output is unchanged",
+                OutputMode::Compact,
+                page().into_html(),
+            ),
+            (
+                "Test pretty output",
+                OutputMode::Pretty { indent: 2 },
+                String::from(
+                    "<body class=\"page\">
+  <nav id=\"top\"><a href=\"/\" class=\"nav-link active\">Home</a><a href=\"/blog/\" class=\"nav-link\">Blog</a><a href=\"https://www.rust-lang.org\" rel=\"me external\">Rust</a></nav>
+  <main>
+    <h1>Posts</h1>
+    <p>Some <b>bold</b> text</p>
+    <pre>fn main() {
+    run();
+}
+</pre>
+    <ul>
+      <li><a href=\"/first/\">First</a></li>
+      <li>
+        Second
+        <ol>
+          <li>a</li>
+        </ol>
+      </li>
+    </ul>
+    <img src=\"cat.png\" alt=\"Cat\">
+    <table>
+      <tr></tr>
+    </table>
+    <img src=\"raw.png\">
+  </main>
+</body>
+",
+                ),
+            ),
+            (
+                "Test minified output",
+                OutputMode::Minified,
+                String::from(
+                    "<body class=page><nav id=top> <a href=/ class=\"nav-link active\">Home</a><a href=/blog/ class=nav-link>Blog</a><a href=https://www.rust-lang.org rel=\"me external\">Rust</a></nav><main><h1>Posts</h1><p>Some <b>bold</b> text<pre>fn main() {\n    run();\n}\n</pre><ul><li><a href=/first/>First</a><li>Second<ol><li>a</ol></ul><img src=cat.png alt=Cat><table><tr></table><img src=\"raw.png\"></main></body>",
+                ),
+            ),
+        ];
+
+        for (title, mode, expected) in test_cases.iter() {
+            assert_eq!(
+                &page().into_html_with(mode),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                mode,
+                expected
+            );
+
+            let mut streamed = Vec::new();
+            page().write_html_with(mode, &mut streamed).unwrap();
+            assert_eq!(
+                &String::from_utf8(streamed).unwrap(),
+                expected,
+                "\"{}\" test failed when streaming for input: {:?} and expexted: {}",
+                title,
+                mode,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_tagless_html_node() {
+        let node = HTMLChildNode::HTML(HTMLNode {
+            tag: None,
+            value: Some(String::from("Intro")),
+            children: Some(vec![
+                HTMLNode {
+                    tag: Some(String::from("p")),
+                    value: Some(String::from("One")),
+                    children: None,
+                    attributes: None,
+                },
+                HTMLNode {
+                    tag: Some(String::from("p")),
+                    value: Some(String::from("Two")),
+                    children: None,
+                    attributes: None,
+                },
+            ]),
+            attributes: None,
+        });
+
+        let test_cases = vec![
+            (
+                "Test compact output keeps every child",
+                OutputMode::Compact,
+                "Intro<p>One</p><p>Two</p>",
+            ),
+            (
+                "Test pretty output keeps every child",
+                OutputMode::Pretty { indent: 2 },
+                "Intro\n<p>One</p>\n<p>Two</p>\n",
+            ),
+            (
+                "Test minified output keeps every child",
+                OutputMode::Minified,
+                "Intro<p>One<p>Two</p>",
+            ),
+        ];
+
+        for (title, mode, expected) in test_cases.iter() {
+            assert_eq!(
+                format_node(&node, mode),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                mode,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_paragraph_closing_tag_is_kept_before_inline_content() {
+        let node = parent(
+            "div",
+            vec![parent("p", vec![text("a")], &[]), text("b")],
+            &[],
+        );
+
+        assert_eq!(
+            format_node(&node, &OutputMode::Minified),
+            "<div><p>a</p>b</div>"
+        );
+    }
+
+    #[test]
+    fn test_paragraph_closing_tag_is_omitted_before_list() {
+        let node = parent(
+            "div",
+            vec![
+                parent("p", vec![text("a")], &[]),
+                parent("ul", vec![parent("li", vec![text("b")], &[])], &[]),
+            ],
+            &[],
+        );
+
+        assert_eq!(
+            format_node(&node, &OutputMode::Minified),
+            "<div><p>a<ul><li>b</ul></div>"
+        );
+    }
+}
@@ -1,54 +1,322 @@
 use core::fmt;
-use std::collections::HashMap;
+use std::io;
+
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::{leaf_node::LeafNode, parent_node::ParentNode};
 
-// HTML Attribute type is used throughout all the nodes.
-#[derive(Debug, PartialEq)]
+// HTML Attribute type is used throughout all the nodes. Attributes are kept
+// in the order they were added so the output matches how the tag was built,
+// a value of None is a boolean attribute like `disabled`.
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct HTMLAttributes {
-    pub attr: HashMap<String, String>,
+    attr: Vec<(String, Option<String>)>,
 }
 
 impl HTMLAttributes {
-    // Converts the Hash map into an HTML string
-    pub fn to_html(&self) -> String {
-        // I want the attributes to produce a lexicographical string, so I
-        // extract the keys, sort then concat the strings.
-        let mut keys: Vec<&String> = self.attr.keys().collect();
+    pub fn new() -> HTMLAttributes {
+        HTMLAttributes::default()
+    }
+
+    // Shorthand for building attributes from string pairs.
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> HTMLAttributes {
+        let mut attributes = HTMLAttributes::new();
+        for (name, value) in pairs {
+            attributes.set(name, value);
+        }
+
+        attributes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attr.is_empty()
+    }
 
-        keys.sort();
+    pub fn len(&self) -> usize {
+        self.attr.len()
+    }
 
-        let string_vec: Vec<String> = keys
+    // Attribute names aren't case sensitive in html.
+    fn position(&self, name: &str) -> Option<usize> {
+        self.attr
             .iter()
-            .map(|&key| format!("{}=\"{}\"", key, self.attr[key]))
-            .collect();
+            .position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    // Boolean attributes have an empty value, same as in the DOM.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name)
+            .map(|i| self.attr[i].1.as_deref().unwrap_or(""))
+    }
+
+    // Replaces the value in place when the attribute is already there, so it
+    // keeps its spot in the output.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.insert(name, Some(String::from(value)));
+    }
+
+    // Adds or removes a boolean attribute like `disabled` or `async`.
+    pub fn set_bool(&mut self, name: &str, on: bool) {
+        if on {
+            self.insert(name, None);
+        } else {
+            self.remove(name);
+        }
+    }
+
+    fn insert(&mut self, name: &str, value: Option<String>) {
+        match self.position(name) {
+            Some(i) => self.attr[i].1 = value,
+            None => self.attr.push((String::from(name), value)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let i = self.position(name)?;
+        let (_, value) = self.attr.remove(i);
 
-        return string_vec.join(" ");
+        Some(value.unwrap_or_default())
     }
+
+    // Name and value pairs in output order, boolean attributes have no value.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.attr.iter().map(|(n, v)| (n.as_str(), v.as_deref()))
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.get("class").unwrap_or("").split_ascii_whitespace()
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes().any(|c| c == class)
+    }
+
+    pub fn add_class(&mut self, class: &str) {
+        if self.has_class(class) {
+            return;
+        }
+
+        let classes = match self.get("class") {
+            Some(c) if !c.trim().is_empty() => format!("{} {}", c.trim(), class),
+            _ => String::from(class),
+        };
+        self.set("class", &classes);
+    }
+
+    // Drops the whole attribute once the last class is gone.
+    pub fn remove_class(&mut self, class: &str) {
+        let classes: Vec<&str> = self.classes().filter(|c| *c != class).collect();
+        if classes.is_empty() {
+            self.remove("class");
+        } else {
+            let classes = classes.join(" ");
+            self.set("class", &classes);
+        }
+    }
+
+    // `data("id")` reads the `data-id` attribute.
+    pub fn data(&self, name: &str) -> Option<&str> {
+        self.get(&format!("data-{}", name))
+    }
+
+    pub fn set_data(&mut self, name: &str, value: &str) {
+        self.set(&format!("data-{}", name), value);
+    }
+
+    // Converts the attributes into an HTML string
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        self.render_to(&mut html)
+            .expect("writing to a String can't fail");
+
+        html
+    }
+
+    pub fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        for (i, (name, value)) in self.iter().enumerate() {
+            if i > 0 {
+                w.write_char(' ')?;
+            }
+            w.write_str(name)?;
+            if let Some(value) = value {
+                w.write_str("=\"")?;
+                write_escaped(w, value)?;
+                w.write_char('"')?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Attributes are written as an object in the same order they are in the tag,
+// boolean attributes have `true` as their value.
+impl Serialize for HTMLAttributes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (name, value) in self.iter() {
+            match value {
+                Some(v) => map.serialize_entry(name, v)?,
+                None => map.serialize_entry(name, &true)?,
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for HTMLAttributes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(AttributesVisitor)
+    }
+}
+
+struct AttributesVisitor;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AttributeValue {
+    Text(String),
+    Boolean(bool),
+}
+
+impl<'de> Visitor<'de> for AttributesVisitor {
+    type Value = HTMLAttributes;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of attribute names to strings or booleans")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<HTMLAttributes, M::Error> {
+        let mut attributes = HTMLAttributes::new();
+        while let Some((name, value)) = map.next_entry::<String, AttributeValue>()? {
+            match value {
+                AttributeValue::Text(v) => attributes.set(&name, &v),
+                AttributeValue::Boolean(on) => attributes.set_bool(&name, on),
+            }
+        }
+
+        Ok(attributes)
+    }
+}
+
+// Escapes text so it can be placed inside of an element or an attribute
+// value. Every `&` is escaped, character references in markdown are decoded
+// by the inline parser before the text ends up here.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    write_escaped(&mut escaped, text).expect("writing to a String can't fail");
+
+    escaped
+}
+
+// Writes the escaped text, the text between characters that need escaping is
+// written in one go.
+pub fn write_escaped<W: fmt::Write + ?Sized>(w: &mut W, text: &str) -> fmt::Result {
+    let mut written = 0;
+
+    for (i, c) in text.char_indices() {
+        let replacement = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' => "&quot;",
+            _ => continue,
+        };
+
+        w.write_str(&text[written..i])?;
+        w.write_str(replacement)?;
+        written = i + c.len_utf8();
+    }
+
+    w.write_str(&text[written..])
 }
 
-#[derive(Debug, PartialEq)]
+// Serialized as `{"parent": {...}}`, `{"leaf": {...}}`, `{"html": {...}}` or
+// `{"raw": "..."}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HTMLChildNode {
+    #[serde(rename = "html")]
     HTML(HTMLNode),
     Leaf(LeafNode),
     Parent(ParentNode),
+    // Html that is written out exactly as it is, like html blocks in markdown.
+    Raw(String),
 }
 
 pub trait ToHtmlString {
-    fn into_html(&self) -> String;
+    // Writes the html straight into `w`, nodes don't build up their own
+    // strings so large pages are written without extra copies.
+    fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result;
+
+    // Named before clippy's into_ convention was checked, and used all over.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_html(&self) -> String {
+        let mut html = String::new();
+        self.render_to(&mut html)
+            .expect("writing to a String can't fail");
+
+        html
+    }
+
+    // Same as `render_to` for io writers like files and stdout.
+    fn write_html<W: io::Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        write_io(w, |writer| self.render_to(writer))
+    }
+}
+
+// Runs a fmt based render against an io writer, keeping hold of the io error
+// since fmt::Error can't carry it.
+pub(crate) fn write_io<W, F>(w: &mut W, render: F) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    F: FnOnce(&mut dyn fmt::Write) -> fmt::Result,
+{
+    let mut writer = IoWriter {
+        inner: w,
+        error: None,
+    };
+
+    match render(&mut writer) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(writer
+            .error
+            .unwrap_or_else(|| io::Error::other("failed to format html"))),
+    }
+}
+
+struct IoWriter<'a, W: io::Write + ?Sized> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write + ?Sized> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
 }
 
 impl ToHtmlString for HTMLChildNode {
-    fn into_html(&self) -> String {
+    fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
         match self {
-            HTMLChildNode::HTML(x) => x.into_html(),
-            HTMLChildNode::Leaf(x) => x.into_html(),
-            HTMLChildNode::Parent(x) => x.into_html(),
+            HTMLChildNode::HTML(x) => x.render_to(w),
+            HTMLChildNode::Leaf(x) => x.render_to(w),
+            HTMLChildNode::Parent(x) => x.render_to(w),
+            HTMLChildNode::Raw(x) => w.write_str(x),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HTMLNode {
     pub tag: Option<String>,
     pub value: Option<String>,
@@ -58,8 +326,8 @@ pub struct HTMLNode {
 
 impl ToHtmlString for HTMLNode {
     //todo! update later to turn the HTMLNode into html
-    fn into_html(&self) -> String {
-        format!("{}", self)
+    fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        write!(w, "{}", self)
     }
 }
 
@@ -109,22 +377,15 @@ mod tests {
         let test_cases = vec![
             (
                 "Test One Attribute to html string",
-                HTMLAttributes {
-                    attr: HashMap::from([
-                        (String::from("href"), String::from("https://www.google.com")),
-                        (String::from("target"), String::from("_blank")),
-                    ]),
-                },
+                HTMLAttributes::from_pairs(&[
+                    ("href", "https://www.google.com"),
+                    ("target", "_blank"),
+                ]),
                 String::from("href=\"https://www.google.com\" target=\"_blank\""),
             ),
             (
                 "Test multiple attributes to html string",
-                HTMLAttributes {
-                    attr: HashMap::from([(
-                        String::from("href"),
-                        String::from("https://www.google.com"),
-                    )]),
-                },
+                HTMLAttributes::from_pairs(&[("href", "https://www.google.com")]),
                 String::from("href=\"https://www.google.com\""),
             ),
         ];
@@ -140,4 +401,232 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_html_attribute_helpers() {
+        let mut async_script = HTMLAttributes::from_pairs(&[("src", "app.js")]);
+        async_script.set_bool("async", true);
+        async_script.set("src", "main.js");
+
+        let mut classes = HTMLAttributes::from_pairs(&[("href", "/"), ("class", "nav")]);
+        classes.add_class("active");
+        classes.add_class("nav");
+
+        let mut removed = HTMLAttributes::from_pairs(&[("class", "nav active"), ("id", "x")]);
+        removed.remove_class("nav");
+        removed.remove_class("active");
+        removed.set_bool("hidden", false);
+
+        let mut data = HTMLAttributes::new();
+        data.set_data("post-id", "42");
+        data.set("Data-Post-Id", "43");
+
+        let test_cases = vec![
+            (
+                "Test insertion order is kept",
+                HTMLAttributes::from_pairs(&[
+                    ("name", "viewport"),
+                    ("content", "width=device-width"),
+                ]),
+                String::from("name=\"viewport\" content=\"width=device-width\""),
+            ),
+            (
+                "Test boolean attribute and value replaced in place",
+                async_script,
+                String::from("src=\"main.js\" async"),
+            ),
+            (
+                "Test classes are only added once",
+                classes,
+                String::from("href=\"/\" class=\"nav active\""),
+            ),
+            (
+                "Test class attribute goes away with the last class",
+                removed,
+                String::from("id=\"x\""),
+            ),
+            (
+                "Test data attributes and names ignore case",
+                data,
+                String::from("data-post-id=\"43\""),
+            ),
+        ];
+
+        for (title, value, expected) in test_cases.iter() {
+            assert_eq!(
+                &value.to_html(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_serialize_nodes() {
+        let mut attributes = HTMLAttributes::from_pairs(&[("type", "checkbox")]);
+        attributes.set_bool("checked", true);
+
+        let test_cases = vec![
+            (
+                "Test attributes keep their order and booleans",
+                HTMLChildNode::Leaf(LeafNode {
+                    tag: Some(String::from("input")),
+                    value: String::new(),
+                    attributes: Some(attributes),
+                }),
+                "{\"leaf\":{\"tag\":\"input\",\"value\":\"\",\"attributes\":{\"type\":\"checkbox\",\"checked\":true}}}",
+            ),
+            (
+                "Test parent with raw child",
+                HTMLChildNode::Parent(ParentNode::new(
+                    "p",
+                    vec![HTMLChildNode::Raw(String::from("<br>"))],
+                    None,
+                )),
+                "{\"parent\":{\"tag\":\"p\",\"children\":[{\"raw\":\"<br>\"}],\"attributes\":null}}",
+            ),
+            (
+                "Test html node",
+                HTMLChildNode::HTML(HTMLNode {
+                    tag: Some(String::from("div")),
+                    value: None,
+                    children: Some(vec![HTMLNode {
+                        tag: None,
+                        value: Some(String::from("Hi")),
+                        children: None,
+                        attributes: None,
+                    }]),
+                    attributes: None,
+                }),
+                "{\"html\":{\"tag\":\"div\",\"value\":null,\"children\":[{\"tag\":null,\"value\":\"Hi\",\"children\":null,\"attributes\":null}],\"attributes\":null}}",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let json = serde_json::to_string(input).unwrap();
+            assert_eq!(
+                &json, expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title, input, expected
+            );
+            assert_eq!(
+                &serde_json::from_str::<HTMLChildNode>(&json).unwrap(),
+                input,
+                "\"{}\" test failed to read back: {}",
+                title,
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_escape_html() {
+        let test_cases = vec![
+            (
+                "Test markup characters are escaped",
+                "<b>\"Fish\" & chips</b>",
+                "&lt;b&gt;&quot;Fish&quot; &amp; chips&lt;/b&gt;",
+            ),
+            (
+                "Test character references are escaped",
+                "&copy; 2024 &#169; &amp;",
+                "&amp;copy; 2024 &amp;#169; &amp;amp;",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &escape_html(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    // Takes `limit` bytes and then fails every write after that.
+    struct FailingWriter {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let room = self.limit - self.written.len();
+            if room == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "disk full"));
+            }
+            let n = buf.len().min(room);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_html() {
+        let node = HTMLChildNode::Parent(ParentNode::new(
+            "p",
+            vec![
+                HTMLChildNode::Leaf(LeafNode {
+                    tag: Some(String::from("b")),
+                    value: String::from("Fish & chips"),
+                    attributes: None,
+                }),
+                HTMLChildNode::Raw(String::from("<br>")),
+            ],
+            None,
+        ));
+        let html = node.into_html();
+
+        let mut out: Vec<u8> = Vec::new();
+        node.write_html(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), html);
+
+        let test_cases = vec![
+            ("Test fails on the first write", 0),
+            ("Test fails partway through", 5),
+            ("Test fails on the last byte", html.len() - 1),
+        ];
+
+        for (title, input) in test_cases.iter() {
+            let mut writer = FailingWriter {
+                written: Vec::new(),
+                limit: *input,
+            };
+            let error = node.write_html(&mut writer).unwrap_err();
+            assert_eq!(
+                (error.kind(), error.to_string(), writer.written.as_slice()),
+                (
+                    io::ErrorKind::BrokenPipe,
+                    String::from("disk full"),
+                    &html.as_bytes()[..*input]
+                ),
+                "\"{}\" test failed for input: {:?} and expexted: the io error",
+                title,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_io_format_error() {
+        let mut out: Vec<u8> = Vec::new();
+        let error = write_io(&mut out, |w| {
+            w.write_str("<p>")?;
+            Err(fmt::Error)
+        })
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "failed to format html");
+        assert_eq!(out, b"<p>");
+    }
 }
@@ -1,8 +1,19 @@
-use super::html_node::{HTMLAttributes, ToHtmlString};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::html_node::{write_escaped, HTMLAttributes, ToHtmlString};
+
+// Elements that can't have any content, they are written without a closing
+// tag.
+pub const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr", "param",
+];
 
 // Leaf Node is a type of HTMLNode that represents a single HTML tag with no
 // children.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LeafNode {
     pub tag: Option<String>,
     pub value: String,
@@ -10,27 +21,34 @@ pub struct LeafNode {
 }
 
 impl ToHtmlString for LeafNode {
-    // Takes the leaf node an turns it into an html string.
-    fn into_html(&self) -> String {
+    // Writes the leaf node out as html.
+    fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
         // If there is a tag then should wrap the value in the tag, but when
-        // there is no tag should return raw text.
-        match &self.tag {
-            Some(t) => {
-                if let Some(a) = &self.attributes {
-                    format!("<{} {}>{}</{}>", t, a.to_html(), self.value, t)
-                } else {
-                    // Ex. t = p then "<p class="disabled">This is the value</p>
-                    format!("<{}>{}</{}>", t, self.value, t)
-                }
-            }
-            None => self.value.clone(),
+        // there is no tag should write raw text.
+        let Some(t) = &self.tag else {
+            return write_escaped(w, &self.value);
+        };
+
+        // Ex. t = p then "<p class="disabled">This is the value</p>
+        write!(w, "<{}", t)?;
+        if let Some(a) = self.attributes.as_ref().filter(|a| !a.is_empty()) {
+            w.write_char(' ')?;
+            a.render_to(w)?;
         }
+        w.write_char('>')?;
+
+        // Ex. <img src="kittens.png" alt="Two kittens">
+        if VOID_ELEMENTS.contains(&t.as_str()) {
+            return Ok(());
+        }
+
+        write_escaped(w, &self.value)?;
+        write!(w, "</{}>", t)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
 
     use super::*;
 
@@ -64,15 +82,34 @@ mod test {
                 LeafNode {
                     tag: Some(String::from("a")),
                     value: String::from("Click me!"),
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([
-                            (String::from("href"), String::from("https://www.google.com")),
-                            (String::from("target"), String::from("_blank")),
-                        ]),
-                    }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[
+                        ("href", "https://www.google.com"),
+                        ("target", "_blank"),
+                    ])),
                 },
                 String::from("<a href=\"https://www.google.com\" target=\"_blank\">Click me!</a>"),
             ),
+            (
+                "Test Leaf Node value is escaped",
+                LeafNode {
+                    tag: Some(String::from("code")),
+                    value: String::from("if a < b && c > d {}"),
+                    attributes: None,
+                },
+                String::from("<code>if a &lt; b &amp;&amp; c &gt; d {}</code>"),
+            ),
+            (
+                "Test void Leaf Node has no closing tag",
+                LeafNode {
+                    tag: Some(String::from("img")),
+                    value: String::from(""),
+                    attributes: Some(HTMLAttributes::from_pairs(&[
+                        ("src", "kittens.png"),
+                        ("alt", "Two kittens"),
+                    ])),
+                },
+                String::from("<img src=\"kittens.png\" alt=\"Two kittens\">"),
+            ),
         ];
 
         for (title, input, expected) in test_cases.iter() {
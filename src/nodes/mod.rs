@@ -1,4 +1,11 @@
+pub mod format;
 pub mod html_node;
 pub mod leaf_node;
 pub mod parent_node;
+pub mod plain_text;
+pub mod sanitize;
+pub mod select;
+#[cfg(test)]
+mod test_nodes;
 pub mod text_node;
+pub mod visit;
@@ -1,38 +1,79 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
 use super::html_node::{HTMLAttributes, HTMLChildNode, ToHtmlString};
 
 // Parent Node will handle the nesting of html nodes.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParentNode {
     tag: String,
     children: Vec<HTMLChildNode>,
     attributes: Option<HTMLAttributes>,
 }
 
+impl ParentNode {
+    pub fn new(
+        tag: &str,
+        children: Vec<HTMLChildNode>,
+        attributes: Option<HTMLAttributes>,
+    ) -> ParentNode {
+        ParentNode {
+            tag: String::from(tag),
+            children,
+            attributes,
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn children(&self) -> &[HTMLChildNode] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<HTMLChildNode> {
+        &mut self.children
+    }
+
+    pub fn attributes(&self) -> Option<&HTMLAttributes> {
+        self.attributes.as_ref()
+    }
+
+    // Gives back the attributes, adding an empty set when there aren't any.
+    pub fn attributes_mut(&mut self) -> &mut HTMLAttributes {
+        self.attributes.get_or_insert_with(HTMLAttributes::new)
+    }
+
+    // Takes the node apart into its tag, children and attributes.
+    pub fn into_parts(self) -> (String, Vec<HTMLChildNode>, Option<HTMLAttributes>) {
+        (self.tag, self.children, self.attributes)
+    }
+}
+
 impl ToHtmlString for ParentNode {
-    fn into_html(&self) -> String {
-        // Take tag and attributes and get tag with
-        let opening_parent_tag = match &self.attributes {
-            Some(a) => format!("{} {}", self.tag, a.to_html()),
-            None => format!("{}", self.tag),
-        };
+    fn render_to<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        // Take tag and attributes and write the opening tag
+        write!(w, "<{}", self.tag)?;
+        if let Some(a) = self.attributes.as_ref().filter(|a| !a.is_empty()) {
+            w.write_char(' ')?;
+            a.render_to(w)?;
+        }
+        w.write_char('>')?;
 
         // Loop through children
-        let hmtl_strings: Vec<String> = self.children.iter().map(|c| c.into_html()).collect();
-
-        return format!(
-            "<{}>{}</{}>",
-            opening_parent_tag,
-            hmtl_strings.join(""),
-            self.tag
-        );
+        for child in &self.children {
+            child.render_to(w)?;
+        }
+
+        write!(w, "</{}>", self.tag)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use std::collections::HashMap;
-
     use crate::nodes::leaf_node::LeafNode;
 
     use super::*;
@@ -94,12 +135,7 @@ mod tests {
                             attributes: None,
                         }),
                     ],
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([
-                            (String::from("href"), String::from("https://www.google.com")),
-                            (String::from("target"), String::from("_blank")),
-                        ]),
-                }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[("href", "https://www.google.com"), ("target", "_blank")])),
                 },
                 String::from("<a href=\"https://www.google.com\" target=\"_blank\"><b>Bold text</b>Normal text<i>italic text</i>Normal text</a>"),
             ),
@@ -121,12 +157,7 @@ mod tests {
                                     attributes: None,
                                 }),
                             ],
-                            attributes: Some(HTMLAttributes {
-                                attr: HashMap::from([
-                                    (String::from("href"), String::from("https://www.google.com")),
-                                    (String::from("target"), String::from("_blank")),
-                                ]),
-                            }),
+                            attributes: Some(HTMLAttributes::from_pairs(&[("href", "https://www.google.com"), ("target", "_blank")])),
                         }),
                         HTMLChildNode::Leaf(LeafNode {
                             tag: Some(String::from("i")),
@@ -0,0 +1,483 @@
+use crate::parser::html::parse_html;
+
+use super::html_node::{HTMLAttributes, HTMLChildNode, ToHtmlString};
+use super::leaf_node::{LeafNode, VOID_ELEMENTS};
+use super::parent_node::ParentNode;
+use super::visit::{walk_parent, Visitor};
+
+// Elements that start on a new paragraph in the text.
+const PARAGRAPH_ELEMENTS: [&str; 22] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "ol",
+    "p",
+    "pre",
+    "section",
+];
+
+// Elements that only start on a new line.
+const LINE_ELEMENTS: [&str; 8] = [
+    "dd",
+    "dt",
+    "figcaption",
+    "li",
+    "summary",
+    "table",
+    "tr",
+    "ul",
+];
+
+// Elements with nothing worth reading in them.
+const SKIPPED_ELEMENTS: [&str; 6] = ["script", "style", "template", "noscript", "iframe", "svg"];
+
+// Classes the markdown renderer puts on decoration that shouldn't end up in
+// the text, like the "1" of a footnote reference.
+const SKIPPED_CLASSES: [&str; 3] = ["footnote-ref", "footnote-backref", "callout-icon"];
+
+// The marker that ends the summary of a page, written on its own line in the
+// markdown.
+const MORE_MARKER: &str = "more";
+
+// Walks the tree writing out only the text. Whitespace is collapsed the same
+// way a browser would, apart from in <pre>, and blocks are kept apart with a
+// blank line, or a single line break for list items and table rows.
+#[derive(Default)]
+struct PlainText {
+    text: String,
+    // 0 when nothing is pending, 1 for a line break and 2 for a blank line.
+    pending_break: usize,
+    pending_space: bool,
+    pre_depth: usize,
+    list_depth: usize,
+    // Set while walking parsed raw html, whose own raw nodes are only comments
+    // and doctypes.
+    in_raw: bool,
+}
+
+impl PlainText {
+    fn block_break(&mut self, lines: usize) {
+        // Everything inside a list stays on single lines.
+        let lines = if self.list_depth > 0 { 1 } else { lines };
+        self.pending_break = self.pending_break.max(lines);
+        self.pending_space = false;
+    }
+
+    fn push(&mut self, text: &str) {
+        if self.pre_depth > 0 {
+            if !text.is_empty() {
+                self.flush();
+                self.text.push_str(text);
+            }
+            return;
+        }
+
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                self.pending_space = true;
+            }
+            self.flush();
+            self.text.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.pending_space = true;
+        }
+    }
+
+    // Writes out whatever break or space is waiting in front of the next bit
+    // of text, breaks at the very start are dropped.
+    fn flush(&mut self) {
+        if self.text.is_empty() {
+            self.pending_break = 0;
+            self.pending_space = false;
+            return;
+        }
+
+        if self.pending_break > 0 {
+            let trimmed = self.text.trim_end_matches(' ').len();
+            self.text.truncate(trimmed);
+            for _ in 0..self.pending_break {
+                self.text.push('\n');
+            }
+        } else if self.pending_space && !self.text.ends_with([' ', '\n']) {
+            self.text.push(' ');
+        }
+
+        self.pending_break = 0;
+        self.pending_space = false;
+    }
+
+    fn finish(self) -> String {
+        let text = self.text.trim_end();
+        String::from(text)
+    }
+}
+
+// How many line breaks go around the element.
+fn block_lines(tag: &str) -> usize {
+    if PARAGRAPH_ELEMENTS.contains(&tag) {
+        2
+    } else if LINE_ELEMENTS.contains(&tag) {
+        1
+    } else {
+        0
+    }
+}
+
+fn skipped(tag: &str, attributes: Option<&HTMLAttributes>) -> bool {
+    SKIPPED_ELEMENTS.contains(&tag)
+        || attributes.is_some_and(|a| SKIPPED_CLASSES.iter().any(|c| a.has_class(c)))
+}
+
+impl Visitor for PlainText {
+    fn visit_parent(&mut self, node: &ParentNode) {
+        let tag = node.tag();
+        if skipped(tag, node.attributes()) {
+            return;
+        }
+
+        let lines = block_lines(tag);
+        if lines > 0 {
+            self.block_break(lines);
+        }
+
+        let list = matches!(tag, "ul" | "ol" | "dl");
+        if tag == "pre" {
+            self.pre_depth += 1;
+        }
+
+        // The list itself is spaced like a paragraph, its items aren't.
+        if list {
+            self.list_depth += 1;
+        }
+        walk_parent(self, node);
+        if list {
+            self.list_depth -= 1;
+        }
+
+        if tag == "pre" {
+            self.pre_depth -= 1;
+        }
+        if matches!(tag, "td" | "th") {
+            self.pending_space = true;
+        }
+        if lines > 0 {
+            self.block_break(lines);
+        }
+    }
+
+    fn visit_leaf(&mut self, node: &LeafNode) {
+        let Some(tag) = node.tag.as_deref() else {
+            return self.push(&node.value);
+        };
+        if skipped(tag, node.attributes.as_ref()) {
+            return;
+        }
+
+        match tag {
+            "br" => self.block_break(1),
+            "hr" => self.block_break(2),
+            // Any other void element, like <img>, has no text.
+            _ if VOID_ELEMENTS.contains(&tag) => {}
+            _ => {
+                let lines = block_lines(tag);
+                if lines > 0 {
+                    self.block_break(lines);
+                }
+                if tag == "pre" {
+                    self.pre_depth += 1;
+                    self.push(&node.value);
+                    self.pre_depth -= 1;
+                } else {
+                    self.push(&node.value);
+                }
+                if matches!(tag, "td" | "th") {
+                    self.pending_space = true;
+                }
+                if lines > 0 {
+                    self.block_break(lines);
+                }
+            }
+        }
+    }
+
+    fn visit_raw(&mut self, html: &str) {
+        // Raw nodes out of the parser are only comments and doctypes.
+        if self.in_raw {
+            return;
+        }
+
+        self.in_raw = true;
+        for node in parse_html(html) {
+            self.visit_child(&node);
+        }
+        self.in_raw = false;
+    }
+}
+
+impl ParentNode {
+    // The text of everything under the node without any tags, for search
+    // indexes and meta descriptions.
+    pub fn to_plain_text(&self) -> String {
+        let mut text = PlainText::default();
+        self.accept(&mut text);
+        text.finish()
+    }
+}
+
+impl HTMLChildNode {
+    pub fn to_plain_text(&self) -> String {
+        let mut text = PlainText::default();
+        text.visit_child(self);
+        text.finish()
+    }
+}
+
+// The start of a page, for index pages and meta descriptions.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    // Whole blocks from the top of the page, so the markup is never cut in
+    // half.
+    pub html: String,
+    pub text: String,
+    // True when the page goes on after the summary, so templates can show a
+    // "read more" link.
+    pub truncated: bool,
+}
+
+// Ex. <!-- more -->, <!--more--> or <!-- MORE -->
+fn is_more_marker(node: &HTMLChildNode) -> bool {
+    let HTMLChildNode::Raw(html) = node else {
+        return false;
+    };
+
+    html.trim()
+        .strip_prefix("<!--")
+        .and_then(|c| c.strip_suffix("-->"))
+        .is_some_and(|c| c.trim().eq_ignore_ascii_case(MORE_MARKER))
+}
+
+impl ParentNode {
+    // Takes everything above a `<!-- more -->` marker when the page has one,
+    // otherwise the first `words` words. The html keeps whole blocks until
+    // there are enough words, while the text is cut at exactly `words` and
+    // ends in "…".
+    pub fn summary(&self, words: usize) -> Summary {
+        let children = self.children();
+
+        if let Some(marker) = children.iter().position(is_more_marker) {
+            let blocks = &children[..marker];
+            return Summary {
+                html: blocks.iter().map(|c| c.into_html()).collect(),
+                text: blocks_text(blocks),
+                truncated: children[marker + 1..]
+                    .iter()
+                    .any(|c| !c.to_plain_text().is_empty()),
+            };
+        }
+
+        let mut html = String::new();
+        let mut count = 0;
+        let mut taken = 0;
+        for child in children {
+            if count >= words {
+                break;
+            }
+            let text = child.to_plain_text();
+            if text.is_empty() {
+                taken += 1;
+                continue;
+            }
+            count += text.split_whitespace().count();
+            html.push_str(&child.into_html());
+            taken += 1;
+        }
+
+        let text = blocks_text(children);
+        let mut cut: Vec<&str> = text.split_whitespace().take(words + 1).collect();
+        let truncated = cut.len() > words
+            || children[taken..]
+                .iter()
+                .any(|c| !c.to_plain_text().is_empty());
+        cut.truncate(words);
+
+        let mut text = cut.join(" ");
+        if truncated && !text.is_empty() {
+            // Ex. "ends here." becomes "ends here…"
+            let end = text.trim_end_matches(['.', ',', ';', ':']).len();
+            text.truncate(end);
+            text.push('…');
+        }
+
+        Summary {
+            html,
+            text,
+            truncated,
+        }
+    }
+}
+
+// The summary text is a single line, it ends up in meta tags and feeds.
+fn blocks_text(blocks: &[HTMLChildNode]) -> String {
+    let mut text = PlainText::default();
+    for block in blocks {
+        text.visit_child(block);
+    }
+
+    text.finish()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::markdown_to_html_node;
+
+    use super::*;
+
+    #[test]
+    fn test_to_plain_text() {
+        let test_cases = vec![
+            (
+                "Test paragraphs are split by a blank line",
+                "# Title\n\nSome **bold** and *em* text.\n\nSecond paragraph\nover two lines.",
+                "Title\n\nSome bold and em text.\n\nSecond paragraph over two lines.",
+            ),
+            (
+                "Test list items are on their own lines",
+                "Intro\n\n- one\n- two\n  - nested\n\n1. first\n\n2. second\n\nOutro",
+                "Intro\n\none\ntwo\nnested\n\nfirst\nsecond\n\nOutro",
+            ),
+            (
+                "Test entities are decoded",
+                "Fish &amp; chips &copy; 2024 &lt;b&gt;",
+                "Fish & chips © 2024 <b>",
+            ),
+            (
+                "Test entities in code are kept",
+                "Write `&amp;` for &amp;amp;",
+                "Write &amp; for &amp;",
+            ),
+            (
+                "Test code blocks keep their whitespace",
+                "```\nfn main() {\n    run();\n}\n```",
+                "fn main() {\n    run();\n}",
+            ),
+            (
+                "Test raw html is read for its text",
+                "<div class=\"note\">\n<p>Raw <em>html</em> &amp; more</p>\n<script>alert(1)</script>\n</div>\n\n<!-- comment -->\n\nAfter",
+                "Raw html & more\n\nAfter",
+            ),
+            (
+                "Test images and footnote numbers are dropped",
+                "See ![a cat](cat.png) here[^1].\n\n[^1]: The note.",
+                "See here.\n\nThe note.",
+            ),
+            (
+                "Test line breaks are kept",
+                "one<br>two",
+                "one\ntwo",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let document = markdown_to_html_node(input);
+            assert_eq!(
+                document.root.to_plain_text(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let test_cases = vec![
+            (
+                "Test content before the more marker",
+                "First *paragraph*.\n\nSecond.\n\n<!-- more -->\n\nThe rest.",
+                5,
+                Summary {
+                    html: String::from("<p>First <i>paragraph</i>.</p><p>Second.</p>"),
+                    text: String::from("First paragraph. Second."),
+                    truncated: true,
+                },
+            ),
+            (
+                "Test marker without spaces at the end of the page",
+                "Only this.\n\n<!--more-->",
+                1,
+                Summary {
+                    html: String::from("<p>Only this.</p>"),
+                    text: String::from("Only this."),
+                    truncated: false,
+                },
+            ),
+            (
+                "Test first words when there is no marker",
+                "One two three four.\n\nFive six.",
+                3,
+                Summary {
+                    html: String::from("<p>One two three four.</p>"),
+                    text: String::from("One two three…"),
+                    truncated: true,
+                },
+            ),
+            (
+                "Test short page isn't truncated",
+                "# Hi\n\nShort page.",
+                10,
+                Summary {
+                    html: String::from("<h1>Hi</h1><p>Short page.</p>"),
+                    text: String::from("Hi Short page."),
+                    truncated: false,
+                },
+            ),
+            (
+                "Test word count ending on a block boundary",
+                "One two.\n\nThree.",
+                2,
+                Summary {
+                    html: String::from("<p>One two.</p>"),
+                    text: String::from("One two…"),
+                    truncated: true,
+                },
+            ),
+        ];
+
+        for (title, input, words, expected) in test_cases.iter() {
+            let document = markdown_to_html_node(input);
+            assert_eq!(
+                &document.root.summary(*words),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,705 @@
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::html::parse_html;
+
+use super::html_node::{HTMLAttributes, HTMLChildNode, HTMLNode, ToHtmlString};
+use super::leaf_node::LeafNode;
+use super::parent_node::ParentNode;
+
+// Attributes that hold a url, their scheme has to be allowed by the policy.
+const URL_ATTRIBUTES: [&str; 7] = [
+    "href",
+    "src",
+    "cite",
+    "action",
+    "formaction",
+    "poster",
+    "xlink:href",
+];
+
+// Decides which tags and attributes are allowed to stay in a node tree.
+// Tags that aren't allowed are unwrapped, keeping their children, unless they
+// are in `removed_with_content` in which case nothing inside of them is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizePolicy {
+    pub allowed_tags: HashSet<String>,
+    // Attributes allowed on every allowed tag.
+    pub allowed_attributes: HashSet<String>,
+    // Extra attributes allowed on specific tags.
+    pub tag_attributes: HashMap<String, HashSet<String>>,
+    pub removed_with_content: HashSet<String>,
+    // Schemes allowed in urls, urls without a scheme are always allowed.
+    pub url_schemes: HashSet<String>,
+    pub allow_style: bool,
+}
+
+fn string_set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|i| i.to_string()).collect()
+}
+
+impl Default for SanitizePolicy {
+    // A policy for user submitted content, it allows the html that markdown
+    // produces along with some common formatting tags.
+    fn default() -> SanitizePolicy {
+        SanitizePolicy {
+            allowed_tags: string_set(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "code",
+                "dd",
+                "del",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "kbd",
+                "li",
+                "ol",
+                "p",
+                "pre",
+                "q",
+                "s",
+                "section",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "tr",
+                "ul",
+            ]),
+            allowed_attributes: string_set(&["class", "id", "title", "lang", "dir"]),
+            tag_attributes: HashMap::from([
+                (String::from("a"), string_set(&["href"])),
+                (
+                    String::from("img"),
+                    string_set(&["src", "alt", "width", "height"]),
+                ),
+                (String::from("ol"), string_set(&["start"])),
+                (String::from("td"), string_set(&["colspan", "rowspan"])),
+                (String::from("th"), string_set(&["colspan", "rowspan"])),
+                (String::from("blockquote"), string_set(&["cite"])),
+            ]),
+            removed_with_content: string_set(&[
+                "script",
+                "style",
+                "iframe",
+                "object",
+                "embed",
+                "noscript",
+                "template",
+                "textarea",
+                "title",
+                "xmp",
+                "noembed",
+                "noframes",
+                "plaintext",
+            ]),
+            url_schemes: string_set(&["http", "https", "mailto"]),
+            allow_style: false,
+        }
+    }
+}
+
+// Something the sanitizer took out of the tree.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Removal {
+    // A tag that isn't allowed, its children were kept.
+    Tag(String),
+    // A tag that was removed along with everything inside of it.
+    TagWithContent(String),
+    Attribute {
+        tag: String,
+        name: String,
+    },
+    Url {
+        tag: String,
+        attribute: String,
+        url: String,
+    },
+    // A comment or doctype in raw html.
+    RawHtml(String),
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Removal::Tag(tag) => write!(f, "removed <{}> tag", tag),
+            Removal::TagWithContent(tag) => write!(f, "removed <{}> and its content", tag),
+            Removal::Attribute { tag, name } => {
+                write!(f, "removed {} attribute from <{}>", name, tag)
+            }
+            Removal::Url {
+                tag,
+                attribute,
+                url,
+            } => write!(f, "removed unsafe {} {:?} from <{}>", attribute, url, tag),
+            Removal::RawHtml(_) => write!(f, "removed raw html"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct SanitizeReport {
+    pub removed: Vec<Removal>,
+}
+
+impl SanitizeReport {
+    pub fn is_clean(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+impl SanitizePolicy {
+    // Sanitizes a page's root node. The root itself is always kept, only its
+    // attributes are checked.
+    pub fn sanitize_parent(&self, root: ParentNode) -> (ParentNode, SanitizeReport) {
+        let mut report = SanitizeReport::default();
+        let (tag, children, attributes) = root.into_parts();
+
+        let attributes = self.clean_attributes(&tag, attributes, &mut report);
+        let children = self.clean_children(children, &mut report);
+
+        (ParentNode::new(&tag, children, attributes), report)
+    }
+
+    pub fn sanitize(&self, nodes: Vec<HTMLChildNode>) -> (Vec<HTMLChildNode>, SanitizeReport) {
+        let mut report = SanitizeReport::default();
+        let nodes = self.clean_children(nodes, &mut report);
+
+        (nodes, report)
+    }
+
+    fn clean_children(
+        &self,
+        nodes: Vec<HTMLChildNode>,
+        report: &mut SanitizeReport,
+    ) -> Vec<HTMLChildNode> {
+        nodes
+            .into_iter()
+            .flat_map(|node| self.clean_node(node, report))
+            .collect()
+    }
+
+    // A node can turn into no nodes when it is removed, or several when an
+    // element is unwrapped and its children take its place.
+    fn clean_node(&self, node: HTMLChildNode, report: &mut SanitizeReport) -> Vec<HTMLChildNode> {
+        match node {
+            // Raw html is parsed so what's inside it goes through the same
+            // checks, only the comments and doctypes left raw are removed.
+            HTMLChildNode::Raw(html) => parse_html(&html)
+                .into_iter()
+                .flat_map(|node| match node {
+                    HTMLChildNode::Raw(html) => {
+                        report.removed.push(Removal::RawHtml(html));
+                        vec![]
+                    }
+                    node => self.clean_node(node, report),
+                })
+                .collect(),
+            HTMLChildNode::Leaf(leaf) => self.clean_leaf(leaf, report),
+            HTMLChildNode::Parent(parent) => {
+                let (tag, children, attributes) = parent.into_parts();
+                let tag = tag.to_ascii_lowercase();
+
+                if !self.keeps_tag(&tag, report) {
+                    if self.removed_with_content.contains(&tag) {
+                        return vec![];
+                    }
+                    return self.clean_children(children, report);
+                }
+
+                let attributes = self.clean_attributes(&tag, attributes, report);
+                let children = self.clean_children(children, report);
+
+                vec![HTMLChildNode::Parent(ParentNode::new(
+                    &tag, children, attributes,
+                ))]
+            }
+            HTMLChildNode::HTML(node) => self
+                .clean_html_node(node, report)
+                .into_iter()
+                .map(HTMLChildNode::HTML)
+                .collect(),
+        }
+    }
+
+    fn clean_leaf(&self, leaf: LeafNode, report: &mut SanitizeReport) -> Vec<HTMLChildNode> {
+        let Some(tag) = leaf.tag.as_deref().map(str::to_ascii_lowercase) else {
+            return vec![HTMLChildNode::Leaf(leaf)];
+        };
+
+        if !self.keeps_tag(&tag, report) {
+            if self.removed_with_content.contains(&tag) || leaf.value.is_empty() {
+                return vec![];
+            }
+            // The value is escaped when rendered so it is safe to keep as text.
+            return vec![HTMLChildNode::Leaf(LeafNode {
+                tag: None,
+                value: leaf.value,
+                attributes: None,
+            })];
+        }
+
+        let attributes = self.clean_attributes(&tag, leaf.attributes, report);
+
+        vec![HTMLChildNode::Leaf(LeafNode {
+            tag: Some(tag),
+            value: leaf.value,
+            attributes,
+        })]
+    }
+
+    fn clean_html_node(&self, node: HTMLNode, report: &mut SanitizeReport) -> Vec<HTMLNode> {
+        let children = node.children.map(|children| {
+            children
+                .into_iter()
+                .flat_map(|child| self.clean_html_node(child, report))
+                .collect::<Vec<_>>()
+        });
+
+        let Some(tag) = node.tag.as_deref().map(str::to_ascii_lowercase) else {
+            return vec![HTMLNode {
+                tag: None,
+                value: self.clean_html_value(node.value, report),
+                children,
+                attributes: None,
+            }];
+        };
+
+        if !self.keeps_tag(&tag, report) {
+            if self.removed_with_content.contains(&tag) {
+                return vec![];
+            }
+            return children.unwrap_or_default();
+        }
+
+        let attributes = self.clean_attributes(&tag, node.attributes, report);
+
+        vec![HTMLNode {
+            tag: Some(tag),
+            value: self.clean_html_value(node.value, report),
+            children,
+            attributes,
+        }]
+    }
+
+    // An html node's value is written out as it is, so it's treated like raw
+    // html and rendered again from what the policy keeps.
+    fn clean_html_value(
+        &self,
+        value: Option<String>,
+        report: &mut SanitizeReport,
+    ) -> Option<String> {
+        value.map(|value| {
+            self.clean_node(HTMLChildNode::Raw(value), report)
+                .iter()
+                .map(|node| node.into_html())
+                .collect()
+        })
+    }
+
+    // Records the removal when the tag isn't allowed. Tags are lowercased by
+    // the callers so `<SCRIPT>` is treated the same as `<script>`.
+    fn keeps_tag(&self, tag: &str, report: &mut SanitizeReport) -> bool {
+        if self.allowed_tags.contains(tag) {
+            return true;
+        }
+
+        let tag = String::from(tag);
+        report
+            .removed
+            .push(if self.removed_with_content.contains(&tag) {
+                Removal::TagWithContent(tag)
+            } else {
+                Removal::Tag(tag)
+            });
+
+        false
+    }
+
+    fn allows_attribute(&self, tag: &str, name: &str) -> bool {
+        if name.starts_with("on") {
+            return false;
+        }
+        if name == "style" {
+            return self.allow_style;
+        }
+
+        self.allowed_attributes.contains(name)
+            || self
+                .tag_attributes
+                .get(tag)
+                .map(|names| names.contains(name))
+                .unwrap_or(false)
+    }
+
+    fn clean_attributes(
+        &self,
+        tag: &str,
+        attributes: Option<HTMLAttributes>,
+        report: &mut SanitizeReport,
+    ) -> Option<HTMLAttributes> {
+        let mut attributes = attributes?;
+
+        let names: Vec<String> = attributes.iter().map(|(n, _)| String::from(n)).collect();
+
+        for name in names {
+            let lower = name.to_ascii_lowercase();
+
+            if !self.allows_attribute(tag, &lower) {
+                attributes.remove(&name);
+                report.removed.push(Removal::Attribute {
+                    tag: String::from(tag),
+                    name,
+                });
+            } else if URL_ATTRIBUTES.contains(&lower.as_str())
+                && !self.allows_url(attributes.get(&name).unwrap_or(""))
+            {
+                let url = attributes.remove(&name).unwrap_or_default();
+                report.removed.push(Removal::Url {
+                    tag: String::from(tag),
+                    attribute: name,
+                    url,
+                });
+            }
+        }
+
+        if attributes.is_empty() {
+            None
+        } else {
+            Some(attributes)
+        }
+    }
+
+    // Browsers ignore whitespace and control characters in a scheme and decode
+    // character references before looking at it, so the same is done here
+    // before comparing it with the allowed schemes.
+    fn allows_url(&self, url: &str) -> bool {
+        let decoded: String = decode_references(url)
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect();
+
+        let scheme_end = decoded.find(':');
+        let path_start = decoded.find(['/', '?', '#']);
+
+        match (scheme_end, path_start) {
+            (Some(colon), Some(path)) if path < colon => true,
+            (Some(colon), _) => self
+                .url_schemes
+                .contains(&decoded[..colon].to_ascii_lowercase()),
+            (None, _) => true,
+        }
+    }
+}
+
+// Decodes numeric character references and the named references that can be
+// used to hide a scheme, like `javascript&colon;`.
+fn decode_references(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let end = rest.find(';').unwrap_or(rest.len());
+        let body = &rest[1..end];
+        let character = if let Some(number) = body.strip_prefix('#') {
+            match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            }
+            .and_then(char::from_u32)
+        } else {
+            match body.to_ascii_lowercase().as_str() {
+                "colon" => Some(':'),
+                "tab" => Some('\t'),
+                "newline" => Some('\n'),
+                _ => None,
+            }
+        };
+
+        match character {
+            Some(c) => {
+                decoded.push(c);
+                rest = rest.get(end + 1..).unwrap_or("");
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::nodes::test_nodes::{leaf, text};
+
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let test_cases = vec![
+            (
+                "Test event handlers and styles are removed",
+                vec![leaf(
+                    "a",
+                    "Click",
+                    &[
+                        ("href", "https://example.com"),
+                        ("onclick", "steal()"),
+                        ("style", "color: red"),
+                    ],
+                )],
+                "<a href=\"https://example.com\">Click</a>",
+                vec![
+                    Removal::Attribute {
+                        tag: String::from("a"),
+                        name: String::from("onclick"),
+                    },
+                    Removal::Attribute {
+                        tag: String::from("a"),
+                        name: String::from("style"),
+                    },
+                ],
+            ),
+            (
+                "Test javascript urls are removed even when obfuscated",
+                vec![
+                    leaf("a", "One", &[("href", " JavaScript:alert(1)")]),
+                    leaf("a", "Two", &[("href", "java&#x09;script&colon;alert(1)")]),
+                    leaf("img", "", &[("src", "/images/cat.png"), ("alt", "cat")]),
+                ],
+                "<a>One</a><a>Two</a><img src=\"/images/cat.png\" alt=\"cat\">",
+                vec![
+                    Removal::Url {
+                        tag: String::from("a"),
+                        attribute: String::from("href"),
+                        url: String::from(" JavaScript:alert(1)"),
+                    },
+                    Removal::Url {
+                        tag: String::from("a"),
+                        attribute: String::from("href"),
+                        url: String::from("java&#x09;script&colon;alert(1)"),
+                    },
+                ],
+            ),
+            (
+                "Test disallowed tags are unwrapped and scripts dropped",
+                vec![HTMLChildNode::Parent(ParentNode::new(
+                    "form",
+                    vec![
+                        text("Name "),
+                        leaf("script", "alert(1)", &[]),
+                        leaf("marquee", "hi", &[]),
+                    ],
+                    None,
+                ))],
+                "Name hi",
+                vec![
+                    Removal::Tag(String::from("form")),
+                    Removal::TagWithContent(String::from("script")),
+                    Removal::Tag(String::from("marquee")),
+                ],
+            ),
+            (
+                "Test raw html is parsed and cleaned",
+                vec![
+                    HTMLChildNode::Raw(String::from(
+                        "<!-- note --><div class=\"box\" onclick=\"go()\"><img src=x onerror=alert(1)><SCRIPT>alert(2)</SCRIPT></div>",
+                    )),
+                    HTMLChildNode::Raw(String::from("<a href=\"javascript:alert(3)\">Go</a>")),
+                ],
+                "<div class=\"box\"><img src=\"x\"></div><a>Go</a>",
+                vec![
+                    Removal::RawHtml(String::from("<!-- note -->")),
+                    Removal::Attribute {
+                        tag: String::from("div"),
+                        name: String::from("onclick"),
+                    },
+                    Removal::Attribute {
+                        tag: String::from("img"),
+                        name: String::from("onerror"),
+                    },
+                    Removal::TagWithContent(String::from("script")),
+                    Removal::Url {
+                        tag: String::from("a"),
+                        attribute: String::from("href"),
+                        url: String::from("javascript:alert(3)"),
+                    },
+                ],
+            ),
+            (
+                "Test tags are matched whatever their case",
+                vec![
+                    leaf("SCRIPT", "alert(1)", &[]),
+                    HTMLChildNode::Parent(ParentNode::new(
+                        "StYlE",
+                        vec![text("body { display: none }")],
+                        None,
+                    )),
+                    leaf("A", "Home", &[("href", "/")]),
+                    leaf("Marquee", "hi", &[]),
+                ],
+                "<a href=\"/\">Home</a>hi",
+                vec![
+                    Removal::TagWithContent(String::from("script")),
+                    Removal::TagWithContent(String::from("style")),
+                    Removal::Tag(String::from("marquee")),
+                ],
+            ),
+        ];
+
+        let policy = SanitizePolicy::default();
+
+        for (title, input, expected_html, expected_removed) in test_cases.into_iter() {
+            let (nodes, report) = policy.sanitize(input);
+            let html: String = nodes.iter().map(|n| n.into_html()).collect();
+
+            assert_eq!(
+                (html.as_str(), &report.removed),
+                (expected_html, &expected_removed),
+                "\"{}\" test failed and expexted: {}",
+                title,
+                expected_html,
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_html_node_case() {
+        let node = HTMLNode {
+            tag: Some(String::from("DIV")),
+            value: None,
+            children: Some(vec![
+                HTMLNode {
+                    tag: Some(String::from("Script")),
+                    value: Some(String::from("alert(1)")),
+                    children: None,
+                    attributes: None,
+                },
+                HTMLNode {
+                    tag: None,
+                    value: Some(String::from("Text")),
+                    children: None,
+                    attributes: None,
+                },
+            ]),
+            attributes: None,
+        };
+
+        let (nodes, report) = SanitizePolicy::default().sanitize(vec![HTMLChildNode::HTML(node)]);
+
+        let HTMLChildNode::HTML(div) = &nodes[0] else {
+            panic!("expected an html node, got {:?}", nodes);
+        };
+        assert_eq!(div.tag.as_deref(), Some("div"));
+        assert_eq!(div.children.as_ref().map(Vec::len), Some(1));
+        assert_eq!(
+            report.removed,
+            vec![Removal::TagWithContent(String::from("script"))]
+        );
+    }
+
+    #[test]
+    fn test_sanitize_html_node_value() {
+        let test_cases = vec![
+            (
+                "Test tagless script value is removed",
+                None,
+                "<script>alert(1)</script>",
+                "",
+            ),
+            (
+                "Test tagless value is escaped",
+                None,
+                "Fish & <b onclick=\"x()\">chips</b>",
+                "Fish &amp; <b>chips</b>",
+            ),
+            (
+                "Test tagged value is cleaned",
+                Some("p"),
+                "<img src=\"javascript:alert(1)\">Hi",
+                "<img>Hi",
+            ),
+        ];
+
+        for (title, tag, input, expected) in test_cases.iter() {
+            let node = HTMLNode {
+                tag: tag.map(String::from),
+                value: Some(String::from(*input)),
+                children: None,
+                attributes: None,
+            };
+
+            let (nodes, _) = SanitizePolicy::default().sanitize(vec![HTMLChildNode::HTML(node)]);
+
+            let HTMLChildNode::HTML(node) = &nodes[0] else {
+                panic!("expected an html node, got {:?}", nodes);
+            };
+            assert_eq!(
+                node.value.as_deref(),
+                Some(*expected),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_parent_with_style_allowed() {
+        let policy = SanitizePolicy {
+            allow_style: true,
+            ..SanitizePolicy::default()
+        };
+        let root = ParentNode::new(
+            "div",
+            vec![leaf("p", "Hi", &[("style", "color: red")])],
+            Some(HTMLAttributes::from_pairs(&[("onload", "x()")])),
+        );
+
+        let (root, report) = policy.sanitize_parent(root);
+
+        assert_eq!(
+            root.into_html(),
+            "<div><p style=\"color: red\">Hi</p></div>"
+        );
+        assert_eq!(
+            report.removed,
+            vec![Removal::Attribute {
+                tag: String::from("div"),
+                name: String::from("onload"),
+            }]
+        );
+    }
+}
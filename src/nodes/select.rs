@@ -0,0 +1,642 @@
+use core::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::html_node::{HTMLAttributes, HTMLChildNode};
+use super::parent_node::ParentNode;
+
+// A parsed css selector, like `nav a.active` or `ul > li:first-child`. Only
+// the parts that are useful for finding elements in a rendered page are
+// supported: tag, class, id and attribute selectors, the descendant and child
+// combinators, `:first-child`, `:last-child` and selector lists split by `,`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+// Compounds joined by combinators, `combinators[i]` sits between
+// `compounds[i]` and `compounds[i + 1]`.
+#[derive(Debug, Clone, PartialEq)]
+struct Complex {
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<AttributeMatch>,
+    first_child: bool,
+    last_child: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeMatch {
+    name: String,
+    value: Option<(AttributeOperator, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AttributeOperator {
+    // [a=b]
+    Equals,
+    // [a~=b], one of the whitespace separated words is b.
+    Includes,
+    // [a^=b]
+    Prefix,
+    // [a$=b]
+    Suffix,
+    // [a*=b]
+    Contains,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SelectorError {
+    Empty,
+    Unexpected(char, usize),
+    UnexpectedEnd,
+    UnknownPseudoClass(String),
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectorError::Empty => write!(f, "selector is empty"),
+            SelectorError::Unexpected(c, i) => {
+                write!(f, "unexpected {:?} at position {} in selector", c, i)
+            }
+            SelectorError::UnexpectedEnd => write!(f, "selector ended unexpectedly"),
+            SelectorError::UnknownPseudoClass(name) => {
+                write!(f, "pseudo class :{} isn't supported", name)
+            }
+        }
+    }
+}
+
+struct SelectorParser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl SelectorParser<'_> {
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {
+            skipped = true;
+        }
+
+        skipped
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn unexpected(&mut self) -> SelectorError {
+        match self.chars.next() {
+            Some((i, c)) => SelectorError::Unexpected(c, i),
+            None => SelectorError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        match self.chars.next_if(|(_, c)| *c == expected) {
+            Some(_) => Ok(()),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, SelectorError> {
+        let mut name = String::new();
+        while let Some((_, c)) = self
+            .chars
+            .next_if(|(_, c)| c.is_alphanumeric() || *c == '-' || *c == '_')
+        {
+            name.push(c);
+        }
+
+        if name.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(name)
+    }
+
+    fn selector(&mut self) -> Result<Selector, SelectorError> {
+        let mut alternatives = vec![self.complex()?];
+        while self.chars.next_if(|(_, c)| *c == ',').is_some() {
+            alternatives.push(self.complex()?);
+        }
+
+        match self.chars.next() {
+            Some((i, c)) => Err(SelectorError::Unexpected(c, i)),
+            None => Ok(Selector { alternatives }),
+        }
+    }
+
+    fn complex(&mut self) -> Result<Complex, SelectorError> {
+        self.skip_whitespace();
+        let mut complex = Complex {
+            compounds: vec![self.compound()?],
+            combinators: vec![],
+        };
+
+        loop {
+            let had_space = self.skip_whitespace();
+            let combinator = match self.peek() {
+                None | Some(',') => return Ok(complex),
+                Some('>') => {
+                    self.chars.next();
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                Some(_) if had_space => Combinator::Descendant,
+                Some(_) => return Err(self.unexpected()),
+            };
+
+            complex.combinators.push(combinator);
+            complex.compounds.push(self.compound()?);
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let mut compound = Compound::default();
+        let mut empty = true;
+
+        match self.peek() {
+            Some('*') => {
+                self.chars.next();
+                empty = false;
+            }
+            Some(c) if c.is_alphabetic() => {
+                compound.tag = Some(self.identifier()?.to_ascii_lowercase());
+                empty = false;
+            }
+            _ => {}
+        }
+
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.chars.next();
+                    compound.id = Some(self.identifier()?);
+                }
+                Some('.') => {
+                    self.chars.next();
+                    compound.classes.push(self.identifier()?);
+                }
+                Some('[') => {
+                    self.chars.next();
+                    compound.attributes.push(self.attribute()?);
+                }
+                Some(':') => {
+                    self.chars.next();
+                    match self.identifier()?.as_str() {
+                        "first-child" => compound.first_child = true,
+                        "last-child" => compound.last_child = true,
+                        other => {
+                            return Err(SelectorError::UnknownPseudoClass(String::from(other)))
+                        }
+                    }
+                }
+                _ if empty => return Err(self.unexpected()),
+                _ => return Ok(compound),
+            }
+            empty = false;
+        }
+    }
+
+    // Everything after the `[` of an attribute selector.
+    fn attribute(&mut self) -> Result<AttributeMatch, SelectorError> {
+        self.skip_whitespace();
+        let name = self.identifier()?.to_ascii_lowercase();
+        self.skip_whitespace();
+
+        let operator = match self.peek() {
+            Some(']') => {
+                self.chars.next();
+                return Ok(AttributeMatch { name, value: None });
+            }
+            Some('=') => AttributeOperator::Equals,
+            Some('~') => AttributeOperator::Includes,
+            Some('^') => AttributeOperator::Prefix,
+            Some('$') => AttributeOperator::Suffix,
+            Some('*') => AttributeOperator::Contains,
+            _ => return Err(self.unexpected()),
+        };
+        self.chars.next();
+        if operator != AttributeOperator::Equals {
+            self.expect('=')?;
+        }
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.chars.next();
+                let mut value = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some((_, c)) if c == quote => break,
+                        Some((_, c)) => value.push(c),
+                        None => return Err(SelectorError::UnexpectedEnd),
+                    }
+                }
+                value
+            }
+            _ => self.identifier()?,
+        };
+
+        self.skip_whitespace();
+        self.expect(']')?;
+
+        Ok(AttributeMatch {
+            name,
+            value: Some((operator, value)),
+        })
+    }
+}
+
+// What the matcher needs to know about an element and where it sits.
+#[derive(Clone, Copy)]
+struct Element<'a> {
+    tag: &'a str,
+    attributes: Option<&'a HTMLAttributes>,
+    first_child: bool,
+    last_child: bool,
+}
+
+impl<'a> Element<'a> {
+    fn from_child(node: &'a HTMLChildNode) -> Option<Element<'a>> {
+        let (tag, attributes) = match node {
+            HTMLChildNode::Parent(p) => (p.tag(), p.attributes()),
+            HTMLChildNode::Leaf(l) => (l.tag.as_deref()?, l.attributes.as_ref()),
+            _ => return None,
+        };
+
+        Some(Element {
+            tag,
+            attributes,
+            first_child: false,
+            last_child: false,
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes?.get(name)
+    }
+}
+
+impl Compound {
+    fn matches(&self, element: &Element) -> bool {
+        if let Some(tag) = &self.tag {
+            if !element.tag.eq_ignore_ascii_case(tag) {
+                return false;
+            }
+        }
+        if let Some(id) = &self.id {
+            if element.attribute("id") != Some(id.as_str()) {
+                return false;
+            }
+        }
+        if !self.classes.is_empty() {
+            let Some(attributes) = element.attributes else {
+                return false;
+            };
+            if !self.classes.iter().all(|c| attributes.has_class(c)) {
+                return false;
+            }
+        }
+        if self.first_child && !element.first_child || self.last_child && !element.last_child {
+            return false;
+        }
+
+        self.attributes.iter().all(|a| a.matches(element))
+    }
+}
+
+impl AttributeMatch {
+    fn matches(&self, element: &Element) -> bool {
+        let Some(actual) = element.attribute(&self.name) else {
+            return false;
+        };
+        let Some((operator, expected)) = &self.value else {
+            return true;
+        };
+
+        match operator {
+            AttributeOperator::Equals => actual == expected,
+            AttributeOperator::Includes => actual.split_ascii_whitespace().any(|w| w == expected),
+            AttributeOperator::Prefix => !expected.is_empty() && actual.starts_with(expected),
+            AttributeOperator::Suffix => !expected.is_empty() && actual.ends_with(expected),
+            AttributeOperator::Contains => !expected.is_empty() && actual.contains(expected),
+        }
+    }
+}
+
+impl Complex {
+    // `chain` holds the element being checked last, preceded by its
+    // ancestors. Matching goes right to left like browsers do.
+    fn matches(&self, chain: &[Element]) -> bool {
+        self.matches_at(self.compounds.len() - 1, chain, chain.len() - 1)
+    }
+
+    fn matches_at(&self, compound: usize, chain: &[Element], position: usize) -> bool {
+        if !self.compounds[compound].matches(&chain[position]) {
+            return false;
+        }
+        if compound == 0 {
+            return true;
+        }
+
+        match self.combinators[compound - 1] {
+            Combinator::Child => position > 0 && self.matches_at(compound - 1, chain, position - 1),
+            Combinator::Descendant => {
+                (0..position).any(|p| self.matches_at(compound - 1, chain, p))
+            }
+        }
+    }
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Selector, SelectorError> {
+        if selector.trim().is_empty() {
+            return Err(SelectorError::Empty);
+        }
+
+        SelectorParser {
+            chars: selector.char_indices().peekable(),
+        }
+        .selector()
+    }
+
+    // Every element under the root that matches, in document order. The root
+    // itself is never returned but it counts as an ancestor, so `div > p`
+    // finds the paragraphs directly inside a root div.
+    pub fn select<'a>(&self, root: &'a ParentNode) -> Vec<&'a HTMLChildNode> {
+        self.matches_with_paths(root)
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect()
+    }
+
+    fn matches_with_paths<'a>(&self, root: &'a ParentNode) -> Vec<(Vec<usize>, &'a HTMLChildNode)> {
+        let mut chain = vec![Element {
+            tag: root.tag(),
+            attributes: root.attributes(),
+            first_child: true,
+            last_child: true,
+        }];
+        let mut found = vec![];
+
+        self.collect(root.children(), &mut chain, &mut vec![], &mut found);
+
+        found
+    }
+
+    fn collect<'a>(
+        &self,
+        children: &'a [HTMLChildNode],
+        chain: &mut Vec<Element<'a>>,
+        path: &mut Vec<usize>,
+        found: &mut Vec<(Vec<usize>, &'a HTMLChildNode)>,
+    ) {
+        // first and last child only count elements, not text.
+        let elements: Vec<usize> = children
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| Element::from_child(c).is_some())
+            .map(|(i, _)| i)
+            .collect();
+
+        for (i, child) in children.iter().enumerate() {
+            let Some(mut element) = Element::from_child(child) else {
+                continue;
+            };
+            element.first_child = elements.first() == Some(&i);
+            element.last_child = elements.last() == Some(&i);
+
+            chain.push(element);
+            path.push(i);
+
+            if self.alternatives.iter().any(|c| c.matches(chain)) {
+                found.push((path.clone(), child));
+            }
+            if let HTMLChildNode::Parent(p) = child {
+                self.collect(p.children(), chain, path, found);
+            }
+
+            path.pop();
+            chain.pop();
+        }
+    }
+}
+
+fn child_at_path_mut<'a>(
+    root: &'a mut ParentNode,
+    path: &[usize],
+) -> Option<&'a mut HTMLChildNode> {
+    let (last, parents) = path.split_last()?;
+    let mut children = root.children_mut();
+
+    for i in parents {
+        match children.get_mut(*i)? {
+            HTMLChildNode::Parent(p) => children = p.children_mut(),
+            _ => return None,
+        }
+    }
+
+    children.get_mut(*last)
+}
+
+impl ParentNode {
+    pub fn select(&self, selector: &str) -> Result<Vec<&HTMLChildNode>, SelectorError> {
+        Ok(Selector::parse(selector)?.select(self))
+    }
+
+    pub fn select_first(&self, selector: &str) -> Result<Option<&HTMLChildNode>, SelectorError> {
+        Ok(self.select(selector)?.into_iter().next())
+    }
+
+    pub fn select_first_mut(
+        &mut self,
+        selector: &str,
+    ) -> Result<Option<&mut HTMLChildNode>, SelectorError> {
+        let selector = Selector::parse(selector)?;
+        let Some((path, _)) = selector.matches_with_paths(self).into_iter().next() else {
+            return Ok(None);
+        };
+
+        Ok(child_at_path_mut(self, &path))
+    }
+
+    // Matches can be nested inside each other so they can't all be handed
+    // out as mutable references at once, instead `f` gets called with each
+    // one. They are visited last to first so changing a match never moves a
+    // match that hasn't been visited yet. Returns how many matched.
+    pub fn select_each_mut<F>(&mut self, selector: &str, mut f: F) -> Result<usize, SelectorError>
+    where
+        F: FnMut(&mut HTMLChildNode),
+    {
+        let selector = Selector::parse(selector)?;
+        let paths: Vec<Vec<usize>> = selector
+            .matches_with_paths(self)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        for path in paths.iter().rev() {
+            if let Some(node) = child_at_path_mut(self, path) {
+                f(node);
+            }
+        }
+
+        Ok(paths.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::nodes::test_nodes::page;
+
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let test_cases = vec![
+            ("Test tag", "h1", vec!["<h1>Posts</h1>"]),
+            (
+                "Test descendant and class",
+                "nav a.active",
+                vec!["<a href=\"/\" class=\"nav-link active\">Home</a>"],
+            ),
+            (
+                "Test id",
+                "#top > a:last-child",
+                vec!["<a href=\"https://www.rust-lang.org\" rel=\"me external\">Rust</a>"],
+            ),
+            (
+                "Test first child skips text",
+                "nav > :first-child",
+                vec!["<a href=\"/\" class=\"nav-link active\">Home</a>"],
+            ),
+            ("Test child combinator", "main > a", vec![]),
+            (
+                "Test root counts as an ancestor",
+                "body > main > h1",
+                vec!["<h1>Posts</h1>"],
+            ),
+            (
+                "Test attribute prefix",
+                "a[href^=\"https\"]",
+                vec!["<a href=\"https://www.rust-lang.org\" rel=\"me external\">Rust</a>"],
+            ),
+            (
+                "Test attribute word",
+                "[rel~=external]",
+                vec!["<a href=\"https://www.rust-lang.org\" rel=\"me external\">Rust</a>"],
+            ),
+            (
+                "Test attribute equals",
+                "a[href='/blog/']",
+                vec!["<a href=\"/blog/\" class=\"nav-link\">Blog</a>"],
+            ),
+            (
+                "Test selector list in document order",
+                "ul > li:last-child, h1",
+                vec!["<h1>Posts</h1>", "<li>Second<ol><li>a</li></ol></li>"],
+            ),
+            (
+                "Test all classes have to match",
+                ".nav-link.active",
+                vec!["<a href=\"/\" class=\"nav-link active\">Home</a>"],
+            ),
+        ];
+
+        for (title, selector, expected) in test_cases.iter() {
+            let node = page();
+            let found: Vec<String> = node
+                .select(selector)
+                .unwrap()
+                .iter()
+                .map(|n| n.into_html())
+                .collect();
+
+            assert_eq!(
+                &found, expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title, selector, expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_selector() {
+        let test_cases = vec![
+            ("Test empty selector", "  ", SelectorError::Empty),
+            (
+                "Test dangling combinator",
+                "nav >",
+                SelectorError::UnexpectedEnd,
+            ),
+            (
+                "Test unclosed attribute",
+                "a[href",
+                SelectorError::UnexpectedEnd,
+            ),
+            (
+                "Test unsupported pseudo class",
+                "a:hover",
+                SelectorError::UnknownPseudoClass(String::from("hover")),
+            ),
+            (
+                "Test unknown combinator",
+                "nav + a",
+                SelectorError::Unexpected('+', 4),
+            ),
+        ];
+
+        for (title, selector, expected) in test_cases.iter() {
+            assert_eq!(
+                &Selector::parse(selector).unwrap_err(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                selector,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_mut() {
+        let mut node = page();
+
+        if let Some(HTMLChildNode::Leaf(h1)) = node.select_first_mut("main h1").unwrap() {
+            h1.value = String::from("All posts");
+        }
+        let count = node
+            .select_each_mut("nav a", |link| {
+                if let HTMLChildNode::Leaf(l) = link {
+                    l.attributes
+                        .get_or_insert_with(HTMLAttributes::new)
+                        .add_class("link");
+                }
+            })
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(
+            node.select("h1").unwrap()[0].into_html(),
+            "<h1>All posts</h1>"
+        );
+        assert_eq!(node.select(".link").unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,96 @@
+// Node trees for the tests of the modules in `nodes`.
+
+use super::html_node::{HTMLAttributes, HTMLChildNode};
+use super::leaf_node::LeafNode;
+use super::parent_node::ParentNode;
+
+fn attributes(pairs: &[(&str, &str)]) -> Option<HTMLAttributes> {
+    if pairs.is_empty() {
+        None
+    } else {
+        Some(HTMLAttributes::from_pairs(pairs))
+    }
+}
+
+pub fn text(value: &str) -> HTMLChildNode {
+    HTMLChildNode::Leaf(LeafNode {
+        tag: None,
+        value: String::from(value),
+        attributes: None,
+    })
+}
+
+pub fn leaf(tag: &str, value: &str, pairs: &[(&str, &str)]) -> HTMLChildNode {
+    HTMLChildNode::Leaf(LeafNode {
+        tag: Some(String::from(tag)),
+        value: String::from(value),
+        attributes: attributes(pairs),
+    })
+}
+
+pub fn parent(tag: &str, children: Vec<HTMLChildNode>, pairs: &[(&str, &str)]) -> HTMLChildNode {
+    HTMLChildNode::Parent(ParentNode::new(tag, children, attributes(pairs)))
+}
+
+// A page with a bit of everything: links, whitespace, a pre, nested lists,
+// images, a table and raw html.
+//
+// Ex.
+// <body class="page"><nav id="top">
+// <a href="/" class="nav-link active">Home</a>...</nav><main>...</main></body>
+pub fn page() -> ParentNode {
+    ParentNode::new(
+        "body",
+        vec![
+            parent(
+                "nav",
+                vec![
+                    text("\n"),
+                    leaf("a", "Home", &[("href", "/"), ("class", "nav-link active")]),
+                    leaf("a", "Blog", &[("href", "/blog/"), ("class", "nav-link")]),
+                    leaf(
+                        "a",
+                        "Rust",
+                        &[
+                            ("href", "https://www.rust-lang.org"),
+                            ("rel", "me external"),
+                        ],
+                    ),
+                ],
+                &[("id", "top")],
+            ),
+            parent(
+                "main",
+                vec![
+                    leaf("h1", "Posts", &[]),
+                    parent(
+                        "p",
+                        vec![text("Some   "), leaf("b", "bold", &[]), text("\n text")],
+                        &[],
+                    ),
+                    leaf("pre", "fn main() {\n    run();\n}\n", &[]),
+                    parent(
+                        "ul",
+                        vec![
+                            parent("li", vec![leaf("a", "First", &[("href", "/first/")])], &[]),
+                            parent(
+                                "li",
+                                vec![
+                                    text("Second"),
+                                    parent("ol", vec![leaf("li", "a", &[])], &[]),
+                                ],
+                                &[],
+                            ),
+                        ],
+                        &[],
+                    ),
+                    leaf("img", "", &[("src", "cat.png"), ("alt", "Cat")]),
+                    parent("table", vec![leaf("tr", "", &[])], &[]),
+                    HTMLChildNode::Raw(String::from("<img src=\"raw.png\">")),
+                ],
+                &[],
+            ),
+        ],
+        Some(HTMLAttributes::from_pairs(&[("class", "page")])),
+    )
+}
@@ -1,10 +1,12 @@
 use core::fmt;
-use std::collections::HashMap;
 
-use super::html_node::HTMLAttributes;
+use serde::{Deserialize, Serialize};
+
+use super::html_node::{HTMLAttributes, HTMLChildNode};
 use super::leaf_node::LeafNode;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TextType {
     Normal,
     Bold,
@@ -12,16 +14,20 @@ pub enum TextType {
     Code,
     Link,
     Image,
+    // A `[^label]` reference, the url points at the footnote definition.
+    FootnoteReference,
+    // An inline html tag or comment written in the markdown.
+    RawHtml,
 }
 
 #[derive(Debug)]
-struct MarkdownDelimiter {
-    name: TextType,
-    delimiters: &'static [&'static str],
+pub struct MarkdownDelimiter {
+    pub name: TextType,
+    pub delimiters: &'static [&'static str],
 }
 
 impl TextType {
-    fn get_delimiter(&self) -> Option<MarkdownDelimiter> {
+    pub fn get_delimiter(&self) -> Option<MarkdownDelimiter> {
         match self {
             TextType::Normal => None,
             TextType::Bold => Some(MarkdownDelimiter {
@@ -39,11 +45,13 @@ impl TextType {
             // todo!() refactor later
             TextType::Link => None,
             TextType::Image => None,
+            TextType::FootnoteReference => None,
+            TextType::RawHtml => None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct TextNode {
     pub content: String,
     pub text_type: TextType,
@@ -54,7 +62,7 @@ impl fmt::Display for TextNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let url = match &self.url {
             None => "",
-            Some(x) => &x,
+            Some(x) => x,
         };
 
         write!(
@@ -94,9 +102,7 @@ impl TextNode {
                 LeafNode {
                     tag: Some(String::from("a")),
                     value: self.content.clone(),
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([(String::from("href"), url)]),
-                    }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[("href", &url)])),
                 }
             }
             TextType::Image => {
@@ -105,59 +111,84 @@ impl TextNode {
                 LeafNode {
                     tag: Some(String::from("img")),
                     value: String::from(""),
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([
-                            (String::from("href"), url),
-                            (String::from("alt"), self.content.clone()),
-                        ]),
-                    }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[
+                        ("src", &url),
+                        ("alt", &self.content),
+                    ])),
+                }
+            }
+            TextType::RawHtml => LeafNode {
+                tag: None,
+                value: self.content.clone(),
+                attributes: None,
+            },
+            TextType::FootnoteReference => {
+                let url = self.url.clone().unwrap_or(String::from(""));
+
+                LeafNode {
+                    tag: Some(String::from("a")),
+                    value: self.content.clone(),
+                    attributes: Some(HTMLAttributes::from_pairs(&[("href", &url)])),
                 }
             }
         }
     }
 
-    pub fn split_node_on_delimiter(&self, delimiter: MarkdownDelimiter) -> Vec<TextNode> {
-        let text_nodes = match self.text_type {
-            TextType::Normal => {
-                let content = vec![self.content.as_str()];
+    // Raw html can't be a leaf node since leaf values are escaped, so it is
+    // turned into a raw child node instead.
+    pub fn into_html_child(&self) -> HTMLChildNode {
+        match self.text_type {
+            TextType::RawHtml => HTMLChildNode::Raw(self.content.clone()),
+            _ => HTMLChildNode::Leaf(self.into_html_node()),
+        }
+    }
 
-                let delim = &delimiter.delimiters;
-                // Update this to convert strings into text nodes.
-                // Then also have to account for there being both text node
-                // type and str type maybe us a generic ?
-                let split = delim.into_iter().fold(content, |acc, d| {
-                    acc.iter()
-                        .flat_map(|&x| x.split(d).collect::<Vec<_>>())
-                        .collect()
-                });
+    // Splits a normal text node on the delimiter, every second piece of the
+    // split ends up wrapped in the delimiter so it takes the delimiter's type.
+    // Nodes that are not normal text are left untouched so things like code
+    // spans don't get parsed twice.
+    pub fn split_node_on_delimiter(&self, delimiter: &MarkdownDelimiter) -> Vec<TextNode> {
+        if self.text_type != TextType::Normal {
+            return vec![self.clone()];
+        }
 
-                // let deliminators = vec![TextType::Bold, TextType::Italic, TextType::Code];
+        delimiter
+            .delimiters
+            .iter()
+            .fold(vec![self.clone()], |acc, d| {
+                acc.iter()
+                    .flat_map(|node| node.split_on(d, &delimiter.name))
+                    .collect()
+            })
+    }
 
-                // deliminators.iter().fold(vec![self.content], |acc, delim| {
-                //     let type_deliminators = delim.get_delimiter().unwrap();
+    fn split_on(&self, delimiter: &str, text_type: &TextType) -> Vec<TextNode> {
+        if self.text_type != TextType::Normal {
+            return vec![self.clone()];
+        }
 
-                //     let delm_vec = type_deliminators.delimiters.iter().fold(&acc, |x, d| {
-                //         x.into_iter().reduce(|acc, a| acc.split(*d)).unwrap()
-                //     });
-                //     println!("{:?}", type_deliminators);
-                //     println!("{:?}", delm_vec);
-                //     acc
-                // });
+        let pieces: Vec<&str> = self.content.split(delimiter).collect();
 
-                // Determine deliminator. Need to loop through all delimnators
-                // Maybe create an enum maybe a hashmap for deliminator types.
-                // like bold = ** or __
-                // like HashMap::from({"**": TextType::Bold, "__": TextType::Bold})
-                vec![TextNode {
-                    content: String::from("test"),
-                    url: None,
-                    text_type: TextType::Normal,
-                }]
-            }
-            _ => vec![self.clone()],
-        };
+        // An even number of pieces means a delimiter was never closed, in
+        // which case the markdown is treated as plain text.
+        if pieces.len() < 3 || pieces.len().is_multiple_of(2) {
+            return vec![self.clone()];
+        }
 
-        return text_nodes;
+        pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| !piece.is_empty())
+            .map(|(i, piece)| TextNode {
+                content: piece.to_string(),
+                text_type: if i % 2 == 0 {
+                    TextType::Normal
+                } else {
+                    text_type.clone()
+                },
+                url: None,
+            })
+            .collect()
     }
 }
 
@@ -231,12 +262,10 @@ mod tests {
                 LeafNode {
                     tag: Some(String::from("a")),
                     value: String::from("About Us"),
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([(
-                            String::from("href"),
-                            String::from("https://google.com"),
-                        )]),
-                    }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[(
+                        "href",
+                        "https://google.com",
+                    )])),
                 },
             ),
             (
@@ -249,18 +278,10 @@ mod tests {
                 LeafNode {
                     tag: Some(String::from("img")),
                     value: String::from(""),
-                    attributes: Some(HTMLAttributes {
-                        attr: HashMap::from([
-                            (
-                                String::from("href"),
-                                String::from("https://placeholder.cdn.com"),
-                            ),
-                            (
-                                String::from("alt"),
-                                String::from("Two kittens playing with yarn"),
-                            ),
-                        ]),
-                    }),
+                    attributes: Some(HTMLAttributes::from_pairs(&[
+                        ("src", "https://placeholder.cdn.com"),
+                        ("alt", "Two kittens playing with yarn"),
+                    ])),
                 },
             ),
         ];
@@ -278,5 +299,106 @@ mod tests {
     }
 
     #[test]
-    fn parse_deliminator() {}
+    fn test_split_node_on_delimiter() {
+        let test_cases = vec![
+            (
+                "Test bold delimiter",
+                TextNode {
+                    content: String::from("Hello **world** this __is__ a test"),
+                    text_type: TextType::Normal,
+                    url: None,
+                },
+                TextType::Bold,
+                vec![
+                    TextNode {
+                        content: String::from("Hello "),
+                        text_type: TextType::Normal,
+                        url: None,
+                    },
+                    TextNode {
+                        content: String::from("world"),
+                        text_type: TextType::Bold,
+                        url: None,
+                    },
+                    TextNode {
+                        content: String::from(" this "),
+                        text_type: TextType::Normal,
+                        url: None,
+                    },
+                    TextNode {
+                        content: String::from("is"),
+                        text_type: TextType::Bold,
+                        url: None,
+                    },
+                    TextNode {
+                        content: String::from(" a test"),
+                        text_type: TextType::Normal,
+                        url: None,
+                    },
+                ],
+            ),
+            (
+                "Test code delimiter at the start",
+                TextNode {
+                    content: String::from("`cargo run` builds the site"),
+                    text_type: TextType::Normal,
+                    url: None,
+                },
+                TextType::Code,
+                vec![
+                    TextNode {
+                        content: String::from("cargo run"),
+                        text_type: TextType::Code,
+                        url: None,
+                    },
+                    TextNode {
+                        content: String::from(" builds the site"),
+                        text_type: TextType::Normal,
+                        url: None,
+                    },
+                ],
+            ),
+            (
+                "Test unclosed delimiter is left as text",
+                TextNode {
+                    content: String::from("2 * 3 = 6"),
+                    text_type: TextType::Normal,
+                    url: None,
+                },
+                TextType::Italic,
+                vec![TextNode {
+                    content: String::from("2 * 3 = 6"),
+                    text_type: TextType::Normal,
+                    url: None,
+                }],
+            ),
+            (
+                "Test non normal nodes are not split",
+                TextNode {
+                    content: String::from("a *b* c"),
+                    text_type: TextType::Code,
+                    url: None,
+                },
+                TextType::Italic,
+                vec![TextNode {
+                    content: String::from("a *b* c"),
+                    text_type: TextType::Code,
+                    url: None,
+                }],
+            ),
+        ];
+
+        for (title, input, text_type, expected) in test_cases.iter() {
+            let delimiter = text_type.get_delimiter().unwrap();
+
+            assert_eq!(
+                &input.split_node_on_delimiter(&delimiter),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
 }
@@ -0,0 +1,267 @@
+use std::mem;
+use std::slice;
+
+use super::html_node::{HTMLChildNode, HTMLNode};
+use super::leaf_node::LeafNode;
+use super::parent_node::ParentNode;
+
+// Reads through a node tree. Every method has a default that keeps walking,
+// so a pass only overrides the nodes it cares about. Overriding `visit_parent`
+// without calling `walk_parent` skips that node's children.
+pub trait Visitor {
+    fn visit_child(&mut self, node: &HTMLChildNode) {
+        walk_child(self, node);
+    }
+
+    fn visit_parent(&mut self, node: &ParentNode) {
+        walk_parent(self, node);
+    }
+
+    fn visit_leaf(&mut self, _node: &LeafNode) {}
+
+    fn visit_html_node(&mut self, _node: &HTMLNode) {}
+
+    fn visit_raw(&mut self, _html: &str) {}
+}
+
+pub fn walk_child<V: Visitor + ?Sized>(visitor: &mut V, node: &HTMLChildNode) {
+    match node {
+        HTMLChildNode::Parent(p) => visitor.visit_parent(p),
+        HTMLChildNode::Leaf(l) => visitor.visit_leaf(l),
+        HTMLChildNode::HTML(h) => visitor.visit_html_node(h),
+        HTMLChildNode::Raw(r) => visitor.visit_raw(r),
+    }
+}
+
+pub fn walk_parent<V: Visitor + ?Sized>(visitor: &mut V, node: &ParentNode) {
+    for child in node.children() {
+        visitor.visit_child(child);
+    }
+}
+
+// Same as `Visitor` but can change the tree as it goes. `visit_children_mut`
+// gets the whole list so a pass can add or remove nodes, and `visit_child_mut`
+// can swap a node for something else, like wrapping it in another element.
+pub trait VisitorMut {
+    fn visit_children_mut(&mut self, children: &mut Vec<HTMLChildNode>) {
+        walk_children_mut(self, children);
+    }
+
+    fn visit_child_mut(&mut self, node: &mut HTMLChildNode) {
+        walk_child_mut(self, node);
+    }
+
+    fn visit_parent_mut(&mut self, node: &mut ParentNode) {
+        walk_parent_mut(self, node);
+    }
+
+    fn visit_leaf_mut(&mut self, _node: &mut LeafNode) {}
+
+    fn visit_html_node_mut(&mut self, _node: &mut HTMLNode) {}
+
+    fn visit_raw_mut(&mut self, _html: &mut String) {}
+}
+
+pub fn walk_children_mut<V: VisitorMut + ?Sized>(visitor: &mut V, children: &mut [HTMLChildNode]) {
+    for child in children {
+        visitor.visit_child_mut(child);
+    }
+}
+
+pub fn walk_child_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut HTMLChildNode) {
+    match node {
+        HTMLChildNode::Parent(p) => visitor.visit_parent_mut(p),
+        HTMLChildNode::Leaf(l) => visitor.visit_leaf_mut(l),
+        HTMLChildNode::HTML(h) => visitor.visit_html_node_mut(h),
+        HTMLChildNode::Raw(r) => visitor.visit_raw_mut(r),
+    }
+}
+
+pub fn walk_parent_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut ParentNode) {
+    visitor.visit_children_mut(node.children_mut());
+}
+
+// Replaces the node with whatever `wrap` builds out of it, handy for passes
+// that wrap an element in another one.
+pub fn replace_with<F>(node: &mut HTMLChildNode, wrap: F)
+where
+    F: FnOnce(HTMLChildNode) -> HTMLChildNode,
+{
+    let old = mem::replace(node, HTMLChildNode::Raw(String::new()));
+    *node = wrap(old);
+}
+
+// Iterates over every node under a parent in document order, the parent
+// itself isn't included.
+pub struct Descendants<'a> {
+    stack: Vec<slice::Iter<'a, HTMLChildNode>>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a HTMLChildNode;
+
+    fn next(&mut self) -> Option<&'a HTMLChildNode> {
+        loop {
+            let top = self.stack.last_mut()?;
+            let Some(node) = top.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            if let HTMLChildNode::Parent(p) = node {
+                self.stack.push(p.children().iter());
+            }
+
+            return Some(node);
+        }
+    }
+}
+
+impl ParentNode {
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants {
+            stack: vec![self.children().iter()],
+        }
+    }
+
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_parent(self);
+    }
+
+    pub fn accept_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.visit_parent_mut(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::{HTMLAttributes, ToHtmlString};
+    use crate::nodes::test_nodes::page;
+
+    use super::*;
+
+    struct ImageUrls(Vec<String>);
+
+    impl Visitor for ImageUrls {
+        fn visit_leaf(&mut self, node: &LeafNode) {
+            if node.tag.as_deref() == Some("img") {
+                if let Some(src) = node.attributes.as_ref().and_then(|a| a.get("src")) {
+                    self.0.push(String::from(src));
+                }
+            }
+        }
+    }
+
+    struct ExternalLinks;
+
+    impl VisitorMut for ExternalLinks {
+        fn visit_leaf_mut(&mut self, node: &mut LeafNode) {
+            let Some(attributes) = node.attributes.as_mut() else {
+                return;
+            };
+            if node.tag.as_deref() == Some("a")
+                && attributes.get("href").unwrap_or("").starts_with("https://")
+            {
+                attributes.set("target", "_blank");
+            }
+        }
+    }
+
+    struct ScrollTables;
+
+    impl VisitorMut for ScrollTables {
+        fn visit_child_mut(&mut self, node: &mut HTMLChildNode) {
+            // Walk first so the table that was just wrapped isn't found again.
+            walk_child_mut(self, node);
+
+            if matches!(node, HTMLChildNode::Parent(p) if p.tag() == "table") {
+                replace_with(node, |table| {
+                    HTMLChildNode::Parent(ParentNode::new(
+                        "div",
+                        vec![table],
+                        Some(HTMLAttributes::from_pairs(&[("class", "scroll")])),
+                    ))
+                });
+            }
+        }
+    }
+
+    struct DropRaw;
+
+    impl VisitorMut for DropRaw {
+        fn visit_children_mut(&mut self, children: &mut Vec<HTMLChildNode>) {
+            children.retain(|c| !matches!(c, HTMLChildNode::Raw(_)));
+            walk_children_mut(self, children);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut urls = ImageUrls(vec![]);
+        page().accept(&mut urls);
+
+        assert_eq!(urls.0, vec![String::from("cat.png")]);
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        // What each visitor changes in the html of the page.
+        let test_cases: Vec<(&str, Box<dyn VisitorMut>, &str, &str)> = vec![
+            (
+                "Test external links open in a new tab",
+                Box::new(ExternalLinks),
+                "rel=\"me external\">Rust",
+                "rel=\"me external\" target=\"_blank\">Rust",
+            ),
+            (
+                "Test tables are wrapped in a scroll div",
+                Box::new(ScrollTables),
+                "<table><tr></tr></table>",
+                "<div class=\"scroll\"><table><tr></tr></table></div>",
+            ),
+            (
+                "Test children can be removed",
+                Box::new(DropRaw),
+                "<img src=\"raw.png\">",
+                "",
+            ),
+        ];
+
+        for (title, mut visitor, from, to) in test_cases.into_iter() {
+            let expected = page().into_html().replace(from, to);
+            let mut node = page();
+            node.accept_mut(visitor.as_mut());
+
+            assert_eq!(
+                node.into_html(),
+                expected,
+                "\"{}\" test failed and expexted: {}",
+                title,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_descendants() {
+        let node = page();
+        let tags: Vec<&str> = node
+            .descendants()
+            .map(|n| match n {
+                HTMLChildNode::Parent(p) => p.tag(),
+                HTMLChildNode::Leaf(l) => l.tag.as_deref().unwrap_or("#text"),
+                HTMLChildNode::HTML(_) => "#html",
+                HTMLChildNode::Raw(_) => "#raw",
+            })
+            .collect();
+
+        assert_eq!(
+            tags,
+            vec![
+                "nav", "#text", "a", "a", "a", "main", "h1", "p", "#text", "b", "#text", "pre",
+                "ul", "li", "a", "li", "#text", "ol", "li", "img", "table", "tr", "#raw"
+            ]
+        );
+    }
+}
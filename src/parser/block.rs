@@ -0,0 +1,747 @@
+use super::raw_html::{html_block_start, HtmlBlockEnd};
+
+// Block level markdown. The parser works line by line, each kind of block
+// knows how to recognise its first line and how far it extends. Blocks that
+// contain other blocks re-run the parser over their own (dedented) lines.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Block {
+    Paragraph(String),
+    Heading {
+        level: usize,
+        text: String,
+    },
+    Code {
+        info: Option<String>,
+        content: String,
+    },
+    FootnoteDefinition {
+        label: String,
+        children: Vec<Block>,
+    },
+    // Each item is the list of blocks inside of it. A tight list has no blank
+    // lines between its items or their blocks, so its paragraphs are rendered
+    // without <p> tags.
+    List {
+        ordered: bool,
+        start: usize,
+        tight: bool,
+        items: Vec<Vec<Block>>,
+    },
+    BlockQuote(Vec<Block>),
+    // Raw html lines, kept exactly as they were written.
+    Html(String),
+    // A blockquote starting with `[!NOTE]`, `[!WARNING]` etc. Any text after
+    // the marker on the same line is used as the title.
+    Callout {
+        kind: CalloutKind,
+        title: Option<String>,
+        children: Vec<Block>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl CalloutKind {
+    fn from_marker(marker: &str) -> Option<CalloutKind> {
+        match marker.to_ascii_uppercase().as_str() {
+            "NOTE" => Some(CalloutKind::Note),
+            "TIP" => Some(CalloutKind::Tip),
+            "IMPORTANT" => Some(CalloutKind::Important),
+            "WARNING" => Some(CalloutKind::Warning),
+            "CAUTION" => Some(CalloutKind::Caution),
+            _ => None,
+        }
+    }
+
+    // Used for the `callout-<name>` class.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Important => "important",
+            CalloutKind::Warning => "warning",
+            CalloutKind::Caution => "caution",
+        }
+    }
+
+    pub fn default_title(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Important => "Important",
+            CalloutKind::Warning => "Warning",
+            CalloutKind::Caution => "Caution",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "ℹ",
+            CalloutKind::Tip => "💡",
+            CalloutKind::Important => "❗",
+            CalloutKind::Warning => "⚠",
+            CalloutKind::Caution => "🛑",
+        }
+    }
+}
+
+pub fn parse_blocks(markdown: &str) -> Vec<Block> {
+    let lines: Vec<&str> = markdown.lines().collect();
+
+    parse_lines(&lines)
+        .into_iter()
+        .map(|(block, _, _)| block)
+        .collect()
+}
+
+// Parses the lines into blocks along with the range of lines each block
+// covers, the ranges are used to find blank lines between blocks.
+pub(crate) fn parse_lines(lines: &[&str]) -> Vec<(Block, usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if is_blank(lines[i]) {
+            i += 1;
+            continue;
+        }
+
+        let (block, next) = parse_heading(lines, i)
+            .or_else(|| parse_fenced_code(lines, i))
+            .or_else(|| parse_html_block(lines, i))
+            .or_else(|| parse_footnote_definition(lines, i))
+            .or_else(|| parse_list(lines, i))
+            .or_else(|| parse_block_quote(lines, i))
+            .unwrap_or_else(|| parse_paragraph(lines, i));
+
+        blocks.push((block, i, next));
+        i = next;
+    }
+
+    blocks
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// Whether the line would start a new block, which ends any paragraph before it.
+pub(crate) fn starts_block(line: &str) -> bool {
+    heading(line).is_some()
+        || fence(line).is_some()
+        || footnote_label(line).is_some()
+        || quote_content(line).is_some()
+        || (indent_width(line) <= 3 && html_block_start(strip_indent(line, 3), true).is_some())
+        || list_marker(line)
+            .map(|m| !m.ordered || m.start == 1)
+            .unwrap_or(false)
+}
+
+// Removes up to `width` columns of leading whitespace, a tab counts as four.
+pub fn strip_indent(line: &str, width: usize) -> &str {
+    let mut columns = 0;
+
+    for (i, c) in line.char_indices() {
+        if columns >= width {
+            return &line[i..];
+        }
+        match c {
+            ' ' => columns += 1,
+            '\t' => columns += 4,
+            _ => return &line[i..],
+        }
+    }
+
+    ""
+}
+
+// The number of columns of leading whitespace, a tab counts as four.
+pub fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = strip_indent(line, 3);
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+
+    if level == 0 || level > 6 {
+        return None;
+    }
+
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // A closing sequence of #'s is optional and not part of the heading.
+    let text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+
+    Some((level, text))
+}
+
+fn parse_heading(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    let (level, text) = heading(lines[i])?;
+
+    Some((
+        Block::Heading {
+            level,
+            text: String::from(text),
+        },
+        i + 1,
+    ))
+}
+
+// Returns the fence character, its length and the info string.
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = strip_indent(line, 3);
+    let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = trimmed.chars().take_while(|c| *c == fence_char).count();
+
+    if length < 3 {
+        return None;
+    }
+
+    let info = trimmed[length..].trim();
+    if fence_char == '`' && info.contains('`') {
+        return None;
+    }
+
+    Some((fence_char, length, info))
+}
+
+fn parse_fenced_code(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    let (fence_char, length, info) = fence(lines[i])?;
+    let indent = indent_width(lines[i]);
+    let mut content = Vec::new();
+    let mut next = i + 1;
+
+    while next < lines.len() {
+        let line = lines[next];
+        next += 1;
+
+        let closes = fence(line)
+            .map(|(c, l, rest)| c == fence_char && l >= length && rest.is_empty())
+            .unwrap_or(false);
+        if closes {
+            break;
+        }
+
+        content.push(strip_indent(line, indent));
+    }
+
+    let mut content = content.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+
+    Some((
+        Block::Code {
+            info: info.split_whitespace().next().map(String::from),
+            content,
+        },
+        next,
+    ))
+}
+
+fn parse_html_block(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    if indent_width(lines[i]) > 3 {
+        return None;
+    }
+
+    let end = html_block_start(strip_indent(lines[i], 3), false)?;
+    let mut next = i;
+
+    while next < lines.len() {
+        let line = lines[next];
+
+        match end {
+            HtmlBlockEnd::BlankLine if is_blank(line) => break,
+            HtmlBlockEnd::Contains(closing) if line.to_ascii_lowercase().contains(closing) => {
+                next += 1;
+                break;
+            }
+            _ => next += 1,
+        }
+    }
+
+    Some((Block::Html(lines[i..next].join("\n")), next))
+}
+
+// Returns the label and the text after the colon of a `[^label]: text` line.
+fn footnote_label(line: &str) -> Option<(&str, &str)> {
+    let trimmed = strip_indent(line, 3);
+    let rest = trimmed.strip_prefix("[^")?;
+    let close = rest.find("]:")?;
+    let label = &rest[..close];
+
+    if label.is_empty() || label.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((label, &rest[close + 2..]))
+}
+
+// A footnote definition runs until a line that isn't indented, apart from
+// lazy continuation lines directly following paragraph text.
+fn parse_footnote_definition(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    let (label, first) = footnote_label(lines[i])?;
+    let mut content = vec![first.trim_start()];
+    let mut next = i + 1;
+
+    while next < lines.len() {
+        let line = lines[next];
+
+        if is_blank(line) {
+            let continues = lines[next..]
+                .iter()
+                .find(|l| !is_blank(l))
+                .map(|l| indent_width(l) >= 4)
+                .unwrap_or(false);
+            if !continues {
+                break;
+            }
+            content.push("");
+        } else if indent_width(line) >= 4 {
+            content.push(strip_indent(line, 4));
+        } else if !starts_block(line) && content.last().map(|l| !is_blank(l)).unwrap_or(false) {
+            content.push(line);
+        } else {
+            break;
+        }
+
+        next += 1;
+    }
+
+    Some((
+        Block::FootnoteDefinition {
+            label: String::from(label),
+            children: parse_blocks(&content.join("\n")),
+        },
+        next,
+    ))
+}
+
+#[derive(Debug, PartialEq)]
+struct ListMarker<'a> {
+    ordered: bool,
+    start: usize,
+    // The bullet character, or the `.` or `)` after an ordered number. Items
+    // only belong to the same list when this matches.
+    delimiter: char,
+    // The column the item's content starts at, lines indented this far
+    // belong to the item.
+    content_indent: usize,
+    content: &'a str,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker<'_>> {
+    let indent = indent_width(line);
+    if indent > 3 {
+        return None;
+    }
+
+    let trimmed = strip_indent(line, 3);
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+
+    let (ordered, start, delimiter, marker_width) = if digits > 0 {
+        if digits > 9 {
+            return None;
+        }
+        let delimiter = trimmed[digits..].chars().next()?;
+        if delimiter != '.' && delimiter != ')' {
+            return None;
+        }
+        (true, trimmed[..digits].parse().ok()?, delimiter, digits + 1)
+    } else {
+        let bullet = trimmed.chars().next()?;
+        if !"-*+".contains(bullet) {
+            return None;
+        }
+        (false, 1, bullet, 1)
+    };
+
+    let after_marker = &trimmed[marker_width..];
+    if !after_marker.is_empty() && !after_marker.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // One to four spaces separate the marker from the content. With more than
+    // that the content is indented code, which only takes a single space.
+    let spaces = indent_width(after_marker);
+    let spaces = if spaces == 0 || spaces > 4 || is_blank(after_marker) {
+        1
+    } else {
+        spaces
+    };
+
+    Some(ListMarker {
+        ordered,
+        start,
+        delimiter,
+        content_indent: indent + marker_width + spaces,
+        content: strip_indent(after_marker, spaces),
+    })
+}
+
+// Collects list items for as long as the following items use the same kind of
+// marker. Each item's lines are dedented and parsed again as blocks, which is
+// how nested lists end up inside of their parent item.
+fn parse_list(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    let first = list_marker(lines[i])?;
+    let mut items = Vec::new();
+    let mut tight = true;
+    let mut next = i;
+
+    while let Some(marker) = lines.get(next).and_then(|l| list_marker(l)) {
+        if marker.ordered != first.ordered || marker.delimiter != first.delimiter {
+            break;
+        }
+
+        let mut item_lines = vec![marker.content];
+        let mut end = next + 1;
+        next += 1;
+
+        while next < lines.len() {
+            let line = lines[next];
+
+            if is_blank(line) {
+                item_lines.push("");
+            } else if indent_width(line) >= marker.content_indent {
+                item_lines.push(strip_indent(line, marker.content_indent));
+            } else if !starts_block(line)
+                && list_marker(line).is_none()
+                && item_lines.last().map(|l| !is_blank(l)).unwrap_or(false)
+            {
+                // Lazy continuation of a paragraph in the item.
+                item_lines.push(line.trim_start());
+            } else {
+                break;
+            }
+
+            next += 1;
+            if !is_blank(line) {
+                end = next;
+            }
+        }
+
+        // Trailing blank lines aren't part of the item.
+        item_lines.truncate(item_lines.len() - (next - end));
+
+        let children = parse_lines(&item_lines);
+        if children.windows(2).any(|pair| pair[0].2 < pair[1].1) {
+            tight = false;
+        }
+
+        items.push(children.into_iter().map(|(block, _, _)| block).collect());
+
+        let continues = lines
+            .get(next)
+            .and_then(|l| list_marker(l))
+            .map(|m| m.ordered == first.ordered && m.delimiter == first.delimiter)
+            .unwrap_or(false);
+        if !continues {
+            next = end;
+            break;
+        }
+        if end < next {
+            tight = false;
+        }
+    }
+
+    Some((
+        Block::List {
+            ordered: first.ordered,
+            start: first.start,
+            tight,
+            items,
+        },
+        next,
+    ))
+}
+
+// Strips the `>` marker and the optional space after it.
+fn quote_content(line: &str) -> Option<&str> {
+    if indent_width(line) > 3 {
+        return None;
+    }
+
+    let rest = strip_indent(line, 3).strip_prefix('>')?;
+
+    Some(rest.strip_prefix([' ', '\t']).unwrap_or(rest))
+}
+
+// A blockquote runs until a blank line, or a line without a `>` that can't
+// lazily continue the paragraph above it. The quoted lines are parsed again
+// so quotes can hold lists, code and other quotes.
+fn parse_block_quote(lines: &[&str], i: usize) -> Option<(Block, usize)> {
+    let mut content = vec![quote_content(lines[i])?];
+    let mut next = i + 1;
+
+    while next < lines.len() {
+        let line = lines[next];
+
+        match quote_content(line) {
+            Some(c) => content.push(c),
+            None if !is_blank(line)
+                && !starts_block(line)
+                && content
+                    .last()
+                    .map(|l| !is_blank(l) && fence(l).is_none())
+                    .unwrap_or(false) =>
+            {
+                content.push(line.trim_start())
+            }
+            None => break,
+        }
+
+        next += 1;
+    }
+
+    if let Some((kind, title)) = callout_marker(content[0]) {
+        return Some((
+            Block::Callout {
+                kind,
+                title: title.map(String::from),
+                children: parse_blocks(&content[1..].join("\n")),
+            },
+            next,
+        ));
+    }
+
+    Some((Block::BlockQuote(parse_blocks(&content.join("\n"))), next))
+}
+
+// Reads a `[!WARNING] Optional title` line.
+fn callout_marker(line: &str) -> Option<(CalloutKind, Option<&str>)> {
+    let rest = line.trim().strip_prefix("[!")?;
+    let close = rest.find(']')?;
+    let kind = CalloutKind::from_marker(&rest[..close])?;
+    let title = rest[close + 1..].trim();
+
+    Some((kind, if title.is_empty() { None } else { Some(title) }))
+}
+
+fn parse_paragraph(lines: &[&str], i: usize) -> (Block, usize) {
+    let mut content = vec![lines[i].trim()];
+    let mut next = i + 1;
+
+    while next < lines.len() && !is_blank(lines[next]) && !starts_block(lines[next]) {
+        content.push(lines[next].trim());
+        next += 1;
+    }
+
+    (Block::Paragraph(content.join("\n")), next)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_blocks() {
+        let test_cases = vec![
+            (
+                "Test paragraphs are split on blank lines",
+                "First paragraph\nstill first\n\nSecond paragraph",
+                vec![
+                    Block::Paragraph(String::from("First paragraph\nstill first")),
+                    Block::Paragraph(String::from("Second paragraph")),
+                ],
+            ),
+            (
+                "Test headings",
+                "# Title\n## Sub title ##\n####### not a heading",
+                vec![
+                    Block::Heading {
+                        level: 1,
+                        text: String::from("Title"),
+                    },
+                    Block::Heading {
+                        level: 2,
+                        text: String::from("Sub title"),
+                    },
+                    Block::Paragraph(String::from("####### not a heading")),
+                ],
+            ),
+            (
+                "Test fenced code keeps blank lines",
+                "```rust\nfn main() {\n\n}\n```\nafter",
+                vec![
+                    Block::Code {
+                        info: Some(String::from("rust")),
+                        content: String::from("fn main() {\n\n}\n"),
+                    },
+                    Block::Paragraph(String::from("after")),
+                ],
+            ),
+            (
+                "Test footnote definitions with indented continuation",
+                "Body[^1]\n\n[^1]: The first line\ncontinues lazily\n\n    Second paragraph\n\nNot part of it",
+                vec![
+                    Block::Paragraph(String::from("Body[^1]")),
+                    Block::FootnoteDefinition {
+                        label: String::from("1"),
+                        children: vec![
+                            Block::Paragraph(String::from("The first line\ncontinues lazily")),
+                            Block::Paragraph(String::from("Second paragraph")),
+                        ],
+                    },
+                    Block::Paragraph(String::from("Not part of it")),
+                ],
+            ),
+            (
+                "Test nested mixed list",
+                "- one\n- two\n  1. a\n  2. b\n- three",
+                vec![Block::List {
+                    ordered: false,
+                    start: 1,
+                    tight: true,
+                    items: vec![
+                        vec![Block::Paragraph(String::from("one"))],
+                        vec![
+                            Block::Paragraph(String::from("two")),
+                            Block::List {
+                                ordered: true,
+                                start: 1,
+                                tight: true,
+                                items: vec![
+                                    vec![Block::Paragraph(String::from("a"))],
+                                    vec![Block::Paragraph(String::from("b"))],
+                                ],
+                            },
+                        ],
+                        vec![Block::Paragraph(String::from("three"))],
+                    ],
+                }],
+            ),
+            (
+                "Test loose list with multiple paragraphs and lazy lines",
+                "7) first\nlazy line\n\n   second paragraph\n8) next\n\nafter",
+                vec![
+                    Block::List {
+                        ordered: true,
+                        start: 7,
+                        tight: false,
+                        items: vec![
+                            vec![
+                                Block::Paragraph(String::from("first\nlazy line")),
+                                Block::Paragraph(String::from("second paragraph")),
+                            ],
+                            vec![Block::Paragraph(String::from("next"))],
+                        ],
+                    },
+                    Block::Paragraph(String::from("after")),
+                ],
+            ),
+            (
+                "Test blank lines between items make the list loose",
+                "- a\n\n- b\n* c",
+                vec![
+                    Block::List {
+                        ordered: false,
+                        start: 1,
+                        tight: false,
+                        items: vec![
+                            vec![Block::Paragraph(String::from("a"))],
+                            vec![Block::Paragraph(String::from("b"))],
+                        ],
+                    },
+                    Block::List {
+                        ordered: false,
+                        start: 1,
+                        tight: true,
+                        items: vec![vec![Block::Paragraph(String::from("c"))]],
+                    },
+                ],
+            ),
+            (
+                "Test only lists starting at one interrupt a paragraph",
+                "The year\n1999. was long\n1. but this is a list",
+                vec![
+                    Block::Paragraph(String::from("The year\n1999. was long")),
+                    Block::List {
+                        ordered: true,
+                        start: 1,
+                        tight: true,
+                        items: vec![vec![Block::Paragraph(String::from("but this is a list"))]],
+                    },
+                ],
+            ),
+            (
+                "Test nested blockquotes with a list and lazy line",
+                "> Quote\nlazy\n>\n> - item\n>\n> > inner\n\nout",
+                vec![
+                    Block::BlockQuote(vec![
+                        Block::Paragraph(String::from("Quote\nlazy")),
+                        Block::List {
+                            ordered: false,
+                            start: 1,
+                            tight: true,
+                            items: vec![vec![Block::Paragraph(String::from("item"))]],
+                        },
+                        Block::BlockQuote(vec![Block::Paragraph(String::from("inner"))]),
+                    ]),
+                    Block::Paragraph(String::from("out")),
+                ],
+            ),
+            (
+                "Test callouts with and without a title",
+                "> [!warning]\n> Careful\n\n> [!TIP] Pro tip\n> Use `cargo`\n\n> [!UNKNOWN]\n> plain",
+                vec![
+                    Block::Callout {
+                        kind: CalloutKind::Warning,
+                        title: None,
+                        children: vec![Block::Paragraph(String::from("Careful"))],
+                    },
+                    Block::Callout {
+                        kind: CalloutKind::Tip,
+                        title: Some(String::from("Pro tip")),
+                        children: vec![Block::Paragraph(String::from("Use `cargo`"))],
+                    },
+                    Block::BlockQuote(vec![Block::Paragraph(String::from(
+                        "[!UNKNOWN]\nplain",
+                    ))]),
+                ],
+            ),
+            (
+                "Test html blocks",
+                "<div class=\"widget\">\n\n*markdown*\n\n</div>\n\n<!-- start\n\nend -->\nText <span>inline</span>",
+                vec![
+                    Block::Html(String::from("<div class=\"widget\">")),
+                    Block::Paragraph(String::from("*markdown*")),
+                    Block::Html(String::from("</div>")),
+                    Block::Html(String::from("<!-- start\n\nend -->")),
+                    Block::Paragraph(String::from("Text <span>inline</span>")),
+                ],
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &parse_blocks(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use super::block::Block;
+
+// Keeps track of the footnote definitions on a page and the references made
+// to them. Footnotes are numbered in the order they are first referenced,
+// not the order they are defined in.
+#[derive(Debug, Default)]
+pub struct Footnotes {
+    definitions: Vec<(String, Vec<Block>)>,
+    order: Vec<String>,
+    reference_counts: HashMap<String, usize>,
+}
+
+// A single reference to a footnote. `index` counts the references made to the
+// same footnote starting from 1, so every reference gets a unique id.
+#[derive(Debug, PartialEq)]
+pub struct FootnoteReference {
+    pub number: usize,
+    pub index: usize,
+}
+
+impl FootnoteReference {
+    pub fn id(&self) -> String {
+        reference_id(self.number, self.index)
+    }
+
+    pub fn href(&self) -> String {
+        format!("#{}", definition_id(self.number))
+    }
+}
+
+pub fn definition_id(number: usize) -> String {
+    format!("fn-{}", number)
+}
+
+pub fn reference_id(number: usize, index: usize) -> String {
+    if index == 1 {
+        format!("fnref-{}", number)
+    } else {
+        format!("fnref-{}-{}", number, index)
+    }
+}
+
+impl Footnotes {
+    // Adds a definition, when a label is defined twice the first one wins.
+    pub fn define(&mut self, label: &str, children: Vec<Block>) {
+        if self.definition(label).is_none() {
+            self.definitions.push((String::from(label), children));
+        }
+    }
+
+    pub fn definition(&self, label: &str) -> Option<&Vec<Block>> {
+        self.definitions
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, children)| children)
+    }
+
+    // Records a reference to the label, returns None when there is no
+    // definition for it.
+    pub fn reference(&mut self, label: &str) -> Option<FootnoteReference> {
+        self.definition(label)?;
+
+        let number = match self.order.iter().position(|l| l == label) {
+            Some(position) => position + 1,
+            None => {
+                self.order.push(String::from(label));
+                self.order.len()
+            }
+        };
+
+        let count = self
+            .reference_counts
+            .entry(String::from(label))
+            .or_insert(0);
+        *count += 1;
+
+        Some(FootnoteReference {
+            number,
+            index: *count,
+        })
+    }
+
+    // The label of the footnote with the given number, if it has been
+    // referenced.
+    pub fn label(&self, number: usize) -> Option<&str> {
+        self.order.get(number - 1).map(|l| l.as_str())
+    }
+
+    pub fn reference_count(&self, label: &str) -> usize {
+        self.reference_counts.get(label).copied().unwrap_or(0)
+    }
+
+    // Labels that were defined but never referenced, in definition order.
+    pub fn unused(&self) -> Vec<&str> {
+        self.definitions
+            .iter()
+            .map(|(l, _)| l.as_str())
+            .filter(|l| !self.order.iter().any(|o| o == l))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_footnote_references() {
+        let mut footnotes = Footnotes::default();
+        footnotes.define("a", vec![]);
+        footnotes.define("b", vec![]);
+        footnotes.define("c", vec![]);
+
+        let test_cases = vec![
+            (
+                "Test first reference is numbered first",
+                "b",
+                Some(FootnoteReference {
+                    number: 1,
+                    index: 1,
+                }),
+            ),
+            (
+                "Test second footnote",
+                "a",
+                Some(FootnoteReference {
+                    number: 2,
+                    index: 1,
+                }),
+            ),
+            (
+                "Test repeated reference keeps its number",
+                "b",
+                Some(FootnoteReference {
+                    number: 1,
+                    index: 2,
+                }),
+            ),
+            ("Test undefined footnote", "missing", None),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &footnotes.reference(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+
+        assert_eq!(footnotes.unused(), vec!["c"]);
+        assert_eq!(footnotes.label(1), Some("b"));
+        assert_eq!(footnotes.reference_count("b"), 2);
+    }
+}
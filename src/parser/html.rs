@@ -0,0 +1,600 @@
+use std::{fs, io, path::Path};
+
+use crate::nodes::html_node::{HTMLAttributes, HTMLChildNode};
+use crate::nodes::leaf_node::{LeafNode, VOID_ELEMENTS};
+use crate::nodes::parent_node::ParentNode;
+use crate::nodes::visit::{walk_children_mut, walk_parent_mut, VisitorMut};
+
+// Elements whose content isn't markup, it is kept as raw html so it's written
+// back out exactly as it was.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+// Elements whose content is text but can hold character references.
+const ESCAPABLE_RAW_TEXT_ELEMENTS: [&str; 2] = ["textarea", "title"];
+
+// Starting one of these closes an open <p>, like browsers do.
+pub(crate) const CLOSES_PARAGRAPH: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+// Named references that show up in real pages, anything else is left as is.
+const NAMED_REFERENCES: [(&str, char); 32] = [
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("hellip", '…'),
+    ("mdash", '—'),
+    ("ndash", '–'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("bull", '•'),
+    ("middot", '·'),
+    ("times", '×'),
+    ("divide", '÷'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("euro", '€'),
+    ("pound", '£'),
+    ("yen", '¥'),
+    ("cent", '¢'),
+    ("sect", '§'),
+    ("para", '¶'),
+    ("larr", '←'),
+    ("rarr", '→'),
+];
+
+// Parses html into nodes. Like a browser it never fails, unclosed elements
+// are closed at the end, stray closing tags are dropped and the end tags html
+// lets you leave out (</p>, </li>, </td>, ...) are filled in. Comments and
+// doctypes are kept as raw html.
+pub fn parse_html(html: &str) -> Vec<HTMLChildNode> {
+    let mut builder = TreeBuilder::default();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map(|e| e + 3).unwrap_or(comment.len());
+            builder.raw(&rest[..4 + end]);
+            rest = &rest[4 + end..];
+            continue;
+        }
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map(|e| e + 1).unwrap_or(rest.len());
+            builder.raw(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        if let Some(tag) = rest.strip_prefix("</") {
+            if tag.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let end = tag.find('>').map(|e| e + 1).unwrap_or(tag.len());
+                builder.close(&tag_name(tag));
+                rest = &tag[end..];
+                continue;
+            }
+        }
+
+        if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            if let Some(tag) = start_tag(rest) {
+                rest = &rest[tag.len..];
+
+                if RAW_TEXT_ELEMENTS.contains(&tag.name.as_str())
+                    || ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&tag.name.as_str())
+                {
+                    let end = find_closing_tag(rest, &tag.name);
+                    builder.raw_text_element(tag, &rest[..end]);
+                    rest = &rest[end..];
+                    // Skip the closing tag itself.
+                    rest = rest.find('>').map(|e| &rest[e + 1..]).unwrap_or("");
+                } else {
+                    builder.open(tag);
+                }
+                continue;
+            }
+        }
+
+        // Always take at least one character so a `<` that isn't a tag
+        // becomes text.
+        let first = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+        let end = rest[first..]
+            .find('<')
+            .map(|e| e + first)
+            .unwrap_or(rest.len());
+        builder.text(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    builder.finish()
+}
+
+pub fn parse_html_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<HTMLChildNode>> {
+    Ok(parse_html(&fs::read_to_string(path)?))
+}
+
+// Replaces raw html nodes, like the html blocks from markdown, with the
+// nodes they parse into so later passes can see inside of them.
+pub fn expand_raw_html(nodes: &mut Vec<HTMLChildNode>) {
+    ExpandRawHtml.visit_children_mut(nodes);
+}
+
+struct ExpandRawHtml;
+
+impl VisitorMut for ExpandRawHtml {
+    fn visit_children_mut(&mut self, children: &mut Vec<HTMLChildNode>) {
+        if children.iter().any(|c| matches!(c, HTMLChildNode::Raw(_))) {
+            *children = children
+                .drain(..)
+                .flat_map(|child| match child {
+                    HTMLChildNode::Raw(html) => parse_html(&html),
+                    other => vec![other],
+                })
+                .collect();
+        }
+        walk_children_mut(self, children);
+    }
+
+    fn visit_parent_mut(&mut self, node: &mut ParentNode) {
+        // Scripts and styles are meant to stay raw.
+        if !RAW_TEXT_ELEMENTS.contains(&node.tag()) {
+            walk_parent_mut(self, node);
+        }
+    }
+}
+
+struct StartTag {
+    name: String,
+    attributes: HTMLAttributes,
+    self_closing: bool,
+    // How much of the input the tag took up.
+    len: usize,
+}
+
+fn tag_name(text: &str) -> String {
+    text.chars()
+        .take_while(|c| !c.is_whitespace() && *c != '/' && *c != '>')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+// Reads `<name attr=value ...>`, returning None when the tag never ends so
+// the text is kept as it is.
+fn start_tag(text: &str) -> Option<StartTag> {
+    let name = tag_name(&text[1..]);
+    let mut attributes = HTMLAttributes::new();
+    let mut rest = &text[1 + name.len()..];
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace());
+
+        if let Some(after) = rest.strip_prefix("/>") {
+            return Some(StartTag {
+                name,
+                attributes,
+                self_closing: true,
+                len: text.len() - after.len(),
+            });
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Some(StartTag {
+                name,
+                attributes,
+                self_closing: false,
+                len: text.len() - after.len(),
+            });
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            rest = after;
+            continue;
+        }
+        if rest.is_empty() {
+            return None;
+        }
+
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len())
+            .max(1);
+        // Names keep their case for svg, attribute lookups ignore it anyway.
+        let attribute = String::from(&rest[..name_end]);
+        rest = rest[name_end..].trim_start_matches(|c: char| c.is_whitespace());
+
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start_matches(|c: char| c.is_whitespace());
+                let (raw, after) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote)?;
+                        (&value[1..end + 1], &value[end + 2..])
+                    }
+                    _ => {
+                        let end = value
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = after;
+                Some(decode_entities(raw))
+            }
+            None => None,
+        };
+
+        // The first one wins when an attribute is repeated.
+        if !attributes.contains(&attribute) {
+            match value {
+                Some(v) => attributes.set(&attribute, &v),
+                None => attributes.set_bool(&attribute, true),
+            }
+        }
+    }
+}
+
+// Where the closing tag of a raw text element starts, or the end of the
+// input if it's never closed.
+fn find_closing_tag(text: &str, name: &str) -> usize {
+    let lower = text.to_ascii_lowercase();
+    let closing = format!("</{}", name);
+
+    let mut from = 0;
+    while let Some(i) = lower[from..].find(&closing) {
+        let end = from + i + closing.len();
+        if lower[end..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            || end == lower.len()
+        {
+            return from + i;
+        }
+        from = end;
+    }
+
+    text.len()
+}
+
+// Decodes character references, ones that aren't known are kept as text.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        // Only looks as far as the longest reference can go, so text with lots
+        // of `&` doesn't get scanned to the end every time.
+        let end = rest.bytes().take(34).position(|b| b == b';');
+        let character = end.and_then(|end| {
+            let body = &rest[1..end];
+            match body.strip_prefix('#') {
+                Some(number) => match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                }
+                .and_then(char::from_u32)
+                .filter(|c| *c != '\0'),
+                None => NAMED_REFERENCES
+                    .iter()
+                    .find(|(name, _)| *name == body)
+                    .map(|(_, c)| *c),
+            }
+        });
+
+        match (character, end) {
+            (Some(c), Some(end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+// Elements that get closed when `next` is opened inside of them.
+fn closed_by(open: &str, next: &str) -> bool {
+    match open {
+        "p" => CLOSES_PARAGRAPH.contains(&next),
+        "li" => next == "li",
+        "dt" | "dd" => next == "dt" || next == "dd",
+        "option" => next == "option" || next == "optgroup",
+        "tr" => next == "tr",
+        "td" | "th" => ["td", "th", "tr"].contains(&next),
+        "thead" | "tbody" => next == "tbody" || next == "tfoot",
+        _ => false,
+    }
+}
+
+struct Frame {
+    tag: String,
+    attributes: Option<HTMLAttributes>,
+    children: Vec<HTMLChildNode>,
+}
+
+// Keeps the elements that are still open, the first frame holds the top
+// level nodes.
+struct TreeBuilder {
+    stack: Vec<Frame>,
+}
+
+impl Default for TreeBuilder {
+    fn default() -> TreeBuilder {
+        TreeBuilder {
+            stack: vec![Frame {
+                tag: String::new(),
+                attributes: None,
+                children: vec![],
+            }],
+        }
+    }
+}
+
+fn non_empty(attributes: HTMLAttributes) -> Option<HTMLAttributes> {
+    if attributes.is_empty() {
+        None
+    } else {
+        Some(attributes)
+    }
+}
+
+impl TreeBuilder {
+    fn children(&mut self) -> &mut Vec<HTMLChildNode> {
+        &mut self
+            .stack
+            .last_mut()
+            .expect("the root frame is never popped")
+            .children
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = decode_entities(text);
+
+        // Text split up by stray tags ends up in a single node.
+        if let Some(HTMLChildNode::Leaf(LeafNode {
+            tag: None, value, ..
+        })) = self.children().last_mut()
+        {
+            value.push_str(&text);
+            return;
+        }
+
+        self.children().push(HTMLChildNode::Leaf(LeafNode {
+            tag: None,
+            value: text,
+            attributes: None,
+        }));
+    }
+
+    fn raw(&mut self, html: &str) {
+        self.children().push(HTMLChildNode::Raw(String::from(html)));
+    }
+
+    fn open(&mut self, tag: StartTag) {
+        while self.stack.len() > 1 && closed_by(&self.stack[self.stack.len() - 1].tag, &tag.name) {
+            self.pop();
+        }
+
+        if VOID_ELEMENTS.contains(&tag.name.as_str()) {
+            self.children().push(HTMLChildNode::Leaf(LeafNode {
+                tag: Some(tag.name),
+                value: String::new(),
+                attributes: non_empty(tag.attributes),
+            }));
+            return;
+        }
+
+        self.stack.push(Frame {
+            tag: tag.name,
+            attributes: non_empty(tag.attributes),
+            children: vec![],
+        });
+        // `<div/>` isn't valid html but it's clear what was meant, and it is
+        // how svg elements are written.
+        if tag.self_closing {
+            self.pop();
+        }
+    }
+
+    fn raw_text_element(&mut self, tag: StartTag, content: &str) {
+        let node = if RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+            let children = if content.is_empty() {
+                vec![]
+            } else {
+                vec![HTMLChildNode::Raw(String::from(content))]
+            };
+            HTMLChildNode::Parent(ParentNode::new(
+                &tag.name,
+                children,
+                non_empty(tag.attributes),
+            ))
+        } else {
+            HTMLChildNode::Leaf(LeafNode {
+                tag: Some(tag.name),
+                value: decode_entities(content),
+                attributes: non_empty(tag.attributes),
+            })
+        };
+
+        self.children().push(node);
+    }
+
+    // Closes the most recent open element with the tag, along with anything
+    // left open inside of it. Closing tags that don't match anything are
+    // dropped.
+    fn close(&mut self, tag: &str) {
+        let Some(position) = self.stack.iter().skip(1).rposition(|f| f.tag == tag) else {
+            return;
+        };
+
+        while self.stack.len() > position + 1 {
+            self.pop();
+        }
+    }
+
+    fn pop(&mut self) {
+        let frame = self.stack.pop().expect("the root frame is never popped");
+        let node = ParentNode::new(&frame.tag, frame.children, frame.attributes);
+        self.children().push(HTMLChildNode::Parent(node));
+    }
+
+    fn finish(mut self) -> Vec<HTMLChildNode> {
+        while self.stack.len() > 1 {
+            self.pop();
+        }
+
+        self.stack.pop().map(|f| f.children).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::parser::markdown_to_html_node;
+
+    use super::*;
+
+    fn to_html(nodes: &[HTMLChildNode]) -> String {
+        nodes.iter().map(|n| n.into_html()).collect()
+    }
+
+    #[test]
+    fn test_parse_html() {
+        let test_cases = vec![
+            (
+                "Test nested elements and attributes",
+                "<div class=\"post\" data-id=3><p>Hi <b>there</b></p></div>",
+                "<div class=\"post\" data-id=\"3\"><p>Hi <b>there</b></p></div>",
+            ),
+            (
+                "Test void and boolean attributes",
+                "<img src='cat.png' alt=\"Cat\"><input type=checkbox checked disabled>",
+                "<img src=\"cat.png\" alt=\"Cat\"><input type=\"checkbox\" checked disabled>",
+            ),
+            (
+                "Test optional end tags are filled in",
+                "<ul><li>one<li>two</ul><p>a<p>b<div>c</div>",
+                "<ul><li>one</li><li>two</li></ul><p>a</p><p>b</p><div>c</div>",
+            ),
+            (
+                "Test unclosed and stray tags",
+                "<section><em>text</span></section><p>end",
+                "<section><em>text</em></section><p>end</p>",
+            ),
+            (
+                "Test entities are decoded and escaped again",
+                "&copy; 2024 &lt;b&gt; &#x41;&#66; &unknown; AT&T",
+                "© 2024 &lt;b&gt; AB &amp;unknown; AT&amp;T",
+            ),
+            (
+                "Test comments and doctype are kept",
+                "<!DOCTYPE html><!-- note --><p>x</p>",
+                "<!DOCTYPE html><!-- note --><p>x</p>",
+            ),
+            (
+                "Test script content is raw",
+                "<script>if (a < b && c) {}</script><title>A &amp; B</title>",
+                "<script>if (a < b && c) {}</script><title>A &amp; B</title>",
+            ),
+            (
+                "Test unfinished tag is text",
+                "café < b and <b",
+                "café &lt; b and &lt;b",
+            ),
+            (
+                "Test self closing svg elements",
+                "<svg viewBox=\"0 0 1 1\"><path d=\"M0\"/></svg>",
+                "<svg viewBox=\"0 0 1 1\"><path d=\"M0\"></path></svg>",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &to_html(&parse_html(input)),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_markdown_output_round_trips() {
+        let markdown = "# Title\n\nSome *text* with `a < b` and [a link](/x).\n\n- one\n- two\n\n> quote\n\n![cat](cat.png)\n";
+        let html = markdown_to_html_node(markdown).root.into_html();
+
+        assert_eq!(to_html(&parse_html(&html)), html);
+    }
+
+    #[test]
+    fn test_expand_raw_html() {
+        let mut nodes = vec![HTMLChildNode::Parent(ParentNode::new(
+            "div",
+            vec![
+                HTMLChildNode::Raw(String::from("<aside class=\"note\">Hi</aside>")),
+                HTMLChildNode::Parent(ParentNode::new(
+                    "script",
+                    vec![HTMLChildNode::Raw(String::from("a < b"))],
+                    None,
+                )),
+            ],
+            None,
+        ))];
+        expand_raw_html(&mut nodes);
+
+        assert!(matches!(
+            &nodes[0],
+            HTMLChildNode::Parent(p) if matches!(&p.children()[0], HTMLChildNode::Parent(a) if a.tag() == "aside")
+        ));
+        assert_eq!(
+            to_html(&nodes),
+            "<div><aside class=\"note\">Hi</aside><script>a < b</script></div>"
+        );
+    }
+}
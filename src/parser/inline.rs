@@ -0,0 +1,293 @@
+use crate::nodes::text_node::{TextNode, TextType};
+
+use super::html::decode_entities;
+use super::raw_html::html_tag_len;
+
+// Turns a line of markdown into a list of text nodes. Code spans are split out
+// first so nothing inside of them gets treated as markdown, then raw html tags,
+// images, links and footnote references, and finally the emphasis delimiters.
+// Character references are decoded last, so `&ast;` can't start emphasis.
+pub fn text_to_text_nodes(text: &str) -> Vec<TextNode> {
+    let nodes = vec![TextNode {
+        content: String::from(text),
+        text_type: TextType::Normal,
+        url: None,
+    }];
+
+    let nodes = split_nodes_delimiter(nodes, TextType::Code);
+    let nodes = split_nodes(nodes, find_raw_html);
+    let nodes = split_nodes(nodes, find_image);
+    let nodes = split_nodes(nodes, find_link);
+    let nodes = split_nodes(nodes, find_footnote_reference);
+    let nodes = split_nodes_delimiter(nodes, TextType::Bold);
+    let nodes = split_nodes_delimiter(nodes, TextType::Italic);
+
+    nodes.into_iter().map(decode_node_entities).collect()
+}
+
+// Code spans, raw html and footnote labels keep their text as written.
+fn decode_node_entities(mut node: TextNode) -> TextNode {
+    match node.text_type {
+        TextType::Code | TextType::RawHtml | TextType::FootnoteReference => node,
+        _ => {
+            node.content = decode_entities(&node.content);
+            node.url = node.url.map(|url| decode_entities(&url));
+            node
+        }
+    }
+}
+
+fn split_nodes_delimiter(nodes: Vec<TextNode>, text_type: TextType) -> Vec<TextNode> {
+    match text_type.get_delimiter() {
+        Some(delimiter) => nodes
+            .iter()
+            .flat_map(|node| node.split_node_on_delimiter(&delimiter))
+            .collect(),
+        None => nodes,
+    }
+}
+
+// A match found by one of the `find_*` functions. `start` and `end` are byte
+// offsets of the whole markdown construct within the searched text.
+struct InlineMatch {
+    start: usize,
+    end: usize,
+    node: TextNode,
+}
+
+// Runs the finder over every normal text node, pulling out each match as its
+// own node and keeping the text around it as normal text.
+fn split_nodes<F>(nodes: Vec<TextNode>, find: F) -> Vec<TextNode>
+where
+    F: Fn(&str) -> Option<InlineMatch>,
+{
+    let mut split = Vec::new();
+
+    for node in nodes {
+        if node.text_type != TextType::Normal {
+            split.push(node);
+            continue;
+        }
+
+        let mut rest = node.content.as_str();
+
+        while let Some(found) = find(rest) {
+            if found.start > 0 {
+                split.push(normal_text(&rest[..found.start]));
+            }
+            split.push(found.node);
+            rest = &rest[found.end..];
+        }
+
+        if !rest.is_empty() {
+            split.push(normal_text(rest));
+        }
+    }
+
+    split
+}
+
+fn normal_text(text: &str) -> TextNode {
+    TextNode {
+        content: String::from(text),
+        text_type: TextType::Normal,
+        url: None,
+    }
+}
+
+// Finds the first `[text](url)` in the text. When `image` is set the opening
+// bracket has to be preceded by a `!`, otherwise it must not be.
+fn find_bracketed(text: &str, image: bool) -> Option<InlineMatch> {
+    let mut search_from = 0;
+
+    while let Some(offset) = text[search_from..].find('[') {
+        let open = search_from + offset;
+        search_from = open + 1;
+
+        let is_image = text[..open].ends_with('!');
+        if is_image != image {
+            continue;
+        }
+
+        let close = text[open..].find(']').map(|i| open + i)?;
+
+        let label = &text[open + 1..close];
+        if label.starts_with('^') || !text[close + 1..].starts_with('(') {
+            continue;
+        }
+
+        let url_end = text[close..].find(')').map(|i| close + i)?;
+
+        return Some(InlineMatch {
+            start: if image { open - 1 } else { open },
+            end: url_end + 1,
+            node: TextNode {
+                content: String::from(label),
+                text_type: if image {
+                    TextType::Image
+                } else {
+                    TextType::Link
+                },
+                url: Some(String::from(text[close + 2..url_end].trim())),
+            },
+        });
+    }
+
+    None
+}
+
+fn find_image(text: &str) -> Option<InlineMatch> {
+    find_bracketed(text, true)
+}
+
+fn find_link(text: &str) -> Option<InlineMatch> {
+    find_bracketed(text, false)
+}
+
+fn find_raw_html(text: &str) -> Option<InlineMatch> {
+    text.match_indices('<').find_map(|(start, _)| {
+        let len = html_tag_len(&text[start..])?;
+
+        Some(InlineMatch {
+            start,
+            end: start + len,
+            node: TextNode {
+                content: String::from(&text[start..start + len]),
+                text_type: TextType::RawHtml,
+                url: None,
+            },
+        })
+    })
+}
+
+// Finds the first `[^label]`. The node's content is the label, the renderer is
+// in charge of numbering it and pointing it at the definition.
+fn find_footnote_reference(text: &str) -> Option<InlineMatch> {
+    let mut search_from = 0;
+
+    while let Some(offset) = text[search_from..].find("[^") {
+        let open = search_from + offset;
+        search_from = open + 2;
+
+        let close = text[open..].find(']').map(|i| open + i)?;
+
+        let label = &text[open + 2..close];
+        if label.is_empty() || label.contains(char::is_whitespace) {
+            continue;
+        }
+
+        return Some(InlineMatch {
+            start: open,
+            end: close + 1,
+            node: TextNode {
+                content: String::from(label),
+                text_type: TextType::FootnoteReference,
+                url: None,
+            },
+        });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn node(content: &str, text_type: TextType, url: Option<&str>) -> TextNode {
+        TextNode {
+            content: String::from(content),
+            text_type,
+            url: url.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_text_to_text_nodes() {
+        let test_cases = vec![
+            (
+                "Test every inline type",
+                "This is **text** with an *italic* word and a `code block` and an ![obi wan image](https://i.imgur.com/fJRm4Vk.jpeg) and a [link](https://boot.dev)",
+                vec![
+                    node("This is ", TextType::Normal, None),
+                    node("text", TextType::Bold, None),
+                    node(" with an ", TextType::Normal, None),
+                    node("italic", TextType::Italic, None),
+                    node(" word and a ", TextType::Normal, None),
+                    node("code block", TextType::Code, None),
+                    node(" and an ", TextType::Normal, None),
+                    node(
+                        "obi wan image",
+                        TextType::Image,
+                        Some("https://i.imgur.com/fJRm4Vk.jpeg"),
+                    ),
+                    node(" and a ", TextType::Normal, None),
+                    node("link", TextType::Link, Some("https://boot.dev")),
+                ],
+            ),
+            (
+                "Test markdown inside code is left alone",
+                "Run `cargo **build**` first",
+                vec![
+                    node("Run ", TextType::Normal, None),
+                    node("cargo **build**", TextType::Code, None),
+                    node(" first", TextType::Normal, None),
+                ],
+            ),
+            (
+                "Test underscores in urls are not emphasis",
+                "See [the docs](https://example.com/snake_case_page) now",
+                vec![
+                    node("See ", TextType::Normal, None),
+                    node(
+                        "the docs",
+                        TextType::Link,
+                        Some("https://example.com/snake_case_page"),
+                    ),
+                    node(" now", TextType::Normal, None),
+                ],
+            ),
+            (
+                "Test footnote references",
+                "As shown[^1] and again[^note].",
+                vec![
+                    node("As shown", TextType::Normal, None),
+                    node("1", TextType::FootnoteReference, None),
+                    node(" and again", TextType::Normal, None),
+                    node("note", TextType::FootnoteReference, None),
+                    node(".", TextType::Normal, None),
+                ],
+            ),
+            (
+                "Test inline html tags",
+                "Press <kbd class=\"key\">Ctrl</kbd> and <!-- not *this* --> a < b",
+                vec![
+                    node("Press ", TextType::Normal, None),
+                    node("<kbd class=\"key\">", TextType::RawHtml, None),
+                    node("Ctrl", TextType::Normal, None),
+                    node("</kbd>", TextType::RawHtml, None),
+                    node(" and ", TextType::Normal, None),
+                    node("<!-- not *this* -->", TextType::RawHtml, None),
+                    node(" a < b", TextType::Normal, None),
+                ],
+            ),
+            (
+                "Test brackets that are not links",
+                "An [aside] and [^ spaced] text",
+                vec![node("An [aside] and [^ spaced] text", TextType::Normal, None)],
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &text_to_text_nodes(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
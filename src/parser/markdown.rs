@@ -0,0 +1,622 @@
+use crate::nodes::text_node::{TextNode, TextType};
+
+use super::block::{indent_width, parse_lines, starts_block, Block};
+use super::inline::text_to_text_nodes;
+
+// How normalized markdown is written out. Everything else is fixed: `#`
+// headings, `-` bullets, `1.` numbers, ``` fences and `*` emphasis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownStyle {
+    // Paragraphs are re-wrapped to fit this many columns, None keeps the
+    // line breaks as they were written.
+    pub wrap: Option<usize>,
+}
+
+impl Default for MarkdownStyle {
+    fn default() -> MarkdownStyle {
+        MarkdownStyle { wrap: Some(80) }
+    }
+}
+
+// Parses the markdown and writes it back out in the normalized style. Front
+// matter is kept as it is, and so is every block with something in it the
+// parser doesn't know, like a table, a setext heading or indented code, since
+// it would come back out as a paragraph.
+pub fn format_markdown(markdown: &str, style: &MarkdownStyle) -> String {
+    let (front_matter, body) = split_front_matter(markdown);
+    let lines: Vec<&str> = body.lines().collect();
+
+    // Runs of blocks to format, and blocks kept as they are, in order.
+    let mut chunks: Vec<Vec<String>> = Vec::new();
+    let mut run: Vec<Block> = Vec::new();
+    for (block, start, end) in parse_lines(&lines) {
+        let source = &lines[start..end];
+        if matches!(block, Block::Code { .. }) || !has_unknown_syntax(source) {
+            run.push(block);
+            continue;
+        }
+
+        if !run.is_empty() {
+            chunks.push(blocks_lines(&run, style.wrap, true));
+            run.clear();
+        }
+        let end = source
+            .iter()
+            .rposition(|l| !l.trim().is_empty())
+            .map_or(0, |i| i + 1);
+        chunks.push(source[..end].iter().map(|l| String::from(*l)).collect());
+    }
+    if !run.is_empty() {
+        chunks.push(blocks_lines(&run, style.wrap, true));
+    }
+
+    let mut formatted = String::from(front_matter);
+    let body = chunks
+        .into_iter()
+        .map(|lines| lines.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !body.is_empty() {
+        formatted.push_str(&body);
+        formatted.push('\n');
+    }
+    formatted
+}
+
+// The yaml between two `---` lines at the very top, with its closing line,
+// and the markdown after it.
+fn split_front_matter(markdown: &str) -> (&str, &str) {
+    if !markdown.starts_with("---\n") && !markdown.starts_with("---\r\n") {
+        return ("", markdown);
+    }
+
+    let mut offset = 0;
+    for (i, line) in markdown.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        if i > 0 && line.trim_end() == "---" {
+            return markdown.split_at(offset);
+        }
+    }
+    ("", markdown)
+}
+
+// Lines the parser reads as something else: thematic breaks and setext
+// underlines, table delimiter rows, and indented code after a blank line.
+// Fenced code in a list or quote is looked at too, keeping a block that
+// didn't need it is harmless.
+fn has_unknown_syntax(lines: &[&str]) -> bool {
+    let mut after_blank = true;
+    for line in lines {
+        let content = line.trim_start_matches([' ', '\t', '>']).trim_end();
+        let only =
+            |chars: &[char]| !content.is_empty() && content.chars().all(|c| chars.contains(&c));
+
+        let rule = ['-', '*', '_']
+            .iter()
+            .any(|m| only(&[*m, ' ', '\t']) && content.matches(*m).count() >= 3);
+        let underline = only(&['=']) || only(&['-']);
+        let delimiter_row =
+            content.contains('|') && content.contains('-') && only(&['|', '-', ':', ' ', '\t']);
+        let indented_code = after_blank && !content.is_empty() && indent_width(line) >= 4;
+        if rule || underline || delimiter_row || indented_code {
+            return true;
+        }
+
+        after_blank = content.is_empty();
+    }
+    false
+}
+
+pub fn blocks_to_markdown(blocks: &[Block], style: &MarkdownStyle) -> String {
+    let lines = blocks_lines(blocks, style.wrap, true);
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    let mut markdown = lines.join("\n");
+    markdown.push('\n');
+
+    markdown
+}
+
+pub fn text_nodes_to_markdown(nodes: &[TextNode]) -> String {
+    nodes.iter().map(|n| node_markdown(n, "*")).collect()
+}
+
+fn node_markdown(node: &TextNode, emphasis: &str) -> String {
+    let url = node.url.as_deref().unwrap_or("");
+
+    match node.text_type {
+        TextType::Normal | TextType::RawHtml => node.content.clone(),
+        TextType::Bold => format!("{0}{0}{1}{0}{0}", emphasis, node.content),
+        TextType::Italic => format!("{0}{1}{0}", emphasis, node.content),
+        TextType::Code => format!("`{}`", node.content),
+        TextType::Link => format!("[{}]({})", node.content, url),
+        TextType::Image => format!("![{}]({})", node.content, url),
+        TextType::FootnoteReference => format!("[^{}]", node.content),
+    }
+}
+
+// Rewrites inline markdown, only when it reads back as the same nodes. The
+// emphasis parser is simple enough that switching `_` for `*` can change the
+// meaning of a stray `*` elsewhere in the text, in that case `_` is tried and
+// then the text is left alone.
+fn normalize_inline(text: &str) -> Option<Vec<TextNode>> {
+    let nodes = text_to_text_nodes(text);
+
+    ["*", "_"].iter().find_map(|emphasis| {
+        let rewritten: Vec<TextNode> = nodes
+            .iter()
+            .map(|n| TextNode {
+                content: node_markdown(n, emphasis),
+                text_type: if n.text_type == TextType::Normal {
+                    TextType::Normal
+                } else {
+                    // Marks pieces that can't be split when wrapping.
+                    TextType::RawHtml
+                },
+                url: None,
+            })
+            .collect();
+        let markdown: String = rewritten.iter().map(|n| n.content.as_str()).collect();
+
+        if text_to_text_nodes(&markdown) == nodes {
+            Some(rewritten)
+        } else {
+            None
+        }
+    })
+}
+
+fn blocks_lines(blocks: &[Block], wrap: Option<usize>, separated: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut previous: Option<&Block> = None;
+    let mut alternate = false;
+
+    for block in blocks {
+        if previous.is_some() && separated {
+            lines.push(String::new());
+        }
+
+        // Two lists in a row would be read back as one unless the second uses
+        // a different marker, so the marker flips for each list in a run.
+        alternate = match (previous, block) {
+            (Some(Block::List { ordered: a, .. }), Block::List { ordered: b, .. }) if a == b => {
+                !alternate
+            }
+            _ => false,
+        };
+
+        lines.extend(block_lines(block, alternate, wrap));
+        previous = Some(block);
+    }
+
+    lines
+}
+
+// Leaves some room for text in deeply nested blocks.
+fn narrower(wrap: Option<usize>, by: usize) -> Option<usize> {
+    wrap.map(|w| w.saturating_sub(by).max(20))
+}
+
+fn block_lines(block: &Block, alternate: bool, wrap: Option<usize>) -> Vec<String> {
+    match block {
+        Block::Paragraph(text) => paragraph_lines(text, wrap),
+        Block::Heading { level, text } => {
+            let text = match normalize_inline(text) {
+                Some(nodes) => nodes.into_iter().map(|n| n.content).collect(),
+                None => text.clone(),
+            };
+            // A trailing `#` would be read as a closing sequence and dropped,
+            // adding a real closing sequence keeps it.
+            let closing = if text.ends_with('#') { " #" } else { "" };
+            vec![format!("{} {}{}", "#".repeat(*level), text, closing)]
+        }
+        Block::Code { info, content } => {
+            // The fence has to be longer than any run of backticks starting a
+            // line in the code.
+            let longest = content
+                .lines()
+                .map(|l| l.trim_start().chars().take_while(|c| *c == '`').count())
+                .max()
+                .unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+
+            let mut lines = vec![format!("{}{}", fence, info.as_deref().unwrap_or(""))];
+            lines.extend(content.lines().map(String::from));
+            lines.push(fence);
+            lines
+        }
+        Block::Html(html) => html.lines().map(String::from).collect(),
+        Block::List {
+            ordered,
+            start,
+            tight,
+            items,
+        } => {
+            let mut lines = Vec::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 && !tight {
+                    lines.push(String::new());
+                }
+
+                let marker = match (ordered, alternate) {
+                    (true, false) => format!("{}.", start + i),
+                    (true, true) => format!("{})", start + i),
+                    (false, false) => String::from("-"),
+                    (false, true) => String::from("*"),
+                };
+                let indent = marker.len() + 1;
+                let item_lines = blocks_lines(item, narrower(wrap, indent), !tight);
+
+                lines.extend(prefix_lines(item_lines, &format!("{} ", marker), indent));
+            }
+            lines
+        }
+        Block::BlockQuote(children) => quote_lines(blocks_lines(children, narrower(wrap, 2), true)),
+        Block::Callout {
+            kind,
+            title,
+            children,
+        } => {
+            let mut marker = format!("[!{}]", kind.name().to_ascii_uppercase());
+            if let Some(title) = title {
+                marker.push(' ');
+                marker.push_str(title);
+            }
+
+            let mut lines = vec![marker];
+            lines.extend(blocks_lines(children, narrower(wrap, 2), true));
+            quote_lines(lines)
+        }
+        Block::FootnoteDefinition { label, children } => prefix_lines(
+            blocks_lines(children, narrower(wrap, 4), true),
+            &format!("[^{}]: ", label),
+            4,
+        ),
+    }
+}
+
+// Puts `first` in front of the first line and indents the rest, blank lines
+// are left empty.
+fn prefix_lines(lines: Vec<String>, first: &str, indent: usize) -> Vec<String> {
+    if lines.is_empty() {
+        return vec![String::from(first.trim_end())];
+    }
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                format!("{}{}", first, line).trim_end().to_string()
+            } else if line.is_empty() {
+                line
+            } else {
+                format!("{}{}", " ".repeat(indent), line)
+            }
+        })
+        .collect()
+}
+
+// An empty quote still needs its `>` so it doesn't disappear.
+fn quote_lines(lines: Vec<String>) -> Vec<String> {
+    if lines.is_empty() {
+        return vec![String::from(">")];
+    }
+
+    lines
+        .into_iter()
+        .map(|line| {
+            if line.is_empty() {
+                String::from(">")
+            } else {
+                format!("> {}", line)
+            }
+        })
+        .collect()
+}
+
+fn paragraph_lines(text: &str, wrap: Option<usize>) -> Vec<String> {
+    let Some(nodes) = normalize_inline(text) else {
+        return text.lines().map(String::from).collect();
+    };
+
+    let Some(width) = wrap else {
+        let markdown: String = nodes.into_iter().map(|n| n.content).collect();
+        return markdown.lines().map(String::from).collect();
+    };
+
+    wrap_words(&words(&nodes), width)
+}
+
+// Splits the paragraph into the pieces a line can break between. Only
+// whitespace in plain text is a break, everything else sticks to the text
+// around it.
+fn words(nodes: &[TextNode]) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for node in nodes {
+        if node.text_type != TextType::Normal {
+            // Emphasis and code can run over a line break, it's written as a
+            // space so the piece stays on one line.
+            current.push_str(&node.content.replace('\n', " "));
+            continue;
+        }
+
+        for c in node.content.chars() {
+            if c.is_whitespace() {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            } else {
+                current.push(c);
+            }
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+// Fills each line with as many words as fit. A word that would start a new
+// block, like `-` or `#`, is never moved to the start of a line.
+fn wrap_words(words: &[String], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for word in words {
+        if line.is_empty() {
+            line.push_str(word);
+            continue;
+        }
+
+        let fits = line.chars().count() + 1 + word.chars().count() <= width;
+        if fits || starts_block(word) {
+            line.push(' ');
+            line.push_str(word);
+        } else {
+            lines.push(std::mem::take(&mut line));
+            line.push_str(word);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::parser::markdown_to_html_node;
+
+    use super::*;
+
+    #[test]
+    fn test_format_markdown() {
+        let test_cases = vec![
+            (
+                "Test emphasis, bullets and headings are normalized",
+                "#   Title #\n## Intro ##\nSome __bold__ and _italic_ text.\n\n* one\n* two\n",
+                "# Title\n\n## Intro\n\nSome **bold** and *italic* text.\n\n- one\n- two\n",
+            ),
+            (
+                "Test stray stars keep underscores",
+                "a * b _c_\n",
+                "a * b _c_\n",
+            ),
+            (
+                "Test paragraphs are wrapped",
+                "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen\n",
+                "one two three four five six seven eight nine ten eleven twelve thirteen fourteen\nfifteen sixteen\n",
+            ),
+            (
+                "Test list markers never start a wrapped line",
+                "Numbers like seventy-two and eighty, then one paragraph of text that fits - here\n",
+                "Numbers like seventy-two and eighty, then one paragraph of text that fits - here\n",
+            ),
+            (
+                "Test loose ordered list with nested blocks",
+                "3) first\n\n   > quoted\n4) second\n",
+                "3. first\n\n   > quoted\n\n4. second\n",
+            ),
+            (
+                "Test lists in a row keep apart",
+                "- a\n\n\n+ b\n\n1. c\n",
+                "- a\n\n* b\n\n1. c\n",
+            ),
+            (
+                "Test code fence and callout",
+                "~~~rust\nlet a = 1;\n```\n~~~\n\n> [!tip] Try it\n> Run it.\n",
+                "````rust\nlet a = 1;\n```\n````\n\n> [!TIP] Try it\n> Run it.\n",
+            ),
+            (
+                "Test front matter is kept",
+                "---\ntitle: Hello world\ntags: [a, b]\n---\n#  Hi\n",
+                "---\ntitle: Hello world\ntags: [a, b]\n---\n# Hi\n",
+            ),
+            (
+                "Test only front matter",
+                "---\ntitle: Hello\n---\n",
+                "---\ntitle: Hello\n---\n",
+            ),
+            (
+                "Test table is kept",
+                "Text  _a_\n\n| a | b |\n|---|:-:|\n| 1 | 2 |\n",
+                "Text *a*\n\n| a | b |\n|---|:-:|\n| 1 | 2 |\n",
+            ),
+            (
+                "Test indented code is kept",
+                "Text\n\n    indented code\n    line two\n",
+                "Text\n\n    indented code\n    line two\n",
+            ),
+            (
+                "Test setext heading is kept",
+                "Setext\n======\n\nTwo\n---\n\n* * *\n",
+                "Setext\n======\n\nTwo\n---\n\n* * *\n",
+            ),
+            (
+                "Test footnote definition",
+                "Text[^1].\n\n[^1]: The note\n    continues.\n",
+                "Text[^1].\n\n[^1]: The note continues.\n",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &format_markdown(input, &MarkdownStyle::default()),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    // Small xorshift generator so the property test runs the same every time
+    // without pulling in a crate.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, below: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % below as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.next(items.len())]
+        }
+    }
+
+    fn random_text(random: &mut Random) -> String {
+        let pieces = [
+            "word",
+            "the",
+            "markdown",
+            "**bold**",
+            "__strong__",
+            "*it*",
+            "_em_",
+            "`code`",
+            "[link](/to/page)",
+            "![alt](cat.png)",
+            "<kbd>K</kbd>",
+            "a-b",
+            "1.",
+            "-",
+            "#",
+            ">",
+            "[^n]",
+            "2)",
+            "x*y",
+            "snake_case",
+        ];
+
+        let count = 1 + random.next(40);
+        let mut text = String::new();
+        for i in 0..count {
+            if i > 0 {
+                text.push(if random.next(8) == 0 { '\n' } else { ' ' });
+            }
+            text.push_str(random.pick(&pieces));
+        }
+
+        text
+    }
+
+    fn random_document(random: &mut Random, depth: usize) -> String {
+        let mut blocks = Vec::new();
+
+        for _ in 0..1 + random.next(5) {
+            let block = match random.next(if depth > 1 { 3 } else { 7 }) {
+                0 | 1 => random_text(random),
+                2 => format!(
+                    "{} {}",
+                    "#".repeat(1 + random.next(6)),
+                    random.pick(&["Title", "A *b*"])
+                ),
+                3 => format!(
+                    "```{}\n{}\n```",
+                    random.pick(&["", "rust"]),
+                    random_text(random)
+                ),
+                4 => {
+                    let bullet = random.pick(&["-", "*", "1.", "1)"]);
+                    let separator = random.pick(&["\n", "\n\n"]);
+                    (0..1 + random.next(3))
+                        .map(|_| {
+                            let item = random_document(random, depth + 1);
+                            let indent = " ".repeat(bullet.len() + 1);
+                            format!(
+                                "{} {}",
+                                bullet,
+                                item.trim_end().replace('\n', &format!("\n{}", indent))
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join(separator)
+                }
+                5 => random_document(random, depth + 1)
+                    .lines()
+                    .map(|l| format!("> {}", l))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                _ => format!(
+                    "> [!{}]\n> {}",
+                    random.pick(&["NOTE", "WARNING"]),
+                    random_text(random).replace('\n', " ")
+                ),
+            };
+            blocks.push(block);
+        }
+
+        blocks.join("\n\n")
+    }
+
+    // Whitespace only changes where lines break, so it's collapsed before
+    // comparing.
+    fn normalized_html(markdown: &str) -> String {
+        markdown_to_html_node(markdown)
+            .root
+            .into_html()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..300 {
+            let markdown = random_document(&mut random, 0);
+            let style = MarkdownStyle {
+                wrap: Some(20 + random.next(60)),
+            };
+            let formatted = format_markdown(&markdown, &style);
+
+            assert_eq!(
+                normalized_html(&formatted),
+                normalized_html(&markdown),
+                "formatting changed the page for input: {:?} formatted: {:?}",
+                markdown,
+                formatted
+            );
+            assert_eq!(
+                format_markdown(&formatted, &style),
+                formatted,
+                "formatting isn't stable for input: {:?}",
+                markdown
+            );
+        }
+    }
+}
@@ -0,0 +1,638 @@
+use core::fmt;
+
+use crate::nodes::html_node::{HTMLAttributes, HTMLChildNode, ToHtmlString};
+use crate::nodes::leaf_node::LeafNode;
+use crate::nodes::parent_node::ParentNode;
+use crate::nodes::sanitize::{Removal, SanitizePolicy};
+use crate::nodes::text_node::{TextNode, TextType};
+
+use self::block::{Block, CalloutKind};
+use self::footnote::{definition_id, reference_id, Footnotes};
+
+pub mod block;
+pub mod footnote;
+pub mod html;
+pub mod inline;
+pub mod markdown;
+pub mod raw_html;
+
+// Problems found in the markdown that don't stop the page from rendering.
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    UndefinedFootnote(String),
+    UnusedFootnote(String),
+    // Something `RawHtml::Sanitize` took out of the page.
+    RemovedHtml(Removal),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::UndefinedFootnote(label) => {
+                write!(f, "footnote [^{}] is referenced but never defined", label)
+            }
+            Warning::UnusedFootnote(label) => {
+                write!(f, "footnote [^{}] is defined but never referenced", label)
+            }
+            Warning::RemovedHtml(removal) => write!(f, "{}", removal),
+        }
+    }
+}
+
+// A rendered markdown page, the root is a div holding every block.
+#[derive(Debug, PartialEq)]
+pub struct Document {
+    pub root: ParentNode,
+    pub warnings: Vec<Warning>,
+}
+
+// What to do with html written in the markdown.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawHtml {
+    // Passed through untouched, for content we trust.
+    Allow,
+    // Passed through, apart from tags like <script> and <iframe> which are
+    // escaped, the same as GitHub's tag filter. Attributes like onclick and
+    // javascript: urls are kept, so it isn't safe for untrusted content, use
+    // Sanitize or Escape for that.
+    Filter,
+    // Escaped so it shows up as text, for untrusted content.
+    Escape,
+    // Passed through and then the whole page, markdown links included, is
+    // cleaned with the policy. Everything it removes is a warning.
+    Sanitize(Box<SanitizePolicy>),
+}
+
+// Settings that change how markdown is turned into nodes.
+#[derive(Debug, Clone)]
+pub struct Options {
+    // Puts an icon in front of callout titles.
+    pub callout_icons: bool,
+    pub raw_html: RawHtml,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            callout_icons: true,
+            raw_html: RawHtml::Allow,
+        }
+    }
+}
+
+pub fn markdown_to_html_node(markdown: &str) -> Document {
+    markdown_to_html_node_with_options(markdown, &Options::default())
+}
+
+pub fn markdown_to_html_node_with_options(markdown: &str, options: &Options) -> Document {
+    let blocks = block::parse_blocks(markdown);
+    let mut renderer = Renderer {
+        options: options.clone(),
+        ..Renderer::default()
+    };
+
+    for block in &blocks {
+        if let Block::FootnoteDefinition { label, children } = block {
+            renderer.footnotes.define(label, children.clone());
+        }
+    }
+
+    let mut children = renderer.render_blocks(&blocks);
+    if let Some(section) = renderer.render_footnotes() {
+        children.push(HTMLChildNode::Parent(section));
+    }
+
+    let mut root = ParentNode::new("div", children, None);
+    if let RawHtml::Sanitize(policy) = &options.raw_html {
+        let (clean, report) = policy.sanitize_parent(root);
+        root = clean;
+        renderer
+            .warnings
+            .extend(report.removed.into_iter().map(Warning::RemovedHtml));
+    }
+
+    Document {
+        root,
+        warnings: renderer.warnings,
+    }
+}
+
+#[derive(Default)]
+struct Renderer {
+    options: Options,
+    footnotes: Footnotes,
+    warnings: Vec<Warning>,
+}
+
+impl Renderer {
+    fn render_blocks(&mut self, blocks: &[Block]) -> Vec<HTMLChildNode> {
+        blocks
+            .iter()
+            .filter_map(|block| self.render_block(block))
+            .collect()
+    }
+
+    fn render_block(&mut self, block: &Block) -> Option<HTMLChildNode> {
+        let node = match block {
+            Block::Paragraph(text) => ParentNode::new("p", self.render_inline(text), None),
+            Block::Heading { level, text } => {
+                ParentNode::new(&format!("h{}", level), self.render_inline(text), None)
+            }
+            Block::Code { info, content } => {
+                let attributes = info
+                    .as_ref()
+                    .map(|i| HTMLAttributes::from_pairs(&[("class", &format!("language-{}", i))]));
+                let code = LeafNode {
+                    tag: Some(String::from("code")),
+                    value: content.clone(),
+                    attributes,
+                };
+
+                ParentNode::new("pre", vec![HTMLChildNode::Leaf(code)], None)
+            }
+            Block::List {
+                ordered,
+                start,
+                tight,
+                items,
+            } => {
+                let items = items
+                    .iter()
+                    .map(|item| {
+                        HTMLChildNode::Parent(ParentNode::new(
+                            "li",
+                            self.render_list_item(item, *tight),
+                            None,
+                        ))
+                    })
+                    .collect();
+
+                if !ordered {
+                    ParentNode::new("ul", items, None)
+                } else if *start != 1 {
+                    let start = start.to_string();
+                    ParentNode::new(
+                        "ol",
+                        items,
+                        Some(HTMLAttributes::from_pairs(&[("start", &start)])),
+                    )
+                } else {
+                    ParentNode::new("ol", items, None)
+                }
+            }
+            Block::BlockQuote(children) => {
+                ParentNode::new("blockquote", self.render_blocks(children), None)
+            }
+            Block::Callout {
+                kind,
+                title,
+                children,
+            } => {
+                let mut content = vec![HTMLChildNode::Parent(
+                    self.render_callout_title(kind, title.as_deref()),
+                )];
+                content.extend(self.render_blocks(children));
+                let class = format!("callout callout-{}", kind.name());
+
+                ParentNode::new(
+                    "div",
+                    content,
+                    Some(HTMLAttributes::from_pairs(&[("class", &class)])),
+                )
+            }
+            Block::Html(html) => {
+                return Some(match self.options.raw_html {
+                    RawHtml::Escape => HTMLChildNode::Parent(ParentNode::new(
+                        "p",
+                        vec![self.render_raw_html(html)],
+                        None,
+                    )),
+                    _ => self.render_raw_html(html),
+                })
+            }
+            // Definitions are collected up front and rendered at the end.
+            Block::FootnoteDefinition { .. } => return None,
+        };
+
+        Some(HTMLChildNode::Parent(node))
+    }
+
+    // Ex. <p class="callout-title"><span class="callout-icon">⚠</span>Warning</p>
+    fn render_callout_title(&mut self, kind: &CalloutKind, title: Option<&str>) -> ParentNode {
+        let mut children = Vec::new();
+
+        if self.options.callout_icons {
+            children.push(HTMLChildNode::Leaf(LeafNode {
+                tag: Some(String::from("span")),
+                value: String::from(kind.icon()),
+                attributes: Some(HTMLAttributes::from_pairs(&[("class", "callout-icon")])),
+            }));
+        }
+        children.extend(self.render_inline(title.unwrap_or(kind.default_title())));
+
+        ParentNode::new(
+            "p",
+            children,
+            Some(HTMLAttributes::from_pairs(&[("class", "callout-title")])),
+        )
+    }
+
+    // Paragraphs in a tight list item are rendered as bare text.
+    fn render_list_item(&mut self, blocks: &[Block], tight: bool) -> Vec<HTMLChildNode> {
+        if !tight {
+            return self.render_blocks(blocks);
+        }
+
+        let mut children = Vec::new();
+        for block in blocks {
+            match block {
+                Block::Paragraph(text) => children.extend(self.render_inline(text)),
+                _ => children.extend(self.render_block(block)),
+            }
+        }
+
+        children
+    }
+
+    fn render_inline(&mut self, text: &str) -> Vec<HTMLChildNode> {
+        let nodes: Vec<HTMLChildNode> = inline::text_to_text_nodes(text)
+            .into_iter()
+            .map(|node| self.render_text_node(node))
+            .collect();
+
+        // Inline html comes in pieces, `<kbd>` and `</kbd>` are raw nodes with
+        // the text between them. The sanitizer parses each raw node on its
+        // own, so they're put back together into one first.
+        let sanitized = matches!(self.options.raw_html, RawHtml::Sanitize(_));
+        if sanitized && nodes.iter().any(|n| matches!(n, HTMLChildNode::Raw(_))) {
+            return vec![HTMLChildNode::Raw(
+                nodes.iter().map(|n| n.into_html()).collect(),
+            )];
+        }
+        nodes
+    }
+
+    fn render_raw_html(&self, html: &str) -> HTMLChildNode {
+        match self.options.raw_html {
+            RawHtml::Allow | RawHtml::Sanitize(_) => HTMLChildNode::Raw(String::from(html)),
+            RawHtml::Filter => HTMLChildNode::Raw(raw_html::filter_tags(html)),
+            RawHtml::Escape => HTMLChildNode::Leaf(LeafNode {
+                tag: None,
+                value: String::from(html),
+                attributes: None,
+            }),
+        }
+    }
+
+    fn render_text_node(&mut self, node: TextNode) -> HTMLChildNode {
+        match node.text_type {
+            TextType::FootnoteReference => {}
+            TextType::RawHtml => return self.render_raw_html(&node.content),
+            _ => return node.into_html_child(),
+        }
+
+        match self.footnotes.reference(&node.content) {
+            // Ex. <sup class="footnote-ref" id="fnref-1"><a href="#fn-1">1</a></sup>
+            Some(reference) => {
+                let link = TextNode {
+                    content: reference.number.to_string(),
+                    text_type: TextType::FootnoteReference,
+                    url: Some(reference.href()),
+                };
+
+                HTMLChildNode::Parent(ParentNode::new(
+                    "sup",
+                    vec![HTMLChildNode::Leaf(link.into_html_node())],
+                    Some(HTMLAttributes::from_pairs(&[
+                        ("class", "footnote-ref"),
+                        ("id", &reference.id()),
+                    ])),
+                ))
+            }
+            // Undefined references are left as the text that was written.
+            None => {
+                self.warnings
+                    .push(Warning::UndefinedFootnote(node.content.clone()));
+
+                HTMLChildNode::Leaf(LeafNode {
+                    tag: None,
+                    value: format!("[^{}]", node.content),
+                    attributes: None,
+                })
+            }
+        }
+    }
+
+    // Builds the <section class="footnotes"> list. Rendering a footnote can
+    // reference footnotes that haven't been seen yet, so the list is walked by
+    // number until it stops growing. Back references are added once every
+    // reference has been counted.
+    fn render_footnotes(&mut self) -> Option<ParentNode> {
+        let mut rendered = Vec::new();
+        let mut number = 1;
+
+        while let Some(label) = self.footnotes.label(number).map(String::from) {
+            let mut blocks = self
+                .footnotes
+                .definition(&label)
+                .cloned()
+                .unwrap_or_default();
+
+            // The back references go at the end of the last paragraph.
+            let trailing_paragraph = match blocks.last() {
+                Some(Block::Paragraph(_)) => match blocks.pop() {
+                    Some(Block::Paragraph(text)) => Some(self.render_inline(&text)),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            rendered.push((label, self.render_blocks(&blocks), trailing_paragraph));
+            number += 1;
+        }
+
+        for label in self.footnotes.unused() {
+            self.warnings
+                .push(Warning::UnusedFootnote(String::from(label)));
+        }
+
+        if rendered.is_empty() {
+            return None;
+        }
+
+        let items = rendered
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, mut children, trailing_paragraph))| {
+                let number = i + 1;
+                let mut paragraph = trailing_paragraph.unwrap_or_default();
+
+                for index in 1..=self.footnotes.reference_count(&label) {
+                    if !paragraph.is_empty() {
+                        paragraph.push(HTMLChildNode::Leaf(LeafNode {
+                            tag: None,
+                            value: String::from(" "),
+                            attributes: None,
+                        }));
+                    }
+                    paragraph.push(HTMLChildNode::Parent(back_reference(number, index)));
+                }
+                children.push(HTMLChildNode::Parent(ParentNode::new("p", paragraph, None)));
+
+                HTMLChildNode::Parent(ParentNode::new(
+                    "li",
+                    children,
+                    Some(HTMLAttributes::from_pairs(&[(
+                        "id",
+                        &definition_id(number),
+                    )])),
+                ))
+            })
+            .collect();
+
+        Some(ParentNode::new(
+            "section",
+            vec![HTMLChildNode::Parent(ParentNode::new("ol", items, None))],
+            Some(HTMLAttributes::from_pairs(&[("class", "footnotes")])),
+        ))
+    }
+}
+
+// Ex. <a class="footnote-backref" href="#fnref-1-2">↩<sup>2</sup></a>
+fn back_reference(number: usize, index: usize) -> ParentNode {
+    let mut children = vec![HTMLChildNode::Leaf(LeafNode {
+        tag: None,
+        value: String::from("↩"),
+        attributes: None,
+    })];
+
+    if index > 1 {
+        children.push(HTMLChildNode::Leaf(LeafNode {
+            tag: Some(String::from("sup")),
+            value: index.to_string(),
+            attributes: None,
+        }));
+    }
+
+    ParentNode::new(
+        "a",
+        children,
+        Some(HTMLAttributes::from_pairs(&[
+            ("class", "footnote-backref"),
+            ("href", &format!("#{}", reference_id(number, index))),
+        ])),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+
+    use super::*;
+
+    #[test]
+    fn test_markdown_to_html_node() {
+        let test_cases = vec![
+            (
+                "Test paragraph, heading and code",
+                "# Title\n\nSome **bold** text\n\n```sh\ncargo run\n```",
+                "<div><h1>Title</h1><p>Some <b>bold</b> text</p><pre><code class=\"language-sh\">cargo run\n</code></pre></div>",
+            ),
+            (
+                "Test footnotes are numbered by first reference",
+                "One[^b] two[^a] three[^b]\n\n[^a]: Note A\n[^b]: Note B",
+                "<div><p>One<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup> two<sup class=\"footnote-ref\" id=\"fnref-2\"><a href=\"#fn-2\">2</a></sup> three<sup class=\"footnote-ref\" id=\"fnref-1-2\"><a href=\"#fn-1\">1</a></sup></p><section class=\"footnotes\"><ol><li id=\"fn-1\"><p>Note B <a class=\"footnote-backref\" href=\"#fnref-1\">↩</a> <a class=\"footnote-backref\" href=\"#fnref-1-2\">↩<sup>2</sup></a></p></li><li id=\"fn-2\"><p>Note A <a class=\"footnote-backref\" href=\"#fnref-2\">↩</a></p></li></ol></section></div>",
+            ),
+            (
+                "Test entities in code are escaped",
+                "Use `&amp;` for &amp; &copy;",
+                "<div><p>Use <code>&amp;amp;</code> for &amp; ©</p></div>",
+            ),
+            (
+                "Test entities in code blocks are escaped",
+                "```html\n&lt;p&gt;\n```",
+                "<div><pre><code class=\"language-html\">&amp;lt;p&amp;gt;\n</code></pre></div>",
+            ),
+            (
+                "Test entities in links are decoded",
+                "[A &amp; B](/search?a=1&amp;b=2)",
+                "<div><p><a href=\"/search?a=1&amp;b=2\">A &amp; B</a></p></div>",
+            ),
+            (
+                "Test tight nested list",
+                "- Step one\n- Step two\n  1. Sub step\n  2. Another\n     - Deep\n- Step three",
+                "<div><ul><li>Step one</li><li>Step two<ol><li>Sub step</li><li>Another<ul><li>Deep</li></ul></li></ol></li><li>Step three</li></ul></div>",
+            ),
+            (
+                "Test loose ordered list with custom start",
+                "3. First\n\n4. Second\n\n   More about second",
+                "<div><ol start=\"3\"><li><p>First</p></li><li><p>Second</p><p>More about second</p></li></ol></div>",
+            ),
+            (
+                "Test footnote ending in a code block",
+                "Text[^1]\n\n[^1]: Intro\n\n    ```\n    code\n    ```",
+                "<div><p>Text<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup></p><section class=\"footnotes\"><ol><li id=\"fn-1\"><p>Intro</p><pre><code>code\n</code></pre><p><a class=\"footnote-backref\" href=\"#fnref-1\">↩</a></p></li></ol></section></div>",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let document = markdown_to_html_node(input);
+
+            assert_eq!(
+                &document.root.into_html(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_callouts() {
+        let test_cases = vec![
+            (
+                "Test callout with default title and icon",
+                "> [!WARNING]\n> Back up **first**.",
+                Options::default(),
+                "<div><div class=\"callout callout-warning\"><p class=\"callout-title\"><span class=\"callout-icon\">⚠</span>Warning</p><p>Back up <b>first</b>.</p></div></div>",
+            ),
+            (
+                "Test callout with custom title and no icon",
+                "> [!NOTE] Heads up\n> - one\n> - two",
+                Options {
+                    callout_icons: false,
+                    ..Options::default()
+                },
+                "<div><div class=\"callout callout-note\"><p class=\"callout-title\">Heads up</p><ul><li>one</li><li>two</li></ul></div></div>",
+            ),
+            (
+                "Test nested blockquote",
+                "> outer\n>\n> > inner",
+                Options::default(),
+                "<div><blockquote><p>outer</p><blockquote><p>inner</p></blockquote></blockquote></div>",
+            ),
+        ];
+
+        for (title, input, options, expected) in test_cases.iter() {
+            assert_eq!(
+                &markdown_to_html_node_with_options(input, options)
+                    .root
+                    .into_html(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_raw_html() {
+        let markdown = "<div class=\"widget\" onclick=\"go()\">\n<script>track()</script>\n</div>\n\nPress <kbd>Ctrl</kbd> & <iframe src=\"x\">";
+
+        let test_cases = vec![
+            (
+                "Test raw html is passed through",
+                RawHtml::Allow,
+                "<div><div class=\"widget\" onclick=\"go()\">\n<script>track()</script>\n</div><p>Press <kbd>Ctrl</kbd> &amp; <iframe src=\"x\"></p></div>",
+            ),
+            (
+                "Test filtered tags are escaped and attributes kept",
+                RawHtml::Filter,
+                "<div><div class=\"widget\" onclick=\"go()\">\n&lt;script>track()&lt;/script>\n</div><p>Press <kbd>Ctrl</kbd> &amp; &lt;iframe src=\"x\"></p></div>",
+            ),
+            (
+                "Test raw html is sanitized",
+                RawHtml::Sanitize(Box::default()),
+                "<div><div class=\"widget\">\n\n</div><p>Press <kbd>Ctrl</kbd> &amp; </p></div>",
+            ),
+            (
+                "Test raw html is shown as text",
+                RawHtml::Escape,
+                "<div><p>&lt;div class=&quot;widget&quot; onclick=&quot;go()&quot;&gt;\n&lt;script&gt;track()&lt;/script&gt;\n&lt;/div&gt;</p><p>Press &lt;kbd&gt;Ctrl&lt;/kbd&gt; &amp; &lt;iframe src=&quot;x&quot;&gt;</p></div>",
+            ),
+        ];
+
+        for (title, raw_html, expected) in test_cases.iter() {
+            let options = Options {
+                raw_html: raw_html.clone(),
+                ..Options::default()
+            };
+
+            assert_eq!(
+                &markdown_to_html_node_with_options(markdown, &options)
+                    .root
+                    .into_html(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                markdown,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_warnings() {
+        let options = Options {
+            raw_html: RawHtml::Sanitize(Box::default()),
+            ..Options::default()
+        };
+        let document = markdown_to_html_node_with_options(
+            "[Go](javascript:run) <span onclick=\"x()\">*hi*</span>",
+            &options,
+        );
+
+        assert_eq!(
+            document.root.into_html(),
+            "<div><p><a>Go</a> <span><i>hi</i></span></p></div>"
+        );
+        assert_eq!(
+            document
+                .warnings
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "removed unsafe href \"javascript:run\" from <a>",
+                "removed onclick attribute from <span>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_footnote_warnings() {
+        let test_cases = vec![
+            (
+                "Test undefined footnote",
+                "Missing[^nope]",
+                vec![Warning::UndefinedFootnote(String::from("nope"))],
+            ),
+            (
+                "Test unused footnote",
+                "No references\n\n[^unused]: Never used",
+                vec![Warning::UnusedFootnote(String::from("unused"))],
+            ),
+            (
+                "Test footnotes referenced from other footnotes are used",
+                "Start[^a]\n\n[^a]: See also[^b]\n[^b]: Nested",
+                vec![],
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &markdown_to_html_node(input).warnings,
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// Recognises raw html in markdown, following the rules CommonMark uses for
+// html blocks and inline tags.
+
+// Tags that start an html block that runs until the next blank line.
+const BLOCK_TAGS: [&str; 62] = [
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "meta",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "section",
+    "source",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+// Tags whose content is kept as is, their html block runs until the closing tag.
+const LITERAL_TAGS: [&str; 4] = ["script", "pre", "style", "textarea"];
+
+// Tags GitHub's tag filter won't let through, they can change how the rest of
+// the page is parsed.
+const FILTERED_TAGS: [&str; 9] = [
+    "title",
+    "textarea",
+    "style",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "script",
+    "plaintext",
+];
+
+// How an html block knows where it stops.
+#[derive(Debug, PartialEq)]
+pub enum HtmlBlockEnd {
+    // The block ends with the line containing this text.
+    Contains(&'static str),
+    // The block ends at the next blank line.
+    BlankLine,
+}
+
+// Checks if the (already dedented) line starts an html block. Generic tags on
+// their own line can't interrupt a paragraph, so those are only matched when
+// `interrupting` is false.
+pub fn html_block_start(line: &str, interrupting: bool) -> Option<HtmlBlockEnd> {
+    if !line.starts_with('<') {
+        return None;
+    }
+
+    let lower = line.to_ascii_lowercase();
+
+    for tag in LITERAL_TAGS {
+        let rest = &lower[1..];
+        if let Some(after) = rest.strip_prefix(tag) {
+            if after.is_empty() || after.starts_with([' ', '\t', '>']) {
+                return Some(HtmlBlockEnd::Contains(match tag {
+                    "script" => "</script>",
+                    "pre" => "</pre>",
+                    "style" => "</style>",
+                    _ => "</textarea>",
+                }));
+            }
+        }
+    }
+
+    if lower.starts_with("<!--") {
+        return Some(HtmlBlockEnd::Contains("-->"));
+    }
+    if lower.starts_with("<?") {
+        return Some(HtmlBlockEnd::Contains("?>"));
+    }
+    if lower.starts_with("<![cdata[") {
+        return Some(HtmlBlockEnd::Contains("]]>"));
+    }
+    if lower.starts_with("<!") && lower[2..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Some(HtmlBlockEnd::Contains(">"));
+    }
+
+    let name_start = if lower.starts_with("</") { 2 } else { 1 };
+    let name: String = lower[name_start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let after = &lower[name_start + name.len()..];
+
+    if BLOCK_TAGS.contains(&name.as_str())
+        && (after.is_empty() || after.starts_with([' ', '\t', '>']) || after.starts_with("/>"))
+    {
+        return Some(HtmlBlockEnd::BlankLine);
+    }
+
+    // Any other complete tag alone on its line.
+    if !interrupting {
+        if let Some(len) = html_tag_len(line) {
+            if line[len..].trim().is_empty() && !LITERAL_TAGS.contains(&name.as_str()) {
+                return Some(HtmlBlockEnd::BlankLine);
+            }
+        }
+    }
+
+    None
+}
+
+// Returns the length of the html tag, comment, processing instruction,
+// declaration or CDATA section the text starts with.
+pub fn html_tag_len(text: &str) -> Option<usize> {
+    if !text.starts_with('<') {
+        return None;
+    }
+
+    if let Some(rest) = text.strip_prefix("<!--") {
+        if rest.starts_with('>') || rest.starts_with("->") {
+            return None;
+        }
+        let end = rest.find("--")?;
+        return if rest[end..].starts_with("-->") {
+            Some(4 + end + 3)
+        } else {
+            None
+        };
+    }
+    if let Some(rest) = text.strip_prefix("<?") {
+        return rest.find("?>").map(|end| 2 + end + 2);
+    }
+    if let Some(rest) = text.strip_prefix("<![CDATA[") {
+        return rest.find("]]>").map(|end| 9 + end + 3);
+    }
+    if let Some(rest) = text.strip_prefix("<!") {
+        let name = rest.chars().take_while(|c| c.is_ascii_uppercase()).count();
+        if name == 0 || !rest[name..].starts_with(char::is_whitespace) {
+            return None;
+        }
+        return rest.find('>').map(|end| 2 + end + 1);
+    }
+    if let Some(rest) = text.strip_prefix("</") {
+        let name = tag_name_len(rest)?;
+        let after = rest[name..].trim_start_matches([' ', '\t', '\n']);
+        return if after.starts_with('>') {
+            Some(text.len() - after.len() + 1)
+        } else {
+            None
+        };
+    }
+
+    open_tag_len(text)
+}
+
+fn tag_name_len(text: &str) -> Option<usize> {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    Some(
+        text.chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .count(),
+    )
+}
+
+// `<name attr="value" other>`, attributes must be separated by whitespace and
+// values are unquoted, single quoted or double quoted.
+fn open_tag_len(text: &str) -> Option<usize> {
+    let name = tag_name_len(&text[1..])?;
+    let mut rest = &text[1 + name..];
+
+    loop {
+        let trimmed = rest.trim_start_matches([' ', '\t', '\n']);
+        let had_space = trimmed.len() < rest.len();
+
+        if trimmed.starts_with('>') {
+            return Some(text.len() - trimmed.len() + 1);
+        }
+        if trimmed.starts_with("/>") {
+            return Some(text.len() - trimmed.len() + 2);
+        }
+        if !had_space {
+            return None;
+        }
+
+        rest = attribute(trimmed)?;
+    }
+}
+
+// Consumes one attribute, returning what's left after it.
+fn attribute(text: &str) -> Option<&str> {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == ':') {
+        return None;
+    }
+
+    let name = text
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || "_.:-".contains(*c))
+        .count();
+    let rest = &text[name..];
+
+    let after_space = rest.trim_start_matches([' ', '\t', '\n']);
+    let Some(value) = after_space.strip_prefix('=') else {
+        return Some(rest);
+    };
+    let value = value.trim_start_matches([' ', '\t', '\n']);
+
+    match value.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = value[1..].find(quote)?;
+            Some(&value[end + 2..])
+        }
+        _ => {
+            let end = value
+                .find(|c: char| c.is_whitespace() || "\"'=<>`".contains(c))
+                .unwrap_or(value.len());
+            if end == 0 {
+                return None;
+            }
+            Some(&value[end..])
+        }
+    }
+}
+
+// GitHub's tag filter, the `<` of filtered tags is escaped so the browser
+// shows them as text.
+pub fn filter_tags(html: &str) -> String {
+    let mut filtered = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        filtered.push_str(&rest[..open]);
+        let tag = &rest[open + 1..];
+        let name_start = if tag.starts_with('/') { 1 } else { 0 };
+        let name: String = tag[name_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let after = &tag[name_start + name.len()..];
+
+        let blocked = FILTERED_TAGS.contains(&name.to_ascii_lowercase().as_str())
+            && (after.is_empty()
+                || after.starts_with(char::is_whitespace)
+                || after.starts_with('>')
+                || after.starts_with("/>"));
+
+        filtered.push_str(if blocked { "&lt;" } else { "<" });
+        rest = tag;
+    }
+    filtered.push_str(rest);
+
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_html_tag_len() {
+        let test_cases = vec![
+            ("Test simple open tag", "<span>text", Some(6)),
+            (
+                "Test attributes with every kind of value",
+                "<a href=\"x\" target='_blank' data-x=y disabled>",
+                Some(46),
+            ),
+            ("Test self closing tag", "<br/> after", Some(5)),
+            ("Test closing tag", "</div >rest", Some(7)),
+            ("Test comment", "<!-- note --> after", Some(13)),
+            (
+                "Test attributes need whitespace",
+                "<a href='x'title='y'>",
+                None,
+            ),
+            ("Test not a tag", "< span>", None),
+            ("Test autolink is not a tag", "<https://example.com>", None),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &html_tag_len(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_html_block_start() {
+        let test_cases = vec![
+            (
+                "Test script block",
+                "<script type=\"module\">",
+                false,
+                Some(HtmlBlockEnd::Contains("</script>")),
+            ),
+            (
+                "Test comment block",
+                "<!-- widget -->",
+                true,
+                Some(HtmlBlockEnd::Contains("-->")),
+            ),
+            (
+                "Test block level tag",
+                "<div class=\"widget\">",
+                true,
+                Some(HtmlBlockEnd::BlankLine),
+            ),
+            (
+                "Test any complete tag on its own line",
+                "<my-widget data-id=\"3\">",
+                false,
+                Some(HtmlBlockEnd::BlankLine),
+            ),
+            (
+                "Test generic tags can't interrupt a paragraph",
+                "<my-widget data-id=\"3\">",
+                true,
+                None,
+            ),
+            (
+                "Test inline tag followed by text",
+                "<span>hi</span>",
+                false,
+                None,
+            ),
+        ];
+
+        for (title, input, interrupting, expected) in test_cases.iter() {
+            assert_eq!(
+                &html_block_start(input, *interrupting),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_tags() {
+        assert_eq!(
+            filter_tags("<div><script>alert(1)</script><Style></div>"),
+            "<div>&lt;script>alert(1)&lt;/script>&lt;Style></div>"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::nodes::html_node::HTMLChildNode;
+use crate::parser::html::{expand_raw_html, parse_html};
+
+use super::links::{collect_ids, INTERNAL_PREFIX};
+use super::page::Page;
+use super::urls::resolve_url;
+use super::{Site, SiteError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    // An `@/` link to a file that isn't a page, or one that's left out of
+    // the build like a draft.
+    MissingSource,
+    MissingPage,
+    // The page is there but nothing on it has the id after the `#`.
+    MissingAnchor,
+    // An image or other file that isn't in `content` or `static`.
+    MissingAsset,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::MissingSource => write!(f, "no page for"),
+            Problem::MissingPage => write!(f, "missing page"),
+            Problem::MissingAnchor => write!(f, "missing anchor"),
+            Problem::MissingAsset => write!(f, "missing file"),
+        }
+    }
+}
+
+// A link or image in the content of a page that goes nowhere.
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    // The page it's on.
+    // Ex. docs/intro.md
+    pub source: String,
+    // The line and column in the markdown file, counted from 1. None when the
+    // link can't be found in it, like when a template wrote it.
+    pub location: Option<(usize, usize)>,
+    // As it is in the page after `@/` links are resolved.
+    pub url: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for BrokenLink {
+    // Ex. docs/intro.md:12:5: missing anchor /docs/setup/#install
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{}:{}", self.source, line, column)?,
+            None => write!(f, "{}", self.source)?,
+        }
+        write!(f, ": {} {}", self.problem, self.url)
+    }
+}
+
+// Renders the site and follows every link and image in the content of its
+// pages, both markdown ones and `<a>` and `<img>` in raw html. Links to other
+// sites aren't followed.
+pub fn check(site: &Site) -> Result<Vec<BrokenLink>, SiteError> {
+    // Every file the build writes by its path, with the ids in it for html.
+    let mut targets: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for file in site.render()? {
+        let path = file.path.to_string_lossy().replace('\\', "/");
+        let mut ids = BTreeSet::new();
+        if path.ends_with(".html") {
+            collect_ids(&parse_html(&file.contents), &mut ids);
+        }
+        targets.insert(path, ids);
+    }
+    for asset in &site.assets {
+        targets.insert(asset.path.clone(), BTreeSet::new());
+    }
+
+    let site_root = site.config.permalink("/");
+    let mut broken = Vec::new();
+    for page in &site.pages {
+        // Where the last link was found, so the same url twice points at
+        // each place it's written in turn.
+        let mut cursor = 0;
+        for (url, image) in links(page) {
+            let Some(problem) = check_link(&targets, &site_root, &page.url, &url, image) else {
+                continue;
+            };
+
+            let written = written_forms(site, &url);
+            let location = locate(&page.markdown, &written, cursor)
+                .or_else(|| locate(&page.markdown, &written, 0))
+                .map(|(offset, line, column)| {
+                    cursor = offset
+                        + page.markdown[offset..]
+                            .chars()
+                            .next()
+                            .map_or(1, char::len_utf8);
+                    (line, column)
+                });
+            broken.push(BrokenLink {
+                source: page.source.clone(),
+                location,
+                url,
+                problem,
+            });
+        }
+    }
+
+    Ok(broken)
+}
+
+// The href of every `<a>` and src of every `<img>` in the page, with whether
+// it's an image.
+pub(crate) fn links(page: &Page) -> Vec<(String, bool)> {
+    let mut root = page.document.root.clone();
+    expand_raw_html(root.children_mut());
+
+    root.descendants()
+        .filter_map(|node| {
+            let (tag, attributes) = match node {
+                HTMLChildNode::Parent(p) => (p.tag(), p.attributes()?),
+                HTMLChildNode::Leaf(l) => (l.tag.as_deref()?, l.attributes.as_ref()?),
+                _ => return None,
+            };
+            match tag {
+                "a" => attributes.get("href").map(|u| (String::from(u), false)),
+                "img" => attributes.get("src").map(|u| (String::from(u), true)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn check_link(
+    targets: &BTreeMap<String, BTreeSet<String>>,
+    site_root: &str,
+    page_url: &str,
+    url: &str,
+    image: bool,
+) -> Option<Problem> {
+    if url.starts_with(INTERNAL_PREFIX) {
+        return Some(Problem::MissingSource);
+    }
+
+    // Absolute links to the site itself are checked like the rest.
+    let url = match url.strip_prefix(site_root) {
+        Some(rest) if site_root.contains("://") => format!("/{}", rest),
+        _ => String::from(url),
+    };
+    let url = resolve_url("", page_url, &url);
+    // Empty, or to another site.
+    if !url.starts_with('/') || url.starts_with("//") {
+        return None;
+    }
+
+    let (path, fragment) = url.split_once('#').unwrap_or((&url, ""));
+    let path = path
+        .split('?')
+        .next()
+        .unwrap_or(path)
+        .trim_start_matches('/');
+    let candidates = if path.is_empty() || path.ends_with('/') {
+        vec![format!("{}index.html", path)]
+    } else {
+        vec![String::from(path), format!("{}/index.html", path)]
+    };
+
+    let Some((found, ids)) = candidates.iter().find_map(|c| targets.get_key_value(c)) else {
+        let name = path.rsplit('/').next().unwrap_or("");
+        let file = name.contains('.') && !name.ends_with(".html");
+        return Some(if image || file {
+            Problem::MissingAsset
+        } else {
+            Problem::MissingPage
+        });
+    };
+
+    // `#` and `#top` go to the top of any page.
+    if found.ends_with(".html")
+        && !fragment.is_empty()
+        && fragment != "top"
+        && !ids.contains(fragment)
+    {
+        return Some(Problem::MissingAnchor);
+    }
+
+    None
+}
+
+// How the url could be written in the markdown, an `@/` link is written as
+// the source of the page it was resolved to.
+fn written_forms(site: &Site, url: &str) -> Vec<String> {
+    let mut forms = vec![String::from(url)];
+    let (path, suffix) = match url.find(['?', '#']) {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    for page in site.pages.iter().filter(|p| p.url == path) {
+        forms.push(format!("{}{}{}", INTERNAL_PREFIX, page.source, suffix));
+    }
+    forms
+}
+
+// The first place at or after `from` where one of the forms is written, as
+// the byte offset, line and column.
+pub(crate) fn locate(text: &str, forms: &[String], from: usize) -> Option<(usize, usize, usize)> {
+    let rest = text.get(from..)?;
+    let offset = forms.iter().filter_map(|f| rest.find(f.as_str())).min()? + from;
+
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    Some((offset, line, column))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::site::config::Config;
+    use crate::site::Asset;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let pages = vec![
+            Page::new("docs/intro.md", "# Intro\n\n## Setup\n\nText.").unwrap(),
+            Page::new(
+                "docs/links.md",
+                "---\ntitle: Links\n---\n[ok](@/docs/intro.md#setup) [top](#top)\n\
+                 [anchor](@/docs/intro.md#install)\n\
+                 [gone](@/docs/gone.md) [page](../nope/)\n\
+                 ![cat](/img/cat.png) ![dog](/img/dog.png)\n\
+                 [site](https://example.com/docs/intro/#nope) [other](https://rust-lang.org/)\n\
+                 <a href=\"/docs/\">docs</a> <a href=\"/missing/\">raw</a>\n\n\
+                 [again](../nope/)",
+            )
+            .unwrap(),
+        ];
+        let mut site = Site::new(
+            Config {
+                base_url: String::from("https://example.com"),
+                ..Config::default()
+            },
+            pages,
+        );
+        site.assets.push(Asset {
+            path: String::from("img/cat.png"),
+            source: Default::default(),
+        });
+        site.resolve_internal_links();
+
+        let found: Vec<String> = check(&site)
+            .unwrap()
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "docs/links.md:5:10: missing anchor /docs/intro/#install",
+                "docs/links.md:6:8: no page for @/docs/gone.md",
+                "docs/links.md:6:31: missing page ../nope/",
+                "docs/links.md:7:29: missing file /img/dog.png",
+                "docs/links.md:8:8: missing anchor https://example.com/docs/intro/#nope",
+                "docs/links.md:9:36: missing page /missing/",
+                "docs/links.md:11:9: missing page ../nope/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locate() {
+        let text = "a\nsee [x](/y/) and [z](/y/)";
+        let test_cases = vec![
+            ("Test first match", 0, Some((10, 2, 9))),
+            ("Test later match", 11, Some((23, 2, 22))),
+            ("Test nothing after", 25, None),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                locate(text, &[String::from("/y/")], *input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
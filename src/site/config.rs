@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::SiteError;
+
+// Settings for the whole site, read from `config.yml` at the root of the
+// site. Everything apart from `base_url` has a default.
+//
+// Ex.
+// base_url: https://example.com
+// title: My blog
+// feeds:
+//   content: summary
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Where the site is served from, used for every absolute url like the
+    // links in feeds.
+    pub base_url: String,
+    pub title: String,
+    pub description: String,
+    pub author: Option<String>,
+    // How many words go in a summary when a page has no `<!-- more -->`.
+    pub summary_words: usize,
+    pub emit: Emit,
+    pub urls: UrlConfig,
+    pub feeds: FeedConfig,
+    pub robots: RobotsConfig,
+    pub pagination: PaginationConfig,
+    pub external_links: ExternalLinksConfig,
+    pub search: SearchConfig,
+    // Front matter lists that pages are grouped by, each one gets a page
+    // listing its terms and a page for every term.
+    pub taxonomies: Vec<TaxonomyConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            base_url: String::from("/"),
+            title: String::new(),
+            description: String::new(),
+            author: None,
+            summary_words: 50,
+            emit: Emit::Html,
+            urls: UrlConfig::default(),
+            feeds: FeedConfig::default(),
+            robots: RobotsConfig::default(),
+            pagination: PaginationConfig::default(),
+            external_links: ExternalLinksConfig::default(),
+            search: SearchConfig::default(),
+            taxonomies: vec![
+                TaxonomyConfig::new("tags"),
+                TaxonomyConfig::new("categories"),
+            ],
+        }
+    }
+}
+
+// What's written for every page. The json is the parsed node tree of its
+// content, like `--emit ast-json` gives for a single file, for rendering it
+// somewhere else. Listings, feeds and the rest are always html.
+// Ex. blog/hello/index.html -> blog/hello/index.json
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Emit {
+    Html,
+    // Json where the html would go, without the html.
+    AstJson,
+    // Json next to the html.
+    Both,
+}
+
+impl Emit {
+    pub fn html(self) -> bool {
+        self != Emit::AstJson
+    }
+
+    pub fn json(self) -> bool {
+        self != Emit::Html
+    }
+}
+
+// Where pages go. Index pages are always at the url of their directory, and
+// a `url` in the front matter of a page beats all of these.
+//
+// Ex.
+// urls:
+//   permalinks:
+//     blog: /blog/:year/:month/:slug/
+//   pretty: false
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlConfig {
+    // Url patterns by section, a page goes by the one for the closest
+    // section above it and the rest are at /:section/:slug/. Patterns can
+    // have :year, :month and :day from the `date`, :slug, :section and
+    // :filename.
+    pub permalinks: BTreeMap<String, String>,
+    // What slugs are made from when there's no `slug` in the front matter.
+    pub slug_from: SlugFrom,
+    // Write every page as an index.html in a directory of its own, so its
+    // url ends in `/`. When false pages are .html files.
+    // Ex. /blog/hello/ or /blog/hello.html
+    pub pretty: bool,
+}
+
+impl Default for UrlConfig {
+    fn default() -> UrlConfig {
+        UrlConfig {
+            permalinks: BTreeMap::new(),
+            slug_from: SlugFrom::Filename,
+            pretty: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlugFrom {
+    Filename,
+    Title,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedConfig {
+    pub rss: bool,
+    pub atom: bool,
+    pub content: FeedContent,
+    // The newest pages that make it into a feed, 0 for all of them.
+    pub limit: usize,
+    // Also write a feed for every section, not only the whole site. Feeds for
+    // taxonomy terms are turned on for each taxonomy.
+    pub sections: bool,
+}
+
+impl Default for FeedConfig {
+    fn default() -> FeedConfig {
+        FeedConfig {
+            rss: true,
+            atom: true,
+            content: FeedContent::Full,
+            limit: 20,
+            sections: true,
+        }
+    }
+}
+
+// How much of each page goes in the feed.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedContent {
+    Full,
+    Summary,
+}
+
+// How section and term pages are split up, the first page is at the url of
+// the list and the rest at /page/2/ and so on under it.
+//
+// Ex.
+// pagination:
+//   per_page: 20
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    // Pages on each page of a list, 0 puts them all on one. A section can
+    // change it with `per_page` in its `_index.md`.
+    pub per_page: usize,
+    // How many page numbers are shown on each side of the current one, the
+    // rest are left out for an ellipsis apart from the first and last.
+    pub window: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> PaginationConfig {
+        PaginationConfig {
+            per_page: 10,
+            window: 2,
+        }
+    }
+}
+
+// How `check --external` goes about links to other sites.
+//
+// Ex.
+// external_links:
+//   concurrency: 4
+//   skip: [https://twitter.com/]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExternalLinksConfig {
+    // Requests made at the same time.
+    pub concurrency: usize,
+    // Time between two requests to the same host, so no site gets hammered.
+    pub domain_delay_ms: u64,
+    pub timeout_secs: u64,
+    // How long a link that worked isn't checked again, 0 checks every link
+    // on every run.
+    pub cache_hours: u64,
+    // Urls starting with any of these are never checked, for sites that turn
+    // away crawlers.
+    pub skip: Vec<String>,
+}
+
+impl Default for ExternalLinksConfig {
+    fn default() -> ExternalLinksConfig {
+        ExternalLinksConfig {
+            concurrency: 8,
+            domain_delay_ms: 1000,
+            timeout_secs: 10,
+            cache_hours: 24,
+            skip: vec![],
+        }
+    }
+}
+
+// An index of the words on every page and a script that searches it in the
+// browser, for sites that can't use a search service. Off by default.
+//
+// Ex.
+// search:
+//   enabled: true
+//   max_bytes: 200000
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    // Write search_index.json and search.js, and load the script in
+    // base.html.
+    pub enabled: bool,
+    // Cut english words down to their stem, so "installing" finds
+    // "installation".
+    pub stemming: bool,
+    // Leave out words like "the" and "of" that are on every page.
+    pub stop_words: bool,
+    // Length of the summary shown with each result.
+    pub summary_words: usize,
+    // How big the index can get, the words that matter least are dropped
+    // until it fits. 0 for no limit.
+    pub max_bytes: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            enabled: false,
+            stemming: true,
+            stop_words: true,
+            summary_words: 25,
+            max_bytes: 500_000,
+        }
+    }
+}
+
+// Ex.
+// taxonomies:
+//   - name: tags
+//   - name: authors
+//     feed: false
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaxonomyConfig {
+    // The front matter key, also used in the url like /tags/.
+    pub name: String,
+    // Write a feed for every term, like /tags/rust/rss.xml.
+    #[serde(default = "default_true")]
+    pub feed: bool,
+}
+
+impl TaxonomyConfig {
+    pub fn new(name: &str) -> TaxonomyConfig {
+        TaxonomyConfig {
+            name: String::from(name),
+            feed: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// What goes in robots.txt, by default every crawler is allowed everywhere.
+//
+// Ex.
+// robots:
+//   rules:
+//     - user_agent: "*"
+//       disallow: [/private/]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotsConfig {
+    // Set to false to leave robots.txt out, like when it's written by hand.
+    pub enabled: bool,
+    pub rules: Vec<RobotsRule>,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> RobotsConfig {
+        RobotsConfig {
+            enabled: true,
+            rules: vec![RobotsRule {
+                user_agent: String::from("*"),
+                allow: vec![],
+                disallow: vec![],
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotsRule {
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+impl Config {
+    pub fn from_yaml(yaml: &str) -> Result<Config, serde_yaml::Error> {
+        // An empty file is a valid config with every default.
+        if yaml.trim().is_empty() {
+            return Ok(Config::default());
+        }
+
+        serde_yaml::from_str(yaml)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, SiteError> {
+        let yaml = fs::read_to_string(path).map_err(|e| SiteError::Io(path.to_path_buf(), e))?;
+        Config::from_yaml(&yaml).map_err(|e| SiteError::Config(path.to_path_buf(), e))
+    }
+
+    // Ex. "https://example.com/blog/" and "/posts/" make
+    // "https://example.com/blog/posts/"
+    pub fn permalink(&self, url: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            url.trim_start_matches('/')
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_config_from_yaml() {
+        let test_cases = vec![
+            ("Test empty config uses the defaults", "", Config::default()),
+            (
+                "Test nested feed settings",
+                "base_url: https://example.com\ntitle: Blog\nfeeds:\n  content: summary\n  limit: 5\n",
+                Config {
+                    base_url: String::from("https://example.com"),
+                    title: String::from("Blog"),
+                    feeds: FeedConfig {
+                        content: FeedContent::Summary,
+                        limit: 5,
+                        ..FeedConfig::default()
+                    },
+                    ..Config::default()
+                },
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                &Config::from_yaml(input).unwrap(),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+
+        assert!(Config::from_yaml("titel: typo").is_err());
+    }
+
+    #[test]
+    fn test_permalink() {
+        let test_cases = vec![
+            (
+                "Test root base url",
+                "https://example.com",
+                "/blog/",
+                "https://example.com/blog/",
+            ),
+            (
+                "Test base url with a path",
+                "https://example.com/docs/",
+                "/intro/",
+                "https://example.com/docs/intro/",
+            ),
+        ];
+
+        for (title, base_url, url, expected) in test_cases.iter() {
+            let config = Config {
+                base_url: String::from(*base_url),
+                ..Config::default()
+            };
+            assert_eq!(
+                config.permalink(url),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                url,
+                expected
+            );
+        }
+    }
+}
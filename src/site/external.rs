@@ -0,0 +1,466 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::check::{links, locate};
+use super::config::ExternalLinksConfig;
+use super::{Site, SiteError};
+
+// Where the results of earlier runs are kept, at the root of the site.
+pub const CACHE_FILE: &str = ".link-cache.json";
+
+// Makes the requests, tests use a stand-in that doesn't need the network.
+pub trait HttpClient: Sync {
+    // The status the url answers with after following redirects, or why
+    // there wasn't an answer.
+    fn status(&self, url: &str) -> Result<u16, String>;
+}
+
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+
+impl UreqClient {
+    pub fn new(config: &ExternalLinksConfig) -> UreqClient {
+        UreqClient {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .user_agent(concat!("rust-ssg/", env!("CARGO_PKG_VERSION")))
+                .build(),
+        }
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn status(&self, url: &str) -> Result<u16, String> {
+        match self.agent.head(url).call() {
+            Ok(response) => Ok(response.status()),
+            // Some servers don't answer HEAD, so those get a GET.
+            Err(ureq::Error::Status(405 | 501, _)) => match self.agent.get(url).call() {
+                Ok(response) => Ok(response.status()),
+                Err(ureq::Error::Status(code, _)) => Ok(code),
+                Err(e) => Err(transport_error(url, e)),
+            },
+            Err(ureq::Error::Status(code, _)) => Ok(code),
+            Err(e) => Err(transport_error(url, e)),
+        }
+    }
+}
+
+// Without the url, which ureq puts at the start.
+// Ex. Connection Failed: Connect error: Connection refused (os error 111)
+fn transport_error(url: &str, error: ureq::Error) -> String {
+    let message = error.to_string();
+    match message.strip_prefix(url).and_then(|m| m.strip_prefix(": ")) {
+        Some(rest) => String::from(rest),
+        None => message,
+    }
+}
+
+// Links that worked on earlier runs, so they aren't asked for again until the
+// cache runs out. Broken ones are always checked again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkCache {
+    pub links: BTreeMap<String, CachedLink>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedLink {
+    pub status: u16,
+    // Unix seconds.
+    pub checked: i64,
+}
+
+impl LinkCache {
+    // A missing or unreadable cache starts over empty.
+    pub fn load(path: &Path) -> Result<LinkCache, SiteError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json).unwrap_or_default()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(LinkCache::default()),
+            Err(e) => Err(SiteError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SiteError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| SiteError::Io(path.to_path_buf(), io::Error::other(e)))?;
+        fs::write(path, json).map_err(|e| SiteError::Io(path.to_path_buf(), e))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BrokenExternal {
+    pub url: String,
+    // The line and column in the markdown file, like `BrokenLink`.
+    pub location: Option<(usize, usize)>,
+    // Ex. 404, or the error when the site couldn't be reached
+    pub reason: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ExternalReport {
+    // Different urls found.
+    pub links: usize,
+    // How many of them came from the cache.
+    pub cached: usize,
+    // By the source of the page they're on.
+    pub broken: BTreeMap<String, Vec<BrokenExternal>>,
+}
+
+impl fmt::Display for ExternalReport {
+    // Ex.
+    // blog/hello.md
+    //   3:9 https://example.com/gone (404)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (source, links) in &self.broken {
+            writeln!(f, "{}", source)?;
+            for link in links {
+                match link.location {
+                    Some((line, column)) => write!(f, "  {}:{} ", line, column)?,
+                    None => write!(f, "  ")?,
+                }
+                writeln!(f, "{} ({})", link.url, link.reason)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Asks for every link and image to another site found in the content of the
+// pages, each url once. Redirects are followed, anything that ends below 400
+// works.
+pub fn check_external(
+    site: &Site,
+    client: &dyn HttpClient,
+    cache: &mut LinkCache,
+    now: DateTime<Utc>,
+) -> ExternalReport {
+    let config = &site.config.external_links;
+    let site_root = site.config.permalink("/");
+    let is_external = |url: &str| {
+        (url.starts_with("http://") || url.starts_with("https://"))
+            && !url.starts_with(&site_root)
+            && !config.skip.iter().any(|s| url.starts_with(s.as_str()))
+    };
+
+    let found: Vec<Vec<String>> = site
+        .pages
+        .iter()
+        .map(|page| {
+            links(page)
+                .into_iter()
+                .map(|(url, _)| url)
+                .filter(|url| is_external(url))
+                .collect()
+        })
+        .collect();
+    let urls: BTreeSet<&String> = found.iter().flatten().collect();
+
+    let max_age = config.cache_hours as i64 * 3600;
+    let (cached, to_check): (Vec<&String>, Vec<&String>) = urls.iter().partition(|url| {
+        cache
+            .links
+            .get(url.as_str())
+            .is_some_and(|c| now.timestamp() - c.checked < max_age)
+    });
+
+    let results = fetch_all(client, to_check.into_iter().cloned().collect(), config);
+    for (url, result) in &results {
+        match result {
+            Ok(status) if *status < 400 => {
+                cache.links.insert(
+                    url.clone(),
+                    CachedLink {
+                        status: *status,
+                        checked: now.timestamp(),
+                    },
+                );
+            }
+            _ => {
+                cache.links.remove(url);
+            }
+        }
+    }
+
+    let mut report = ExternalReport {
+        links: urls.len(),
+        cached: cached.len(),
+        ..ExternalReport::default()
+    };
+    for (page, urls) in site.pages.iter().zip(&found) {
+        let mut cursor = 0;
+        for url in urls {
+            let reason = match results.get(url) {
+                Some(Ok(status)) if *status >= 400 => status.to_string(),
+                Some(Err(e)) => e.clone(),
+                _ => continue,
+            };
+
+            let forms = [url.clone(), url.replace('&', "&amp;")];
+            let location = locate(&page.markdown, &forms, cursor)
+                .or_else(|| locate(&page.markdown, &forms, 0))
+                .map(|(offset, line, column)| {
+                    cursor = offset + 1;
+                    (line, column)
+                });
+            report
+                .broken
+                .entry(page.source.clone())
+                .or_default()
+                .push(BrokenExternal {
+                    url: url.clone(),
+                    location,
+                    reason,
+                });
+        }
+    }
+
+    report
+}
+
+// Makes the requests on `concurrency` threads. Requests to the same host wait
+// `domain_delay_ms` after the one before, and hosts take turns in the queue so
+// the threads aren't all stuck waiting on one of them.
+fn fetch_all(
+    client: &dyn HttpClient,
+    urls: Vec<String>,
+    config: &ExternalLinksConfig,
+) -> BTreeMap<String, Result<u16, String>> {
+    let mut by_host: BTreeMap<String, VecDeque<String>> = BTreeMap::new();
+    for url in urls {
+        by_host.entry(host(&url)).or_default().push_back(url);
+    }
+    let mut queue = VecDeque::new();
+    while !by_host.is_empty() {
+        by_host.retain(|_, urls| {
+            queue.extend(urls.pop_front());
+            !urls.is_empty()
+        });
+    }
+
+    let queue = Mutex::new(queue);
+    // When each host can be asked next.
+    let next: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+    let results = Mutex::new(BTreeMap::new());
+    let delay = Duration::from_millis(config.domain_delay_ms);
+
+    thread::scope(|scope| {
+        for _ in 0..config.concurrency.max(1) {
+            scope.spawn(|| loop {
+                let Some(url) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+
+                let wait = {
+                    let mut next = next.lock().unwrap();
+                    let now = Instant::now();
+                    let host = host(&url);
+                    let slot = next.get(&host).copied().unwrap_or(now).max(now);
+                    next.insert(host, slot + delay);
+                    slot - now
+                };
+                thread::sleep(wait);
+
+                let result = client.status(&url);
+                results.lock().unwrap().insert(url, result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+// Ex. https://Example.com:8080/a?b -> example.com:8080
+fn host(url: &str) -> String {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    rest[..end].to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use crate::site::config::Config;
+    use crate::site::page::Page;
+
+    use super::*;
+
+    struct StandIn {
+        statuses: BTreeMap<&'static str, Result<u16, String>>,
+        asked: Mutex<Vec<String>>,
+    }
+
+    impl HttpClient for StandIn {
+        fn status(&self, url: &str) -> Result<u16, String> {
+            self.asked.lock().unwrap().push(String::from(url));
+            self.statuses
+                .get(url)
+                .cloned()
+                .unwrap_or_else(|| Err(String::from("no such host")))
+        }
+    }
+
+    fn site() -> Site {
+        let mut config = Config {
+            base_url: String::from("https://example.com"),
+            ..Config::default()
+        };
+        config.external_links.domain_delay_ms = 0;
+        config.external_links.skip = vec![String::from("https://skip.test/")];
+
+        Site::new(
+            config,
+            vec![
+                Page::new(
+                    "a.md",
+                    "[ok](https://ok.test/) [gone](https://gone.test/)\n\n\
+                     [self](https://example.com/a/) [skip](https://skip.test/x)",
+                )
+                .unwrap(),
+                Page::new(
+                    "b.md",
+                    "![img](https://ok.test/) <a href=\"https://down.test/\">down</a>",
+                )
+                .unwrap(),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_check_external() {
+        let client = StandIn {
+            statuses: BTreeMap::from([
+                ("https://ok.test/", Ok(200)),
+                ("https://gone.test/", Ok(404)),
+            ]),
+            asked: Mutex::new(vec![]),
+        };
+        let mut cache = LinkCache::default();
+        let now = Utc::now();
+
+        let report = check_external(&site(), &client, &mut cache, now);
+        assert_eq!(
+            report.to_string(),
+            "a.md\n  1:31 https://gone.test/ (404)\nb.md\n  1:35 https://down.test/ (no such host)\n"
+        );
+        assert_eq!((report.links, report.cached), (3, 0));
+        assert_eq!(client.asked.lock().unwrap().len(), 3);
+        assert_eq!(
+            cache.links.keys().collect::<Vec<_>>(),
+            vec!["https://ok.test/"]
+        );
+
+        // Only the broken ones are asked for again.
+        client.asked.lock().unwrap().clear();
+        let report = check_external(&site(), &client, &mut cache, now);
+        assert_eq!((report.links, report.cached), (3, 1));
+        let mut asked = client.asked.lock().unwrap().clone();
+        asked.sort();
+        assert_eq!(asked, vec!["https://down.test/", "https://gone.test/"]);
+    }
+
+    #[test]
+    fn test_domain_delay() {
+        let client = StandIn {
+            statuses: BTreeMap::new(),
+            asked: Mutex::new(vec![]),
+        };
+        let config = ExternalLinksConfig {
+            concurrency: 4,
+            domain_delay_ms: 50,
+            ..ExternalLinksConfig::default()
+        };
+        let urls = vec![
+            String::from("https://a.test/1"),
+            String::from("https://a.test/2"),
+            String::from("https://a.test/3"),
+            String::from("https://b.test/1"),
+        ];
+
+        let start = Instant::now();
+        let results = fetch_all(&client, urls, &config);
+        assert_eq!(results.len(), 4);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        // Hosts take turns, so b.test doesn't wait behind a.test.
+        let mut first = client.asked.lock().unwrap()[..2].to_vec();
+        first.sort();
+        assert_eq!(first, vec!["https://a.test/1", "https://b.test/1"]);
+    }
+
+    #[test]
+    fn test_ureq_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let status = if request.contains(" /ok ") {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+
+        let client = UreqClient::new(&ExternalLinksConfig::default());
+        let test_cases = vec![
+            ("Test page that's there", "ok", Ok(200)),
+            ("Test missing page", "gone", Ok(404)),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let url = format!("http://{}/{}", address, input);
+            assert_eq!(
+                client.status(&url),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_host() {
+        let test_cases = vec![
+            ("Test plain host", "https://example.com/a", "example.com"),
+            (
+                "Test port and case",
+                "http://Example.com:8080?a",
+                "example.com:8080",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                host(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
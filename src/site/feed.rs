@@ -0,0 +1,364 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::nodes::html_node::{escape_html, ToHtmlString};
+
+use super::config::{Config, FeedContent};
+use super::page::{sort_newest_first, Page};
+use super::urls::make_urls_absolute;
+
+// A list of pages written out as `rss.xml` and `atom.xml` next to the page
+// the feed is for.
+#[derive(Debug)]
+pub struct Feed<'a> {
+    pub title: String,
+    // The page the feed belongs to, the feed files go in the same directory.
+    // Ex. /blog/ has /blog/rss.xml and /blog/atom.xml
+    pub url: String,
+    // Newest first.
+    pub pages: Vec<&'a Page>,
+}
+
+impl<'a> Feed<'a> {
+    // Only pages with a date go in a feed, newest first and cut down to the
+    // limit from the config.
+    pub fn new(config: &Config, title: &str, url: &str, pages: &[&'a Page]) -> Feed<'a> {
+        let mut pages: Vec<&Page> = pages
+            .iter()
+            .copied()
+            .filter(|p| p.front_matter.date.is_some())
+            .collect();
+        sort_newest_first(&mut pages);
+        if config.feeds.limit > 0 {
+            pages.truncate(config.feeds.limit);
+        }
+
+        Feed {
+            title: String::from(title),
+            url: String::from(url),
+            pages,
+        }
+    }
+
+    pub fn rss_url(&self) -> String {
+        format!("{}rss.xml", self.url)
+    }
+
+    pub fn atom_url(&self) -> String {
+        format!("{}atom.xml", self.url)
+    }
+
+    // The newest change to any page in the feed.
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        self.pages.iter().filter_map(|p| updated(p)).max()
+    }
+}
+
+fn updated(page: &Page) -> Option<DateTime<Utc>> {
+    page.front_matter.updated.or(page.front_matter.date)
+}
+
+// Like `escape_html`, but also escapes `'` since feed readers don't all agree
+// on how attributes are quoted.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// The page as html for a feed reader, with every link made absolute since
+// the reader isn't on the site.
+fn content_html(config: &Config, page: &Page) -> String {
+    let permalink = config.permalink(&page.url);
+    let mut root = page.document.root.clone();
+    make_urls_absolute(&mut root, &config.base_url, &permalink);
+
+    match config.feeds.content {
+        FeedContent::Full => root.children().iter().map(|c| c.into_html()).collect(),
+        FeedContent::Summary => match &page.front_matter.summary {
+            Some(summary) => format!("<p>{}</p>", escape_html(summary)),
+            None => root.summary(config.summary_words).html,
+        },
+    }
+}
+
+fn summary_text(config: &Config, page: &Page) -> String {
+    match &page.front_matter.summary {
+        Some(summary) => summary.clone(),
+        None => page.summary(config.summary_words).text,
+    }
+}
+
+// Ex. Fri, 01 Mar 2024 09:30:00 +0000
+fn rss_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc2822()
+}
+
+// Ex. 2024-03-01T09:30:00Z
+fn atom_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn rss(config: &Config, feed: &Feed) -> String {
+    let mut xml = String::new();
+    let link = config.permalink(&feed.url);
+
+    // Writing to a String can't fail.
+    let _ = writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(
+        xml,
+        "<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">"
+    );
+    let _ = writeln!(xml, "<channel>");
+    let _ = writeln!(xml, "<title>{}</title>", escape_xml(&feed.title));
+    let _ = writeln!(xml, "<link>{}</link>", escape_xml(&link));
+    let _ = writeln!(
+        xml,
+        "<description>{}</description>",
+        escape_xml(&config.description)
+    );
+    let _ = writeln!(
+        xml,
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+        escape_xml(&config.permalink(&feed.rss_url()))
+    );
+    if let Some(date) = feed.updated() {
+        let _ = writeln!(xml, "<lastBuildDate>{}</lastBuildDate>", rss_date(&date));
+    }
+
+    for page in &feed.pages {
+        let permalink = config.permalink(&page.url);
+        let _ = writeln!(xml, "<item>");
+        let _ = writeln!(xml, "<title>{}</title>", escape_xml(&page.title()));
+        let _ = writeln!(xml, "<link>{}</link>", escape_xml(&permalink));
+        let _ = writeln!(
+            xml,
+            "<guid isPermaLink=\"true\">{}</guid>",
+            escape_xml(&permalink)
+        );
+        if let Some(date) = &page.front_matter.date {
+            let _ = writeln!(xml, "<pubDate>{}</pubDate>", rss_date(date));
+        }
+        for tag in &page.front_matter.tags {
+            let _ = writeln!(xml, "<category>{}</category>", escape_xml(tag));
+        }
+        let _ = writeln!(
+            xml,
+            "<description>{}</description>",
+            escape_xml(&content_html(config, page))
+        );
+        let _ = writeln!(xml, "</item>");
+    }
+
+    let _ = writeln!(xml, "</channel>");
+    let _ = writeln!(xml, "</rss>");
+    xml
+}
+
+pub fn atom(config: &Config, feed: &Feed) -> String {
+    let mut xml = String::new();
+    let link = config.permalink(&feed.url);
+    let self_link = config.permalink(&feed.atom_url());
+
+    let _ = writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(xml, "<feed xmlns=\"http://www.w3.org/2005/Atom\">");
+    let _ = writeln!(xml, "<id>{}</id>", escape_xml(&self_link));
+    let _ = writeln!(xml, "<title>{}</title>", escape_xml(&feed.title));
+    if !config.description.is_empty() {
+        let _ = writeln!(
+            xml,
+            "<subtitle>{}</subtitle>",
+            escape_xml(&config.description)
+        );
+    }
+    let _ = writeln!(
+        xml,
+        "<link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>",
+        escape_xml(&self_link)
+    );
+    let _ = writeln!(xml, "<link href=\"{}\"/>", escape_xml(&link));
+    // Atom needs an updated date, an empty feed has never been updated so it
+    // gets the start of time.
+    let _ = writeln!(
+        xml,
+        "<updated>{}</updated>",
+        atom_date(&feed.updated().unwrap_or_default())
+    );
+    if let Some(author) = &config.author {
+        let _ = writeln!(xml, "<author><name>{}</name></author>", escape_xml(author));
+    }
+
+    for page in &feed.pages {
+        let permalink = config.permalink(&page.url);
+        let _ = writeln!(xml, "<entry>");
+        let _ = writeln!(xml, "<id>{}</id>", escape_xml(&permalink));
+        let _ = writeln!(xml, "<title>{}</title>", escape_xml(&page.title()));
+        let _ = writeln!(xml, "<link href=\"{}\"/>", escape_xml(&permalink));
+        if let Some(date) = &page.front_matter.date {
+            let _ = writeln!(xml, "<published>{}</published>", atom_date(date));
+        }
+        if let Some(date) = updated(page) {
+            let _ = writeln!(xml, "<updated>{}</updated>", atom_date(&date));
+        }
+        for tag in &page.front_matter.tags {
+            let _ = writeln!(xml, "<category term=\"{}\"/>", escape_xml(tag));
+        }
+        let _ = writeln!(
+            xml,
+            "<summary>{}</summary>",
+            escape_xml(&summary_text(config, page))
+        );
+        if config.feeds.content == FeedContent::Full {
+            let _ = writeln!(
+                xml,
+                "<content type=\"html\">{}</content>",
+                escape_xml(&content_html(config, page))
+            );
+        }
+        let _ = writeln!(xml, "</entry>");
+    }
+
+    let _ = writeln!(xml, "</feed>");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config(content: FeedContent) -> Config {
+        let mut config = Config {
+            base_url: String::from("https://example.com"),
+            title: String::from("Tom & Jerry's blog"),
+            description: String::from("Cats <and> mice"),
+            author: Some(String::from("Tom")),
+            ..Config::default()
+        };
+        config.feeds.content = content;
+        config
+    }
+
+    fn pages() -> Vec<Page> {
+        vec![
+            Page::new(
+                "blog/old.md",
+                "---\ntitle: Old & busted\ndate: 2024-01-01\n---\nSee ![cat](cat.png).",
+            )
+            .unwrap(),
+            Page::new(
+                "blog/new.md",
+                "---\ntitle: New\ndate: 2024-03-01 09:30\ntags: [rust]\n---\nFirst &copy; [home](/).\n\n<!-- more -->\n\nRest.",
+            )
+            .unwrap(),
+            Page::new("blog/undated.md", "No date, no feed.").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom &amp; Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp;amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_feeds() {
+        let pages = pages();
+        let pages: Vec<&Page> = pages.iter().collect();
+
+        let test_cases = vec![
+            (
+                "Test full rss",
+                rss(
+                    &config(FeedContent::Full),
+                    &Feed::new(&config(FeedContent::Full), "Blog", "/blog/", &pages),
+                ),
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">
+<channel>
+<title>Blog</title>
+<link>https://example.com/blog/</link>
+<description>Cats &lt;and&gt; mice</description>
+<atom:link href=\"https://example.com/blog/rss.xml\" rel=\"self\" type=\"application/rss+xml\"/>
+<lastBuildDate>Fri, 1 Mar 2024 09:30:00 +0000</lastBuildDate>
+<item>
+<title>New</title>
+<link>https://example.com/blog/new/</link>
+<guid isPermaLink=\"true\">https://example.com/blog/new/</guid>
+<pubDate>Fri, 1 Mar 2024 09:30:00 +0000</pubDate>
+<category>rust</category>
+<description>&lt;p&gt;First © &lt;a href=&quot;https://example.com/&quot;&gt;home&lt;/a&gt;.&lt;/p&gt;&lt;!-- more --&gt;&lt;p&gt;Rest.&lt;/p&gt;</description>
+</item>
+<item>
+<title>Old &amp; busted</title>
+<link>https://example.com/blog/old/</link>
+<guid isPermaLink=\"true\">https://example.com/blog/old/</guid>
+<pubDate>Mon, 1 Jan 2024 00:00:00 +0000</pubDate>
+<description>&lt;p&gt;See &lt;img src=&quot;https://example.com/blog/old/cat.png&quot; alt=&quot;cat&quot;&gt;.&lt;/p&gt;</description>
+</item>
+</channel>
+</rss>
+",
+            ),
+            (
+                "Test summary atom",
+                atom(
+                    &config(FeedContent::Summary),
+                    &Feed::new(&config(FeedContent::Summary), "Tom & Jerry's blog", "/", &pages[1..]),
+                ),
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+<id>https://example.com/atom.xml</id>
+<title>Tom &amp; Jerry&apos;s blog</title>
+<subtitle>Cats &lt;and&gt; mice</subtitle>
+<link href=\"https://example.com/atom.xml\" rel=\"self\" type=\"application/atom+xml\"/>
+<link href=\"https://example.com/\"/>
+<updated>2024-03-01T09:30:00Z</updated>
+<author><name>Tom</name></author>
+<entry>
+<id>https://example.com/blog/new/</id>
+<title>New</title>
+<link href=\"https://example.com/blog/new/\"/>
+<published>2024-03-01T09:30:00Z</published>
+<updated>2024-03-01T09:30:00Z</updated>
+<category term=\"rust\"/>
+<summary>First © home.</summary>
+</entry>
+</feed>
+",
+            ),
+        ];
+
+        for (title, output, expected) in test_cases.iter() {
+            assert_eq!(
+                output, expected,
+                "\"{}\" test failed and expexted: {}",
+                title, expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_summary_rss_description() {
+        let config = config(FeedContent::Summary);
+        let pages = pages();
+
+        assert_eq!(
+            content_html(&config, &pages[1]),
+            "<p>First © <a href=\"https://example.com/\">home</a>.</p>"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+// The yaml block at the top of a page, between two `---` lines.
+//
+// Ex.
+// ---
+// title: Hello
+// date: 2024-03-01
+// tags: [rust, web]
+// ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    // Used in the url instead of one made from the file name or title.
+    pub slug: Option<String>,
+    // The whole url of the page, whatever the permalinks say.
+    // Ex. /2019/03/old-post.html
+    pub url: Option<String>,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: Option<DateTime<Utc>>,
+    // When the page last changed, feeds fall back to `date` without it.
+    #[serde(deserialize_with = "deserialize_date")]
+    pub updated: Option<DateTime<Utc>>,
+    // Drafts are only built with `--drafts`.
+    pub draft: bool,
+    // When the page comes down, like `date` in the future it's only built
+    // with `--future` after that.
+    #[serde(deserialize_with = "deserialize_date")]
+    pub expiry_date: Option<DateTime<Utc>>,
+    // Used instead of the summary taken from the content.
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    // Set to false to leave the page out of sitemap.xml.
+    pub sitemap: bool,
+    // Set to false to leave the page out of the search index.
+    pub search: bool,
+    // Lighter pages come first in sections sorted by weight.
+    pub weight: Option<i64>,
+    // How the pages of a section are ordered, read from its `_index.md`.
+    pub sort_by: SortBy,
+    // Pages on each page of the section's listing, read from its
+    // `_index.md` instead of the config.
+    pub per_page: Option<usize>,
+    // Everything else, like the terms of taxonomies from the config. It's
+    // handed to templates as `page.extra`.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl Default for FrontMatter {
+    fn default() -> FrontMatter {
+        FrontMatter {
+            title: None,
+            slug: None,
+            url: None,
+            date: None,
+            updated: None,
+            draft: false,
+            expiry_date: None,
+            summary: None,
+            tags: vec![],
+            categories: vec![],
+            sitemap: true,
+            search: true,
+            weight: None,
+            sort_by: SortBy::Date,
+            per_page: None,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    // Newest first.
+    Date,
+    // Lowest weight first, pages without one go last.
+    Weight,
+    Title,
+}
+
+impl FrontMatter {
+    // The terms the page has for a taxonomy, a single term can be written
+    // without the list.
+    // Ex. authors: [Ann, Bob] or authors: Ann
+    pub fn terms(&self, taxonomy: &str) -> Vec<String> {
+        match taxonomy {
+            "tags" => self.tags.clone(),
+            "categories" => self.categories.clone(),
+            _ => match self.extra.get(taxonomy) {
+                Some(serde_yaml::Value::Sequence(terms)) => {
+                    terms.iter().filter_map(yaml_string).collect()
+                }
+                Some(term) => yaml_string(term).into_iter().collect(),
+                None => vec![],
+            },
+        }
+    }
+}
+
+fn yaml_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// Splits a page into its front matter and the markdown after it. Pages
+// without front matter get the defaults.
+pub fn split_front_matter(source: &str) -> Result<(FrontMatter, &str), FrontMatterError> {
+    let Some(rest) = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    else {
+        return Ok((FrontMatter::default(), source));
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let front_matter = if yaml.trim().is_empty() {
+                FrontMatter::default()
+            } else {
+                serde_yaml::from_str(yaml).map_err(FrontMatterError::Yaml)?
+            };
+            check_path("url", &front_matter.url)?;
+            check_path("slug", &front_matter.slug)?;
+
+            return Ok((front_matter, body));
+        }
+        offset += line.len();
+    }
+
+    Err(FrontMatterError::Unclosed)
+}
+
+// A `url` or `slug` ends up as a path in the output directory, so it can't
+// climb out of it or point at another site.
+// Ex. ../../etc, posts\hello, https://example.com/
+fn check_path(field: &'static str, value: &Option<String>) -> Result<(), FrontMatterError> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+
+    let has_scheme = value.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+    if value.contains('\\') || has_scheme || value.split('/').any(|s| s == "..") {
+        return Err(FrontMatterError::Path(field, value.clone()));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum FrontMatterError {
+    // The opening `---` never got a closing one.
+    Unclosed,
+    Yaml(serde_yaml::Error),
+    // A `url` or `slug` with `..`, a backslash or a scheme in it.
+    Path(&'static str, String),
+}
+
+impl std::fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrontMatterError::Unclosed => write!(f, "front matter is missing its closing ---"),
+            FrontMatterError::Yaml(e) => write!(f, "invalid front matter: {}", e),
+            FrontMatterError::Path(field, value) => write!(
+                f,
+                "invalid {} {:?}: it can't have .., a backslash or a scheme in it",
+                field, value
+            ),
+        }
+    }
+}
+
+// Reads the dates people actually write, times without a zone are taken as
+// UTC.
+// Ex. 2024-03-01, 2024-03-01 09:30, 2024-03-01T09:30:00+02:00
+pub fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(date.and_utc());
+        }
+    }
+
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(text) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    parse_date(&text)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid date {:?}", text)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_split_front_matter() {
+        let test_cases = vec![
+            (
+                "Test page without front matter",
+                "# Hello\n",
+                FrontMatter::default(),
+                "# Hello\n",
+            ),
+            (
+                "Test title, date and tags",
+                "---\ntitle: Hello\ndate: 2024-03-01\ntags: [rust, web]\n---\n# Hello\n",
+                FrontMatter {
+                    title: Some(String::from("Hello")),
+                    date: Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+                    tags: vec![String::from("rust"), String::from("web")],
+                    ..FrontMatter::default()
+                },
+                "# Hello\n",
+            ),
+            (
+                "Test empty front matter",
+                "---\n---\nText",
+                FrontMatter::default(),
+                "Text",
+            ),
+            (
+                "Test a thematic break later on isn't front matter",
+                "Text\n\n---\n",
+                FrontMatter::default(),
+                "Text\n\n---\n",
+            ),
+        ];
+
+        for (title, input, expected, body) in test_cases.iter() {
+            let (front_matter, rest) = split_front_matter(input).unwrap();
+            assert_eq!(
+                (&front_matter, rest),
+                (expected, *body),
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+
+        assert!(matches!(
+            split_front_matter("---\ntitle: Hello\n"),
+            Err(FrontMatterError::Unclosed)
+        ));
+        assert!(matches!(
+            split_front_matter("---\ndate: soon\n---\n"),
+            Err(FrontMatterError::Yaml(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        let test_cases = vec![
+            ("Test url climbing out", "url: ../../escaped", "url"),
+            ("Test .. in the middle", "url: /blog/../../x/", "url"),
+            ("Test backslash", "url: 'posts\\hello'", "url"),
+            ("Test scheme", "url: https://example.com/", "url"),
+            (
+                "Test scheme without slashes",
+                "url: 'javascript:alert(1)'",
+                "url",
+            ),
+            ("Test slug climbing out", "slug: ../hello", "slug"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let source = format!("---\n{}\n---\n", input);
+            let result = split_front_matter(&source);
+            assert!(
+                matches!(result, Err(FrontMatterError::Path(field, _)) if field == *expected),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+
+        let (front_matter, _) =
+            split_front_matter("---\nurl: /notes/a..b/12:30.html\nslug: v1.2\n---\n").unwrap();
+        assert_eq!(front_matter.url.as_deref(), Some("/notes/a..b/12:30.html"));
+    }
+
+    #[test]
+    fn test_terms() {
+        let (front_matter, _) = split_front_matter(
+            "---\ntags: [rust]\ncategories: [news]\nauthors: Ann\nseries: [1, two]\n---\n",
+        )
+        .unwrap();
+
+        let test_cases = vec![
+            ("Test tags", "tags", vec!["rust"]),
+            ("Test categories", "categories", vec!["news"]),
+            ("Test a single term without a list", "authors", vec!["Ann"]),
+            ("Test numbers are terms too", "series", vec!["1", "two"]),
+            ("Test missing taxonomy", "topics", vec![]),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                front_matter.terms(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let test_cases = vec![
+            ("Test date only", "2024-03-01", Some((2024, 3, 1, 0, 0))),
+            (
+                "Test date and time",
+                "2024-03-01 09:30",
+                Some((2024, 3, 1, 9, 30)),
+            ),
+            (
+                "Test rfc 3339 with an offset",
+                "2024-03-01T09:30:00+02:00",
+                Some((2024, 3, 1, 7, 30)),
+            ),
+            ("Test not a date", "March 1st", None),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let expected =
+                expected.map(|(y, m, d, h, min)| Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap());
+            assert_eq!(
+                parse_date(input),
+                expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::nodes::html_node::{HTMLAttributes, HTMLChildNode};
+use crate::nodes::leaf_node::LeafNode;
+use crate::nodes::parent_node::ParentNode;
+use crate::nodes::visit::{walk_parent_mut, VisitorMut};
+use crate::parser::html::{expand_raw_html, parse_html};
+
+use super::urls::slugify;
+
+// Links starting with this point at another markdown file in the content
+// directory rather than at a url, so they keep working when urls change.
+// Ex. [Setup](@/docs/intro.md#setup)
+pub const INTERNAL_PREFIX: &str = "@/";
+
+const HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+// Gives every heading without an id one made from its text, so a part of a
+// page can be linked to. The same text twice gets a number on the end.
+// Ex. ## Getting started -> <h2 id="getting-started">
+pub fn add_heading_ids(root: &mut ParentNode) {
+    let mut used = BTreeSet::new();
+    collect_ids(root.children(), &mut used);
+    root.accept_mut(&mut HeadingIds { used });
+}
+
+// Every id under the nodes, the ones in raw html included. Anchors with a
+// `name` count too, since links can go to those the same way.
+pub fn collect_ids(nodes: &[HTMLChildNode], ids: &mut BTreeSet<String>) {
+    for node in nodes {
+        let attributes = match node {
+            HTMLChildNode::Parent(p) => {
+                collect_ids(p.children(), ids);
+                p.attributes().map(|a| (p.tag(), a))
+            }
+            HTMLChildNode::Leaf(l) => l
+                .attributes
+                .as_ref()
+                .map(|a| (l.tag.as_deref().unwrap_or(""), a)),
+            HTMLChildNode::Raw(html) => {
+                // Comments and doctypes come back as raw html again.
+                let mut nodes = parse_html(html);
+                nodes.retain(|n| !matches!(n, HTMLChildNode::Raw(_)));
+                collect_ids(&nodes, ids);
+                None
+            }
+            HTMLChildNode::HTML(_) => None,
+        };
+
+        if let Some((tag, attributes)) = attributes {
+            if let Some(id) = attributes.get("id") {
+                ids.insert(String::from(id));
+            }
+            if let Some(name) = attributes.get("name").filter(|_| tag == "a") {
+                ids.insert(String::from(name));
+            }
+        }
+    }
+}
+
+struct HeadingIds {
+    used: BTreeSet<String>,
+}
+
+impl VisitorMut for HeadingIds {
+    fn visit_parent_mut(&mut self, node: &mut ParentNode) {
+        let has_id = node.attributes().is_some_and(|a| a.contains("id"));
+        if HEADINGS.contains(&node.tag()) && !has_id {
+            let mut base = slugify(&node.to_plain_text());
+            if base.is_empty() {
+                base = String::from("heading");
+            }
+
+            let mut id = base.clone();
+            let mut n = 1;
+            while self.used.contains(&id) {
+                id = format!("{}-{}", base, n);
+                n += 1;
+            }
+            node.attributes_mut().set("id", &id);
+            self.used.insert(id);
+        }
+        walk_parent_mut(self, node);
+    }
+}
+
+// The url an `@/` link ends up at, `urls` has the url of every page by its
+// source. None when it isn't an `@/` link or there's no such page.
+// Ex. @/docs/intro.md#setup -> /docs/intro/#setup
+pub fn internal_url(link: &str, urls: &BTreeMap<String, String>) -> Option<String> {
+    let link = link.strip_prefix(INTERNAL_PREFIX)?;
+    let (source, suffix) = match link.find(['?', '#']) {
+        Some(i) => link.split_at(i),
+        None => (link, ""),
+    };
+
+    urls.get(source).map(|url| format!("{}{}", url, suffix))
+}
+
+// Rewrites the `@/` links and images in the tree to the urls of the pages
+// they point at. Ones without a page are left alone for `check` to find.
+pub fn resolve_internal_links(root: &mut ParentNode, urls: &BTreeMap<String, String>) {
+    let in_raw_html = root
+        .descendants()
+        .any(|n| matches!(n, HTMLChildNode::Raw(html) if html.contains(INTERNAL_PREFIX)));
+    if in_raw_html {
+        expand_raw_html(root.children_mut());
+    }
+
+    root.accept_mut(&mut InternalLinks { urls });
+}
+
+struct InternalLinks<'a> {
+    urls: &'a BTreeMap<String, String>,
+}
+
+impl InternalLinks<'_> {
+    fn rewrite(&self, attributes: &mut HTMLAttributes) {
+        for name in ["href", "src"] {
+            if let Some(url) = attributes
+                .get(name)
+                .and_then(|u| internal_url(u, self.urls))
+            {
+                attributes.set(name, &url);
+            }
+        }
+    }
+}
+
+impl VisitorMut for InternalLinks<'_> {
+    fn visit_parent_mut(&mut self, node: &mut ParentNode) {
+        if node.attributes().is_some() {
+            self.rewrite(node.attributes_mut());
+        }
+        walk_parent_mut(self, node);
+    }
+
+    fn visit_leaf_mut(&mut self, node: &mut LeafNode) {
+        if let Some(attributes) = node.attributes.as_mut() {
+            self.rewrite(attributes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::parser::markdown_to_html_node;
+
+    use super::*;
+
+    #[test]
+    fn test_add_heading_ids() {
+        let test_cases = vec![
+            (
+                "Test id from the text",
+                "## Getting *started*",
+                "<div><h2 id=\"getting-started\">Getting <i>started</i></h2></div>",
+            ),
+            (
+                "Test repeated headings",
+                "# Setup\n\n# Setup",
+                "<div><h1 id=\"setup\">Setup</h1><h1 id=\"setup-1\">Setup</h1></div>",
+            ),
+            (
+                "Test heading without text",
+                "# !!",
+                "<div><h1 id=\"heading\">!!</h1></div>",
+            ),
+            (
+                "Test ids already on the page are skipped",
+                "<p id=\"intro\">Hi</p>\n\n# Intro",
+                "<div><p id=\"intro\">Hi</p><h1 id=\"intro-1\">Intro</h1></div>",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let mut root = markdown_to_html_node(input).root;
+            add_heading_ids(&mut root);
+            assert_eq!(
+                root.into_html(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_internal_links() {
+        let urls = BTreeMap::from([
+            (String::from("docs/intro.md"), String::from("/docs/intro/")),
+            (String::from("_index.md"), String::from("/")),
+        ]);
+        let test_cases = vec![
+            (
+                "Test link with an anchor",
+                "[Setup](@/docs/intro.md#setup)",
+                "<div><p><a href=\"/docs/intro/#setup\">Setup</a></p></div>",
+            ),
+            (
+                "Test link to a section",
+                "[Home](@/_index.md)",
+                "<div><p><a href=\"/\">Home</a></p></div>",
+            ),
+            (
+                "Test missing page is left alone",
+                "[Gone](@/gone.md)",
+                "<div><p><a href=\"@/gone.md\">Gone</a></p></div>",
+            ),
+            (
+                "Test link in raw html",
+                "<p><a href=\"@/docs/intro.md\">Intro</a></p>",
+                "<div><p><a href=\"/docs/intro/\">Intro</a></p></div>",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let mut root = markdown_to_html_node(input).root;
+            resolve_internal_links(&mut root, &urls);
+            assert_eq!(
+                root.into_html(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,1096 @@
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use minijinja::context;
+
+use self::config::Config;
+use self::feed::{atom, rss, Feed};
+use self::front_matter::FrontMatterError;
+use self::links::resolve_internal_links;
+use self::page::{output_path, Page, Status};
+use self::pagination::{paginate, redirect_html};
+use self::permalinks::page_url;
+use self::search::{search_index, search_script, INDEX_FILE, SCRIPT_FILE};
+use self::section::Sections;
+use self::sitemap::{robots_txt, sitemaps, SitemapEntry};
+use self::taxonomy::taxonomies;
+use self::templates::{
+    PageContext, SectionContext, SiteContext, TaxonomyContext, Templates, TermContext,
+    TEMPLATES_DIR,
+};
+
+pub mod check;
+pub mod config;
+pub mod external;
+pub mod feed;
+pub mod front_matter;
+pub mod links;
+pub mod page;
+pub mod pagination;
+pub mod permalinks;
+pub mod search;
+pub mod section;
+pub mod sitemap;
+pub mod stem;
+pub mod taxonomy;
+pub mod templates;
+pub mod urls;
+
+// A site is a directory with a `config.yml` and a `content` directory of
+// markdown pages, with optional `templates` to change how pages look.
+// Building it writes the html and feeds to `public`.
+//
+// Ex.
+// config.yml
+// content/index.md        -> public/index.html
+// content/blog/hello.md   -> public/blog/hello/index.html
+pub const CONFIG_FILE: &str = "config.yml";
+pub const CONTENT_DIR: &str = "content";
+// Files copied into the output as they are, like a favicon. Files in
+// `content` that aren't markdown are copied too, next to the pages.
+pub const STATIC_DIR: &str = "static";
+pub const OUTPUT_DIR: &str = "public";
+
+#[derive(Debug)]
+pub enum SiteError {
+    Io(PathBuf, io::Error),
+    Config(PathBuf, serde_yaml::Error),
+    FrontMatter(PathBuf, FrontMatterError),
+    // A template that doesn't parse or fails to render, with its name.
+    Template(String, minijinja::Error),
+    // A page whose url can't be made from the permalinks in the config.
+    Url(PathBuf, String),
+    // A file in the output that more than one page would be written to,
+    // with the pages.
+    Collision(PathBuf, Vec<String>),
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SiteError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SiteError::Config(path, e) => write!(f, "{}: invalid config: {}", path.display(), e),
+            SiteError::FrontMatter(path, e) => write!(f, "{}: {}", path.display(), e),
+            SiteError::Template(name, e) => write!(f, "template {}: {:#}", name, e),
+            SiteError::Url(path, e) => write!(f, "{}: {}", path.display(), e),
+            SiteError::Collision(path, sources) => write!(
+                f,
+                "{}: written by both {}",
+                path.display(),
+                sources.join(" and ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+// A file the build writes, relative to the output directory.
+#[derive(Debug, PartialEq)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+// Which pages that aren't published make it into a build. They're left out
+// by default, like for the build that gets deployed.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    // Pages with `draft: true`.
+    pub drafts: bool,
+    // Pages with a `date` after `now`. Pages past their `expiry_date` are
+    // never built.
+    pub future: bool,
+    pub now: DateTime<Utc>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> BuildOptions {
+        BuildOptions {
+            drafts: false,
+            future: false,
+            now: Utc::now(),
+        }
+    }
+}
+
+// A file from `content` or `static` that's copied as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+    // Relative to the output directory, always with `/` separators.
+    // Ex. content/blog/cat.png -> blog/cat.png
+    pub path: String,
+    pub source: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Site {
+    pub config: Config,
+    // Sorted by source path so builds always come out the same.
+    pub pages: Vec<Page>,
+    // Sorted by path.
+    pub assets: Vec<Asset>,
+    pub templates: Templates,
+}
+
+impl Site {
+    pub fn new(config: Config, mut pages: Vec<Page>) -> Site {
+        pages.sort_by(|a, b| a.source.cmp(&b.source));
+        Site {
+            config,
+            pages,
+            assets: vec![],
+            templates: Templates::default(),
+        }
+    }
+
+    pub fn load(root: &Path, options: &BuildOptions) -> Result<Site, SiteError> {
+        let config = Config::load(&root.join(CONFIG_FILE))?;
+        let content = root.join(CONTENT_DIR);
+
+        let mut files = Vec::new();
+        find_files(&content, &mut files)?;
+        let (sources, others): (Vec<PathBuf>, Vec<PathBuf>) = files
+            .into_iter()
+            .partition(|p| p.extension().is_some_and(|e| e == "md"));
+
+        let mut assets: Vec<Asset> = others
+            .into_iter()
+            .map(|path| Asset {
+                path: relative_path(&content, &path),
+                source: path,
+            })
+            .collect();
+        let static_dir = root.join(STATIC_DIR);
+        if static_dir.is_dir() {
+            let mut files = Vec::new();
+            find_files(&static_dir, &mut files)?;
+            assets.extend(files.into_iter().map(|path| Asset {
+                path: relative_path(&static_dir, &path),
+                source: path,
+            }));
+        }
+        assets.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut pages = Vec::new();
+        for path in sources {
+            let markdown = fs::read_to_string(&path).map_err(|e| SiteError::Io(path.clone(), e))?;
+            let source = relative_path(&content, &path);
+
+            let mut page = Page::new(&source, &markdown).map_err(|e| match e {
+                SiteError::FrontMatter(_, e) => SiteError::FrontMatter(path.clone(), e),
+                e => e,
+            })?;
+            page.modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(DateTime::<Utc>::from);
+            pages.push(page);
+        }
+
+        let mut site = Site::new(config, pages);
+        site.assets = assets;
+        site.publish(options);
+        site.assign_urls()?;
+        site.resolve_internal_links();
+        site.templates = Templates::load(&root.join(TEMPLATES_DIR))?;
+        Ok(site)
+    }
+
+    // Drops the pages the options leave out. Everything else, like feeds and
+    // listings, only sees what's left.
+    pub fn publish(&mut self, options: &BuildOptions) {
+        self.pages.retain_mut(|page| {
+            page.status = page.status_at(options.now);
+            match page.status {
+                Status::Published => true,
+                Status::Draft => options.drafts,
+                Status::Scheduled => options.future,
+                Status::Expired => false,
+            }
+        });
+    }
+
+    // Gives every page its url from the permalinks in the config, before
+    // `resolve_internal_links` so links go to the new urls.
+    pub fn assign_urls(&mut self) -> Result<(), SiteError> {
+        for page in &mut self.pages {
+            page.url = page_url(&self.config.urls, page)
+                .map_err(|e| SiteError::Url(PathBuf::from(&page.source), e))?;
+        }
+        Ok(())
+    }
+
+    // Rewrites `@/` links to the urls of the pages they point at, after
+    // `publish` so links to pages left out stay broken.
+    pub fn resolve_internal_links(&mut self) {
+        let urls: BTreeMap<String, String> = self
+            .pages
+            .iter()
+            .map(|p| (p.source.clone(), p.url.clone()))
+            .collect();
+        for page in &mut self.pages {
+            resolve_internal_links(&mut page.document.root, &urls);
+        }
+    }
+
+    // Every file of the site apart from the assets, without touching the
+    // disk. Fails when two of them, or one of them and an asset, would be
+    // written to the same path.
+    pub fn render(&self) -> Result<Vec<OutputFile>, SiteError> {
+        let site = SiteContext::new(&self.config);
+        let mut files = Vec::new();
+        // Every html page, for the sitemap.
+        let mut entries = Vec::new();
+        let sections = Sections::new(&self.pages);
+
+        for page in &self.pages {
+            if self.config.emit.json() {
+                files.push(OutputFile {
+                    path: output_path(&page.url).with_extension("json"),
+                    contents: serde_json::to_string_pretty(&page.document.root)
+                        .expect("nodes are valid json"),
+                });
+            }
+            // Rendered with the listing of its section below.
+            if page.is_section_index() || !self.config.emit.html() {
+                continue;
+            }
+
+            let context = PageContext::new(&self.config, page);
+            let html = self.templates.render(
+                "page.html",
+                context! {
+                    site => &site,
+                    page => context.with_navigation(sections.navigation(page)),
+                },
+            )?;
+            files.push(OutputFile {
+                path: output_path(&page.url),
+                contents: html,
+            });
+            if page.front_matter.sitemap && page.is_published() {
+                entries.push(SitemapEntry {
+                    url: self.config.permalink(&page.url),
+                    lastmod: page.lastmod(),
+                });
+            }
+        }
+
+        for section in sections.iter().filter(|s| s.is_listed()) {
+            let per_page = section
+                .index
+                .and_then(|i| i.front_matter.per_page)
+                .unwrap_or(self.config.pagination.per_page);
+            let lastmod = section
+                .index
+                .and_then(|i| i.lastmod())
+                .max(newest(&section.pages));
+            let pagers = paginate(
+                &section.url,
+                &section.pages,
+                per_page,
+                self.config.pagination.window,
+            );
+            if pagers.len() > 1 {
+                files.push(self.first_page_redirect(&section.url));
+            }
+
+            for (paginator, pages) in pagers {
+                let html = self.templates.render(
+                    "section.html",
+                    context! {
+                        site => &site,
+                        section => SectionContext::new(&self.config, &sections, section, pages),
+                        paginator => &paginator,
+                    },
+                )?;
+                files.push(OutputFile {
+                    path: output_path(&paginator.url),
+                    contents: html,
+                });
+                if section
+                    .index
+                    .is_none_or(|i| i.front_matter.sitemap && i.is_published())
+                {
+                    entries.push(SitemapEntry {
+                        url: self.config.permalink(&paginator.url),
+                        lastmod,
+                    });
+                }
+            }
+        }
+
+        for taxonomy in taxonomies(&self.config, &self.pages) {
+            if taxonomy.terms.is_empty() {
+                continue;
+            }
+
+            let html = self.templates.render(
+                "taxonomy.html",
+                context! { site => &site, taxonomy => TaxonomyContext::new(&taxonomy) },
+            )?;
+            files.push(OutputFile {
+                path: output_path(&taxonomy.url),
+                contents: html,
+            });
+            entries.push(SitemapEntry {
+                url: self.config.permalink(&taxonomy.url),
+                lastmod: taxonomy.terms.iter().filter_map(|t| newest(&t.pages)).max(),
+            });
+
+            for term in &taxonomy.terms {
+                let pagers = paginate(
+                    &term.url,
+                    &term.pages,
+                    self.config.pagination.per_page,
+                    self.config.pagination.window,
+                );
+                if pagers.len() > 1 {
+                    files.push(self.first_page_redirect(&term.url));
+                }
+
+                for (paginator, pages) in pagers {
+                    let html = self.templates.render(
+                        "term.html",
+                        context! {
+                            site => &site,
+                            term => TermContext::new(&self.config, &taxonomy, term, pages),
+                            paginator => &paginator,
+                        },
+                    )?;
+                    files.push(OutputFile {
+                        path: output_path(&paginator.url),
+                        contents: html,
+                    });
+                    entries.push(SitemapEntry {
+                        url: self.config.permalink(&paginator.url),
+                        lastmod: newest(&term.pages),
+                    });
+                }
+            }
+        }
+
+        for feed in self.feeds() {
+            if self.config.feeds.rss {
+                files.push(OutputFile {
+                    path: output_path(&feed.rss_url()),
+                    contents: rss(&self.config, &feed),
+                });
+            }
+            if self.config.feeds.atom {
+                files.push(OutputFile {
+                    path: output_path(&feed.atom_url()),
+                    contents: atom(&self.config, &feed),
+                });
+            }
+        }
+
+        if self.config.search.enabled {
+            let index = search_index(&self.config, &self.pages);
+            files.push(OutputFile {
+                path: PathBuf::from(INDEX_FILE),
+                contents: index.to_json(self.config.search.max_bytes),
+            });
+            files.push(OutputFile {
+                path: PathBuf::from(SCRIPT_FILE),
+                contents: search_script(&self.config),
+            });
+        }
+
+        for (path, contents) in sitemaps(&self.config, &entries) {
+            files.push(OutputFile {
+                path: PathBuf::from(path),
+                contents,
+            });
+        }
+
+        if self.config.robots.enabled {
+            files.push(OutputFile {
+                path: PathBuf::from("robots.txt"),
+                contents: robots_txt(&self.config),
+            });
+        }
+
+        check_collisions(&self.pages, &self.assets, &files)?;
+        Ok(files)
+    }
+
+    // Renders the site into `output` and copies the assets, giving back what
+    // was rendered.
+    pub fn build(&self, output: &Path) -> Result<Vec<OutputFile>, SiteError> {
+        let files = self.render()?;
+        for file in &files {
+            let path = output.join(&file.path);
+            create_parent(&path)?;
+            fs::write(&path, &file.contents).map_err(|e| SiteError::Io(path.clone(), e))?;
+        }
+        for asset in &self.assets {
+            let path = output.join(&asset.path);
+            create_parent(&path)?;
+            fs::copy(&asset.source, &path).map_err(|e| SiteError::Io(asset.source.clone(), e))?;
+        }
+
+        Ok(files)
+    }
+
+    // The feed for the whole site, then one for every section and tag that
+    // has dated pages in it. Drafts and scheduled pages are left out, even
+    // when they're built.
+    pub fn feeds(&self) -> Vec<Feed<'_>> {
+        let published: Vec<&Page> = self.pages.iter().filter(|p| p.is_published()).collect();
+        let mut feeds = vec![Feed::new(&self.config, &self.config.title, "/", &published)];
+
+        if self.config.feeds.sections {
+            let mut sections: BTreeMap<&str, Vec<&Page>> = BTreeMap::new();
+            for page in &published {
+                let mut section = page.section();
+                // A page is in every section above it too, like blog/2024 is
+                // in blog.
+                while !section.is_empty() {
+                    sections.entry(section).or_default().push(page);
+                    section = section.rsplit_once('/').map(|(s, _)| s).unwrap_or("");
+                }
+            }
+
+            for (section, pages) in sections {
+                let url = format!("/{}/", section);
+                let name = self
+                    .pages
+                    .iter()
+                    .find(|p| p.url == url)
+                    .map(|p| p.title())
+                    .unwrap_or_else(|| String::from(section));
+                feeds.push(Feed::new(
+                    &self.config,
+                    &self.feed_title(&name),
+                    &url,
+                    &pages,
+                ));
+            }
+        }
+
+        for taxonomy in taxonomies(&self.config, &self.pages) {
+            if !taxonomy.feed {
+                continue;
+            }
+            for term in &taxonomy.terms {
+                let pages: Vec<&Page> = term
+                    .pages
+                    .iter()
+                    .copied()
+                    .filter(|p| p.is_published())
+                    .collect();
+                feeds.push(Feed::new(
+                    &self.config,
+                    &self.feed_title(&term.name),
+                    &term.url,
+                    &pages,
+                ));
+            }
+        }
+
+        feeds.retain(|f| f.url == "/" || !f.pages.is_empty());
+        feeds
+    }
+
+    // /blog/page/1/ is the same as /blog/, links to it go there instead.
+    fn first_page_redirect(&self, url: &str) -> OutputFile {
+        OutputFile {
+            path: output_path(&format!("{}page/1/", url)),
+            contents: redirect_html(&self.config.permalink(url)),
+        }
+    }
+
+    // Ex. "My blog - Rust"
+    fn feed_title(&self, name: &str) -> String {
+        if self.config.title.is_empty() {
+            String::from(name)
+        } else {
+            format!("{} - {}", self.config.title, name)
+        }
+    }
+}
+
+// The last time any of the pages changed.
+fn newest(pages: &[&Page]) -> Option<DateTime<Utc>> {
+    pages.iter().filter_map(|p| p.lastmod()).max()
+}
+
+// Two pages with the same url, or a page and a copied file at the same path,
+// would leave only the one written last, so the build fails instead.
+// Listings, feeds and the like are named by what they are since there's no
+// page for them.
+fn check_collisions(
+    pages: &[Page],
+    assets: &[Asset],
+    files: &[OutputFile],
+) -> Result<(), SiteError> {
+    let mut counts: BTreeMap<&Path, usize> = BTreeMap::new();
+    for file in files {
+        *counts.entry(&file.path).or_default() += 1;
+    }
+    for asset in assets {
+        *counts.entry(Path::new(&asset.path)).or_default() += 1;
+    }
+    let Some((path, count)) = counts.into_iter().find(|(_, count)| *count > 1) else {
+        return Ok(());
+    };
+
+    let mut sources: Vec<String> = pages
+        .iter()
+        .filter(|p| output_path(&p.url) == path)
+        .map(|p| {
+            if p.is_section_index() {
+                format!("the listing for {}", p.source)
+            } else {
+                p.source.clone()
+            }
+        })
+        .collect();
+    sources.extend(
+        assets
+            .iter()
+            .filter(|a| Path::new(&a.path) == path)
+            .map(|a| a.source.display().to_string()),
+    );
+    while sources.len() < count {
+        sources.push(String::from("a listing or feed"));
+    }
+    Err(SiteError::Collision(path.to_path_buf(), sources))
+}
+
+// Every file under `dir`, leaving out hidden ones like .DS_Store.
+fn find_files(dir: &Path, found: &mut Vec<PathBuf>) -> Result<(), SiteError> {
+    let entries = fs::read_dir(dir).map_err(|e| SiteError::Io(dir.to_path_buf(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| SiteError::Io(dir.to_path_buf(), e))?
+            .path();
+        if path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            find_files(&path, found)?;
+        } else {
+            found.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Ex. content and content/blog/hello.md -> blog/hello.md
+fn relative_path(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn create_parent(path: &Path) -> Result<(), SiteError> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).map_err(|e| SiteError::Io(dir.to_path_buf(), e)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::nodes::parent_node::ParentNode;
+
+    use super::config::{Emit, SearchConfig};
+    use super::*;
+
+    fn site() -> Site {
+        let pages = vec![
+            Page::new("index.md", "# Home").unwrap(),
+            Page::new("blog/_index.md", "# The Blog").unwrap(),
+            Page::new(
+                "blog/2024/a.md",
+                "---\ndate: 2024-01-01\ntags: [Rust Lang]\n---\nA",
+            )
+            .unwrap(),
+            Page::new("docs/intro.md", "---\nsitemap: false\n---\nNot dated").unwrap(),
+        ];
+
+        Site::new(
+            Config {
+                base_url: String::from("https://example.com"),
+                title: String::from("Site"),
+                ..Config::default()
+            },
+            pages,
+        )
+    }
+
+    #[test]
+    fn test_feeds() {
+        let site = site();
+        let feeds: Vec<(String, String, usize)> = site
+            .feeds()
+            .iter()
+            .map(|f| (f.title.clone(), f.url.clone(), f.pages.len()))
+            .collect();
+
+        assert_eq!(
+            feeds,
+            vec![
+                (String::from("Site"), String::from("/"), 1),
+                (String::from("Site - The Blog"), String::from("/blog/"), 1),
+                (
+                    String::from("Site - blog/2024"),
+                    String::from("/blog/2024/"),
+                    1
+                ),
+                (
+                    String::from("Site - Rust Lang"),
+                    String::from("/tags/rust-lang/"),
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_paths() {
+        let paths: Vec<PathBuf> = site()
+            .render()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "blog/2024/a/index.html",
+                "docs/intro/index.html",
+                "index.html",
+                "blog/index.html",
+                "blog/2024/index.html",
+                "docs/index.html",
+                "tags/index.html",
+                "tags/rust-lang/index.html",
+                "rss.xml",
+                "atom.xml",
+                "blog/rss.xml",
+                "blog/atom.xml",
+                "blog/2024/rss.xml",
+                "blog/2024/atom.xml",
+                "tags/rust-lang/rss.xml",
+                "tags/rust-lang/atom.xml",
+                "sitemap.xml",
+                "robots.txt",
+            ]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let files = site().render().unwrap();
+        let contents = |path: &str| {
+            files
+                .iter()
+                .find(|f| f.path == Path::new(path))
+                .map(|f| f.contents.as_str())
+                .unwrap()
+        };
+
+        let test_cases = vec![
+            (
+                "Test section lists its pages",
+                "blog/2024/index.html",
+                "<li><a href=\"/blog/2024/a/\">a</a> <time",
+            ),
+            (
+                "Test section lists the ones under it",
+                "blog/index.html",
+                "<li><a href=\"/blog/2024/\">2024</a> (1)</li>",
+            ),
+            (
+                "Test page breadcrumbs",
+                "blog/2024/a/index.html",
+                "<nav><a href=\"/\">Home</a> / <a href=\"/blog/\">The Blog</a> / <a href=\"/blog/2024/\">2024</a></nav>",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert!(
+                contents(input).contains(expected),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_pagination() {
+        let pages = (1..=5)
+            .map(|i| Page::new(&format!("blog/{}.md", i), "Post").unwrap())
+            .collect();
+        let mut config = Config::default();
+        config.pagination.per_page = 2;
+        let files = Site::new(config, pages).render().unwrap();
+        let contents = |path: &str| {
+            files
+                .iter()
+                .find(|f| f.path == Path::new(path))
+                .map(|f| f.contents.as_str())
+                .unwrap_or("")
+        };
+
+        let test_cases = vec![
+            (
+                "Test first page links to the next",
+                "blog/index.html",
+                "<a href=\"/blog/page/2/\" rel=\"next\">Next</a>",
+            ),
+            (
+                "Test last page has the rest",
+                "blog/page/3/index.html",
+                "<li><a href=\"/blog/5/\">5</a></li>\n</ul>",
+            ),
+            (
+                "Test first page redirect",
+                "blog/page/1/index.html",
+                "<meta http-equiv=\"refresh\" content=\"0; url=/blog/\">",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert!(
+                contents(input).contains(expected),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+        assert!(!files
+            .iter()
+            .any(|f| f.path == Path::new("blog/page/4/index.html")));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let test_cases = vec![
+            ("Test html only", Emit::Html, vec![], true),
+            (
+                "Test json next to the html",
+                Emit::Both,
+                vec![
+                    "blog/2024/a/index.json",
+                    "blog/index.json",
+                    "docs/intro/index.json",
+                    "index.json",
+                ],
+                true,
+            ),
+            (
+                "Test json instead of the html",
+                Emit::AstJson,
+                vec![
+                    "blog/2024/a/index.json",
+                    "blog/index.json",
+                    "docs/intro/index.json",
+                    "index.json",
+                ],
+                false,
+            ),
+        ];
+
+        for (title, emit, expected, html) in test_cases.into_iter() {
+            let mut site = site();
+            site.config.emit = emit;
+            let files = site.render().unwrap();
+            let mut json: Vec<String> = files
+                .iter()
+                .map(|f| f.path.to_string_lossy().replace('\\', "/"))
+                .filter(|p| p.ends_with(".json"))
+                .collect();
+            json.sort();
+            let has_html = files
+                .iter()
+                .any(|f| f.path == Path::new("blog/2024/a/index.html"));
+
+            assert_eq!(
+                (json, has_html),
+                (expected.iter().map(|p| p.to_string()).collect(), html),
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                emit,
+                expected
+            );
+        }
+
+        let mut site = site();
+        site.config.emit = Emit::Both;
+        let files = site.render().unwrap();
+        let json = files
+            .iter()
+            .find(|f| f.path == Path::new("index.json"))
+            .map(|f| f.contents.as_str())
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<ParentNode>(json).unwrap(),
+            site.pages
+                .iter()
+                .find(|p| p.source == "index.md")
+                .unwrap()
+                .document
+                .root
+        );
+    }
+
+    #[test]
+    fn test_render_collisions() {
+        let test_cases = vec![
+            (
+                "Test two pages with the same slug",
+                vec![
+                    ("blog/a.md", "---\nslug: same\n---\n"),
+                    ("blog/b.md", "---\nslug: same\n---\n"),
+                ],
+                vec![],
+                "blog/same/index.html: written by both blog/a.md and blog/b.md",
+            ),
+            (
+                "Test page in the place of a listing",
+                vec![
+                    ("blog/_index.md", ""),
+                    ("about.md", "---\nurl: /blog/\n---\n"),
+                ],
+                vec![],
+                "blog/index.html: written by both about.md and the listing for blog/_index.md",
+            ),
+            (
+                "Test page in the place of a taxonomy",
+                vec![("tags.md", ""), ("post.md", "---\ntags: [a]\n---\n")],
+                vec![],
+                "tags/index.html: written by both tags.md and a listing or feed",
+            ),
+            (
+                "Test page in the place of an asset",
+                vec![("404.md", "---\nurl: /404.html\n---\n")],
+                vec![("404.html", "static/404.html")],
+                "404.html: written by both 404.md and static/404.html",
+            ),
+            (
+                "Test asset in the place of a feed",
+                vec![],
+                vec![("rss.xml", "static/rss.xml")],
+                "rss.xml: written by both static/rss.xml and a listing or feed",
+            ),
+            (
+                "Test two assets at the same path",
+                vec![],
+                vec![
+                    ("cat.png", "content/cat.png"),
+                    ("cat.png", "static/cat.png"),
+                ],
+                "cat.png: written by both content/cat.png and static/cat.png",
+            ),
+        ];
+
+        for (title, pages, assets, expected) in test_cases.iter() {
+            let mut site = Site::new(
+                Config::default(),
+                pages
+                    .iter()
+                    .map(|(source, markdown)| Page::new(source, markdown).unwrap())
+                    .collect(),
+            );
+            site.assets = assets
+                .iter()
+                .map(|(path, source)| Asset {
+                    path: String::from(*path),
+                    source: PathBuf::from(source),
+                })
+                .collect();
+            let error = site.render().unwrap_err().to_string();
+            assert_eq!(
+                error, *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title, pages, expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_assign_urls() {
+        let mut config = Config::default();
+        config.urls.pretty = false;
+        config
+            .urls
+            .permalinks
+            .insert(String::from("blog"), String::from("/:year/:slug/"));
+        let mut site = Site::new(
+            config,
+            vec![
+                Page::new("blog/hello.md", "---\ndate: 2024-03-01\n---\n").unwrap(),
+                Page::new("links.md", "[Hello](@/blog/hello.md)").unwrap(),
+            ],
+        );
+        site.assign_urls().unwrap();
+        site.resolve_internal_links();
+
+        let paths: Vec<PathBuf> = site.render().unwrap().into_iter().map(|f| f.path).collect();
+        assert!(paths.contains(&PathBuf::from("2024/hello.html")));
+        assert!(paths.contains(&PathBuf::from("links.html")));
+        assert!(site.pages[1]
+            .document
+            .root
+            .into_html()
+            .contains("href=\"/2024/hello.html\""));
+
+        site.pages[0].front_matter.date = None;
+        assert_eq!(
+            site.assign_urls().unwrap_err().to_string(),
+            "blog/hello.md: the permalink /:year/:slug/ needs a date"
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let options = |drafts: bool, future: bool| BuildOptions {
+            drafts,
+            future,
+            now,
+        };
+
+        let test_cases = vec![
+            (
+                "Test only published pages by default",
+                options(false, false),
+                vec!["live.md"],
+            ),
+            (
+                "Test drafts",
+                options(true, false),
+                vec!["draft.md", "live.md"],
+            ),
+            (
+                "Test scheduled pages",
+                options(false, true),
+                vec!["live.md", "scheduled.md"],
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let mut site = Site::new(
+                Config::default(),
+                vec![
+                    Page::new("live.md", "---\ndate: 2024-01-01\n---\n").unwrap(),
+                    Page::new("draft.md", "---\ndraft: true\n---\n").unwrap(),
+                    Page::new("scheduled.md", "---\ndate: 2024-07-01\n---\n").unwrap(),
+                    Page::new("expired.md", "---\nexpiry_date: 2024-05-01\n---\n").unwrap(),
+                ],
+            );
+            site.publish(input);
+            let sources: Vec<&str> = site.pages.iter().map(|p| p.source.as_str()).collect();
+            assert_eq!(
+                sources, *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title, input, expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_preview_pages_stay_out_of_feeds_sitemap_and_search() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut site = Site::new(
+            Config {
+                base_url: String::from("https://example.com"),
+                search: SearchConfig {
+                    enabled: true,
+                    ..SearchConfig::default()
+                },
+                ..Config::default()
+            },
+            vec![
+                Page::new("blog/live.md", "---\ndate: 2024-01-01\n---\nLive").unwrap(),
+                Page::new("blog/draft.md", "---\ndraft: true\n---\nDraft").unwrap(),
+                Page::new("blog/scheduled.md", "---\ndate: 2024-07-01\n---\nLater").unwrap(),
+            ],
+        );
+        site.publish(&BuildOptions {
+            drafts: true,
+            future: true,
+            now,
+        });
+        let files = site.render().unwrap();
+        let contents = |path: &str| {
+            files
+                .iter()
+                .find(|f| f.path == Path::new(path))
+                .map(|f| f.contents.clone())
+                .unwrap()
+        };
+
+        assert!(files
+            .iter()
+            .any(|f| f.path == Path::new("blog/draft/index.html")));
+        for feed in site.feeds() {
+            let sources: Vec<&str> = feed.pages.iter().map(|p| p.source.as_str()).collect();
+            assert_eq!(sources, vec!["blog/live.md"], "feed {}", feed.url);
+        }
+        for path in ["sitemap.xml", "search_index.json"] {
+            let contents = contents(path);
+            assert!(contents.contains("/blog/live/"), "{}", path);
+            assert!(!contents.contains("/blog/draft/"), "{}", path);
+            assert!(!contents.contains("/blog/scheduled/"), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_sitemap_skips_pages() {
+        let files = site().render().unwrap();
+        let sitemap = files
+            .iter()
+            .find(|f| f.path == Path::new("sitemap.xml"))
+            .unwrap();
+        let urls: Vec<&str> = sitemap
+            .contents
+            .lines()
+            .filter_map(|l| l.strip_prefix("<loc>")?.strip_suffix("</loc>"))
+            .collect();
+
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/blog/2024/a/",
+                "https://example.com/",
+                "https://example.com/blog/",
+                "https://example.com/blog/2024/",
+                "https://example.com/docs/",
+                "https://example.com/tags/",
+                "https://example.com/tags/rust-lang/",
+            ]
+        );
+    }
+}
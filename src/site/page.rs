@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::nodes::html_node::HTMLChildNode;
+use crate::nodes::plain_text::Summary;
+use crate::parser::{markdown_to_html_node, Document};
+
+use super::config::UrlConfig;
+use super::front_matter::{split_front_matter, FrontMatter};
+use super::links::add_heading_ids;
+use super::permalinks::page_url;
+use super::SiteError;
+
+// A markdown file from the content directory, rendered into nodes.
+#[derive(Debug)]
+pub struct Page {
+    // Relative to the content directory, always with `/` separators.
+    // Ex. blog/hello.md
+    pub source: String,
+    // From the permalinks in the config.
+    // Ex. /blog/hello/
+    pub url: String,
+    // The whole file as it was read, front matter included.
+    pub markdown: String,
+    pub front_matter: FrontMatter,
+    pub document: Document,
+    // When the file was last written, for pages loaded from disk.
+    pub modified: Option<DateTime<Utc>>,
+    // Whether the page is out yet, as of when it was made.
+    pub status: Status,
+}
+
+// Pages that aren't published are left out of a build unless it asks for
+// them, and get a banner saying so when they're in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Published,
+    // `draft: true`.
+    Draft,
+    // Its `date` hasn't come yet.
+    Scheduled,
+    // Its `expiry_date` has gone by, these are left out of every build.
+    Expired,
+}
+
+impl Page {
+    pub fn new(source: &str, markdown: &str) -> Result<Page, SiteError> {
+        let (front_matter, body) = split_front_matter(markdown)
+            .map_err(|e| SiteError::FrontMatter(PathBuf::from(source), e))?;
+
+        let mut document = markdown_to_html_node(body);
+        add_heading_ids(&mut document.root);
+
+        let mut page = Page {
+            source: String::from(source),
+            url: url_for(source),
+            markdown: String::from(markdown),
+            front_matter,
+            document,
+            modified: None,
+            status: Status::Published,
+        };
+        page.status = page.status_at(Utc::now());
+        // Sites change it to the one from their config.
+        page.url =
+            page_url(&UrlConfig::default(), &page).expect("the default permalink has no dates");
+        Ok(page)
+    }
+
+    // Only published pages go into feeds, the sitemap and the search index,
+    // even when the build takes in the others to preview them.
+    pub fn is_published(&self) -> bool {
+        self.status == Status::Published
+    }
+
+    // Whether the page is out at `now`, a draft stays one whatever its dates.
+    pub fn status_at(&self, now: DateTime<Utc>) -> Status {
+        let front_matter = &self.front_matter;
+        if front_matter.draft {
+            Status::Draft
+        } else if front_matter.date.is_some_and(|date| date > now) {
+            Status::Scheduled
+        } else if front_matter.expiry_date.is_some_and(|date| date <= now) {
+            Status::Expired
+        } else {
+            Status::Published
+        }
+    }
+
+    // The title from the front matter, or else the first heading, or else the
+    // file name.
+    pub fn title(&self) -> String {
+        if let Some(title) = &self.front_matter.title {
+            return title.clone();
+        }
+
+        let heading = self.document.root.children().iter().find_map(|c| match c {
+            HTMLChildNode::Parent(p) if p.tag() == "h1" => Some(c.to_plain_text()),
+            _ => None,
+        });
+
+        heading.unwrap_or_else(|| {
+            let stem = Path::new(&self.source).file_stem().unwrap_or_default();
+            stem.to_string_lossy().into_owned()
+        })
+    }
+
+    // The directory the page is in, "" for pages at the top.
+    // Ex. blog/2024/hello.md is in blog/2024
+    pub fn section(&self) -> &str {
+        self.source
+            .rsplit_once('/')
+            .map(|(dir, _)| dir)
+            .unwrap_or("")
+    }
+
+    // An `index.md` or `_index.md`, the page at the url of its directory.
+    pub fn is_index(&self) -> bool {
+        let name = self.source.rsplit('/').next().unwrap_or("");
+        name == "index.md" || name == "_index.md"
+    }
+
+    // An `_index.md`, which is rendered as the listing of its section rather
+    // than as a page of its own.
+    pub fn is_section_index(&self) -> bool {
+        self.source.rsplit('/').next() == Some("_index.md")
+    }
+
+    // When the page last changed, from the front matter or else the file.
+    pub fn lastmod(&self) -> Option<DateTime<Utc>> {
+        self.front_matter
+            .updated
+            .or(self.front_matter.date)
+            .or(self.modified)
+    }
+
+    pub fn summary(&self, words: usize) -> Summary {
+        self.document.root.summary(words)
+    }
+}
+
+// Dated pages first with the newest at the top, then the rest by source path.
+pub fn sort_newest_first(pages: &mut [&Page]) {
+    pages.sort_by(|a, b| {
+        b.front_matter
+            .date
+            .cmp(&a.front_matter.date)
+            .then_with(|| a.source.cmp(&b.source))
+    });
+}
+
+// The url of the directory for index pages, and the url a page would have
+// at the same path for the rest.
+// Ex. blog/hello.md -> /blog/hello/, blog/index.md -> /blog/
+pub fn url_for(source: &str) -> String {
+    let path = source.strip_suffix(".md").unwrap_or(source);
+    let path = match path.rsplit_once('/') {
+        Some((dir, "index" | "_index")) => dir,
+        None if path == "index" || path == "_index" => "",
+        _ => path,
+    };
+
+    if path.is_empty() {
+        String::from("/")
+    } else {
+        format!("/{}/", path)
+    }
+}
+
+// Where the html for a url goes in the output directory.
+// Ex. /blog/hello/ -> blog/hello/index.html
+pub fn output_path(url: &str) -> PathBuf {
+    let path = url.trim_matches('/');
+    if path.ends_with(".html") || path.ends_with(".xml") {
+        PathBuf::from(path)
+    } else {
+        Path::new(path).join("index.html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_url_for() {
+        let test_cases = vec![
+            ("Test page at the top", "about.md", "/about/"),
+            (
+                "Test nested page",
+                "blog/2024/hello.md",
+                "/blog/2024/hello/",
+            ),
+            ("Test index page", "blog/index.md", "/blog/"),
+            ("Test section index page", "blog/_index.md", "/blog/"),
+            ("Test home page", "index.md", "/"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                url_for(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_page_status() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let test_cases = vec![
+            ("Test no dates", "Text", Status::Published),
+            (
+                "Test date that has come",
+                "---\ndate: 2024-06-01 12:00\n---\n",
+                Status::Published,
+            ),
+            (
+                "Test date in the future",
+                "---\ndate: 2024-06-02\n---\n",
+                Status::Scheduled,
+            ),
+            (
+                "Test expiry date that has gone by",
+                "---\ndate: 2024-01-01\nexpiry_date: 2024-06-01\n---\n",
+                Status::Expired,
+            ),
+            (
+                "Test expiry date still ahead",
+                "---\nexpiry_date: 2025-01-01\n---\n",
+                Status::Published,
+            ),
+            (
+                "Test draft wins over dates",
+                "---\ndraft: true\ndate: 2030-01-01\n---\n",
+                Status::Draft,
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let page = Page::new("hello.md", input).unwrap();
+            assert_eq!(
+                page.status_at(now),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_page_title() {
+        let test_cases = vec![
+            (
+                "Test front matter title",
+                "---\ntitle: From yaml\n---\n# Heading",
+                "From yaml",
+            ),
+            (
+                "Test first heading",
+                "Intro\n\n# The *heading*",
+                "The heading",
+            ),
+            ("Test file name", "No heading here", "hello"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let page = Page::new("blog/hello.md", input).unwrap();
+            assert_eq!(
+                page.title(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
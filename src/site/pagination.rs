@@ -0,0 +1,212 @@
+use serde::Serialize;
+
+use super::feed::escape_xml;
+
+// `paginator` in section.html and term.html, for the page of the list being
+// rendered.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Paginator {
+    // Counted from 1.
+    pub current: usize,
+    // How many pages the list has, at least 1.
+    pub total: usize,
+    pub per_page: usize,
+    // Everything in the list, on every page.
+    pub items: usize,
+    // Ex. /blog/page/2/
+    pub url: String,
+    pub first: String,
+    pub last: String,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    // The numbers to link to, with gaps for an ellipsis.
+    // Ex. 1 … 4 5 [6] 7 8 … 20
+    pub numbers: Vec<PageNumber>,
+}
+
+// A link to a page of the list, or a gap when `number` is none.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PageNumber {
+    pub number: Option<usize>,
+    pub url: Option<String>,
+    pub current: bool,
+}
+
+// Splits `items` into pages of `per_page`, 0 keeps them all on one. An empty
+// list still gets its first page.
+pub fn paginate<'a, T>(
+    url: &str,
+    items: &'a [T],
+    per_page: usize,
+    window: usize,
+) -> Vec<(Paginator, &'a [T])> {
+    let chunks: Vec<&[T]> = if per_page == 0 || items.is_empty() {
+        vec![items]
+    } else {
+        items.chunks(per_page).collect()
+    };
+    let total = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let current = i + 1;
+            let paginator = Paginator {
+                current,
+                total,
+                per_page,
+                items: items.len(),
+                url: page_url(url, current),
+                first: page_url(url, 1),
+                last: page_url(url, total),
+                prev: (current > 1).then(|| page_url(url, current - 1)),
+                next: (current < total).then(|| page_url(url, current + 1)),
+                numbers: page_numbers(url, current, total, window),
+            };
+            (paginator, chunk)
+        })
+        .collect()
+}
+
+// The first page is the list itself.
+// Ex. /blog/ and 1 -> /blog/, /blog/ and 3 -> /blog/page/3/
+pub fn page_url(url: &str, number: usize) -> String {
+    if number <= 1 {
+        String::from(url)
+    } else {
+        format!("{}page/{}/", url, number)
+    }
+}
+
+fn page_numbers(url: &str, current: usize, total: usize, window: usize) -> Vec<PageNumber> {
+    let shown: Vec<usize> = (1..=total)
+        .filter(|&n| n == 1 || n == total || n.abs_diff(current) <= window)
+        .collect();
+
+    let link = |n: usize| PageNumber {
+        number: Some(n),
+        url: Some(page_url(url, n)),
+        current: n == current,
+    };
+
+    let mut numbers = Vec::new();
+    for (i, &n) in shown.iter().enumerate() {
+        if let Some(&before) = i.checked_sub(1).and_then(|i| shown.get(i)) {
+            // An ellipsis for a single number takes as much room as the
+            // number.
+            if n - before == 2 {
+                numbers.push(link(n - 1));
+            } else if n - before > 2 {
+                numbers.push(PageNumber {
+                    number: None,
+                    url: None,
+                    current: false,
+                });
+            }
+        }
+        numbers.push(link(n));
+    }
+
+    numbers
+}
+
+// Sends /blog/page/1/ on to /blog/, which is where the first page is.
+pub fn redirect_html(permalink: &str) -> String {
+    let url = escape_xml(permalink);
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Redirect</title>
+<link rel=\"canonical\" href=\"{url}\">
+<meta http-equiv=\"refresh\" content=\"0; url={url}\">
+</head>
+<body>
+<a href=\"{url}\">{url}</a>
+</body>
+</html>
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // The numbers as text, like "1 … 4 [5] 6 … 9".
+    fn numbers(paginator: &Paginator) -> String {
+        paginator
+            .numbers
+            .iter()
+            .map(|n| match n.number {
+                Some(number) if n.current => format!("[{}]", number),
+                Some(number) => number.to_string(),
+                None => String::from("…"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<usize> = (1..=25).collect();
+        let pages = paginate("/blog/", &items, 10, 1);
+        let test_cases = vec![
+            (
+                "Test first page",
+                &pages[0],
+                (None, Some("/blog/page/2/"), vec![1, 10]),
+            ),
+            (
+                "Test last page is short",
+                &pages[2],
+                (Some("/blog/page/2/"), None, vec![21, 25]),
+            ),
+        ];
+
+        for (title, (paginator, chunk), expected) in test_cases.iter() {
+            let output = (
+                paginator.prev.as_deref(),
+                paginator.next.as_deref(),
+                vec![chunk[0], chunk[chunk.len() - 1]],
+            );
+            assert_eq!(
+                output, *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title, paginator.current, expected
+            );
+        }
+
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[1].0.url, "/blog/page/2/");
+        assert_eq!(pages[1].0.last, "/blog/page/3/");
+        assert_eq!(paginate("/", &items, 0, 2).len(), 1);
+        assert_eq!(paginate::<usize>("/", &[], 10, 2).len(), 1);
+    }
+
+    #[test]
+    fn test_page_numbers() {
+        let items: Vec<usize> = (1..=9).collect();
+        let pages = paginate("/", &items, 1, 1);
+        let test_cases = vec![
+            ("Test first page", 1, "[1] 2 … 9"),
+            ("Test a gap of one is filled in", 3, "1 2 [3] 4 … 9"),
+            ("Test gaps on both sides", 5, "1 … 4 [5] 6 … 9"),
+            ("Test last page", 9, "1 … 8 [9]"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                numbers(&pages[input - 1].0),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
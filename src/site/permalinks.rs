@@ -0,0 +1,266 @@
+use super::config::{SlugFrom, UrlConfig};
+use super::page::{url_for, Page};
+use super::urls::{slugify, transliterate};
+
+// Where pages go in sections without a pattern of their own.
+pub const DEFAULT_PERMALINK: &str = "/:section/:slug/";
+
+// The url of a page. Index pages are at the url of their directory, a `url`
+// in the front matter is used as it is, and the rest go by the pattern for
+// their section. The error says what's wrong with the pattern.
+// Ex. /blog/:year/:month/:slug/ -> /blog/2024/03/hello/
+pub fn page_url(config: &UrlConfig, page: &Page) -> Result<String, String> {
+    if page.is_index() {
+        return Ok(url_for(&page.source));
+    }
+    if let Some(url) = &page.front_matter.url {
+        return Ok(clean_url(url));
+    }
+
+    let pattern = pattern_for(config, page.section());
+    let mut url = format!("/{}", expand(pattern, config, page)?);
+    while url.contains("//") {
+        url = url.replace("//", "/");
+    }
+
+    if !config.pretty && url.len() > 1 && url.ends_with('/') {
+        url.pop();
+        url.push_str(".html");
+    }
+    Ok(url)
+}
+
+// The `slug` from the front matter, or else one made from the file name or
+// title with accented, Greek and Cyrillic letters spelled in ascii. Names
+// without a letter or digit in them keep the file name.
+// Ex. Crème Brûlée.md -> creme-brulee
+pub fn slug(config: &UrlConfig, page: &Page) -> String {
+    if let Some(slug) = &page.front_matter.slug {
+        return String::from(slug.trim_matches('/'));
+    }
+
+    let name = file_name(page);
+    let text = match config.slug_from {
+        SlugFrom::Filename => String::from(name),
+        SlugFrom::Title => page.title(),
+    };
+    let slug = slugify(&transliterate(&text));
+    if slug.is_empty() {
+        String::from(name)
+    } else {
+        slug
+    }
+}
+
+// The pattern for the closest section to `section` that has one.
+fn pattern_for<'a>(config: &'a UrlConfig, section: &str) -> &'a str {
+    let mut dir = section;
+    loop {
+        let pattern = config
+            .permalinks
+            .iter()
+            .find(|(path, _)| path.trim_matches('/') == dir);
+        if let Some((_, pattern)) = pattern {
+            return pattern;
+        }
+        if dir.is_empty() {
+            return DEFAULT_PERMALINK;
+        }
+        dir = dir.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+    }
+}
+
+// Fills in every `:name` in the pattern, a `:` without a name after it is
+// kept.
+fn expand(pattern: &str, config: &UrlConfig, page: &Page) -> Result<String, String> {
+    let mut url = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(':') {
+        url.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(after.len());
+        let name = &after[..end];
+
+        let date = || {
+            page.front_matter
+                .date
+                .ok_or_else(|| format!("the permalink {} needs a date", pattern))
+        };
+        match name {
+            "" => url.push(':'),
+            "year" => url.push_str(&date()?.format("%Y").to_string()),
+            "month" => url.push_str(&date()?.format("%m").to_string()),
+            "day" => url.push_str(&date()?.format("%d").to_string()),
+            "slug" => url.push_str(&slug(config, page)),
+            "section" => url.push_str(page.section()),
+            "filename" => url.push_str(file_name(page)),
+            _ => return Err(format!("unknown :{} in the permalink {}", name, pattern)),
+        }
+        rest = &after[end..];
+    }
+
+    url.push_str(rest);
+    Ok(url)
+}
+
+// Ex. blog/hello.md -> hello
+fn file_name(page: &Page) -> &str {
+    let name = page.source.rsplit('/').next().unwrap_or("");
+    name.strip_suffix(".md").unwrap_or(name)
+}
+
+// Front matter urls start at the root, and ones without a file name at the
+// end get a `/` like every other page.
+// Ex. old/post -> /old/post/, /feed.html -> /feed.html
+fn clean_url(url: &str) -> String {
+    let url = format!("/{}", url.trim_start_matches('/'));
+    let name = url.rsplit('/').next().unwrap_or("");
+    if name.is_empty() || name.contains('.') {
+        url
+    } else {
+        format!("{}/", url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_page_url() {
+        let mut config = UrlConfig::default();
+        config.permalinks.insert(
+            String::from("blog"),
+            String::from("/blog/:year/:month/:slug/"),
+        );
+        config.permalinks.insert(
+            String::from("/notes/"),
+            String::from("/:section/:day-:filename.html"),
+        );
+        let test_cases = vec![
+            ("Test default", "docs/intro.md", "", "/docs/intro/"),
+            ("Test page at the top", "about.md", "", "/about/"),
+            (
+                "Test file name is made a slug",
+                "docs/Crème Brûlée!.md",
+                "",
+                "/docs/creme-brulee/",
+            ),
+            (
+                "Test section pattern",
+                "blog/hello.md",
+                "date: 2024-03-01",
+                "/blog/2024/03/hello/",
+            ),
+            (
+                "Test pattern of the section above",
+                "blog/2024/hello.md",
+                "date: 2024-03-01",
+                "/blog/2024/03/hello/",
+            ),
+            (
+                "Test slug from the front matter",
+                "blog/hello.md",
+                "date: 2024-03-01\nslug: hi-there",
+                "/blog/2024/03/hi-there/",
+            ),
+            (
+                "Test url from the front matter",
+                "blog/hello.md",
+                "url: 2019/old-post",
+                "/2019/old-post/",
+            ),
+            (
+                "Test pattern ending in .html",
+                "notes/Todo.md",
+                "date: 2024-03-09",
+                "/notes/09-Todo.html",
+            ),
+            ("Test index page", "blog/_index.md", "", "/blog/"),
+            ("Test name without letters", "docs/!!.md", "", "/docs/!!/"),
+        ];
+
+        for (title, source, front_matter, expected) in test_cases.iter() {
+            let page = Page::new(source, &format!("---\n{}\n---\n", front_matter)).unwrap();
+            assert_eq!(
+                page_url(&config, &page).unwrap(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                source,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_page_url_options() {
+        let page = Page::new("blog/hello.md", "# Grüße aus Köln").unwrap();
+        let test_cases = vec![
+            ("Test pretty urls", true, SlugFrom::Filename, "/blog/hello/"),
+            (
+                "Test .html urls",
+                false,
+                SlugFrom::Filename,
+                "/blog/hello.html",
+            ),
+            (
+                "Test slug from the title",
+                true,
+                SlugFrom::Title,
+                "/blog/grusse-aus-koln/",
+            ),
+        ];
+
+        for (title, pretty, slug_from, expected) in test_cases.iter() {
+            let config = UrlConfig {
+                pretty: *pretty,
+                slug_from: *slug_from,
+                ..UrlConfig::default()
+            };
+            assert_eq!(
+                page_url(&config, &page).unwrap(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                (pretty, slug_from),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_page_url_errors() {
+        let page = Page::new("blog/hello.md", "").unwrap();
+        let test_cases = vec![
+            (
+                "Test date needed",
+                "/:year/:slug/",
+                "the permalink /:year/:slug/ needs a date",
+            ),
+            (
+                "Test unknown name",
+                "/:author/:slug/",
+                "unknown :author in the permalink /:author/:slug/",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let mut config = UrlConfig::default();
+            config
+                .permalinks
+                .insert(String::from("blog"), String::from(*input));
+            assert_eq!(
+                page_url(&config, &page).unwrap_err(),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
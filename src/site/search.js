@@ -0,0 +1,291 @@
+// The search box for sites built with rust-ssg. base.html loads it with the
+// url of search_index.json in `data-index`, the index is only fetched once
+// the box is used. The box goes in the element with id="search" when the
+// page has one, or else at the top of the page.
+//
+// Words are split up, left out and stemmed the same way as search.rs and
+// stem.rs do it for the index, the three have to stay in step.
+(() => {
+  "use strict";
+
+  const STOP_WORDS = new Set([/* STOP_WORDS */]);
+  const MAX_WORD_CHARS = 40;
+  const MAX_RESULTS = 10;
+
+  const script = document.currentScript;
+  const indexUrl = script.getAttribute("data-index");
+  let loading = null;
+
+  function loadIndex() {
+    if (!loading) {
+      loading = fetch(indexUrl).then((response) => {
+        if (!response.ok) {
+          throw new Error(`${indexUrl}: ${response.status}`);
+        }
+        return response.json();
+      });
+    }
+    return loading;
+  }
+
+  // Porter stemmer, see stem.rs.
+
+  const STEP_2 = [
+    ["ational", "ate"], ["tional", "tion"], ["enci", "ence"], ["anci", "ance"],
+    ["izer", "ize"], ["abli", "able"], ["alli", "al"], ["entli", "ent"],
+    ["eli", "e"], ["ousli", "ous"], ["ization", "ize"], ["ation", "ate"],
+    ["ator", "ate"], ["alism", "al"], ["iveness", "ive"], ["fulness", "ful"],
+    ["ousness", "ous"], ["aliti", "al"], ["iviti", "ive"], ["biliti", "ble"],
+  ];
+  const STEP_3 = [
+    ["icate", "ic"], ["ative", ""], ["alize", "al"], ["iciti", "ic"],
+    ["ical", "ic"], ["ful", ""], ["ness", ""],
+  ];
+  const STEP_4 = [
+    "al", "ance", "ence", "er", "ic", "able", "ible", "ant", "ement", "ment",
+    "ent", "ou", "ism", "ate", "iti", "ous", "ive", "ize",
+  ].map((suffix) => [suffix, ""]);
+
+  function isConsonant(w, i) {
+    switch (w[i]) {
+      case "a": case "e": case "i": case "o": case "u":
+        return false;
+      case "y":
+        return i === 0 || !isConsonant(w, i - 1);
+      default:
+        return true;
+    }
+  }
+
+  function measure(w) {
+    let m = 0;
+    let afterVowel = false;
+    for (let i = 0; i < w.length; i++) {
+      if (isConsonant(w, i)) {
+        if (afterVowel) {
+          m++;
+        }
+        afterVowel = false;
+      } else {
+        afterVowel = true;
+      }
+    }
+    return m;
+  }
+
+  function hasVowel(w) {
+    for (let i = 0; i < w.length; i++) {
+      if (!isConsonant(w, i)) {
+        return true;
+      }
+    }
+    return false;
+  }
+
+  function endsDoubleConsonant(w) {
+    const n = w.length;
+    return n >= 2 && w[n - 1] === w[n - 2] && isConsonant(w, n - 1);
+  }
+
+  function endsCvc(w) {
+    const n = w.length;
+    return n >= 3 && isConsonant(w, n - 1) && !isConsonant(w, n - 2) &&
+      isConsonant(w, n - 3) && !"wxy".includes(w[n - 1]);
+  }
+
+  function replaceSuffix(w, rules, condition) {
+    const rule = rules.find(([suffix]) => w.endsWith(suffix));
+    if (!rule) {
+      return w;
+    }
+    const stem = w.slice(0, w.length - rule[0].length);
+    return condition(stem) ? stem + rule[1] : w;
+  }
+
+  function stem(word) {
+    if (word.length <= 2 || !/^[a-z]+$/.test(word)) {
+      return word;
+    }
+
+    let w = replaceSuffix(word, [["sses", "ss"], ["ies", "i"], ["ss", "ss"], ["s", ""]], () => true);
+
+    if (w.endsWith("eed")) {
+      if (measure(w.slice(0, -3)) > 0) {
+        w = w.slice(0, -1);
+      }
+    } else {
+      const suffix = ["ed", "ing"].find((s) => w.endsWith(s) && hasVowel(w.slice(0, -s.length)));
+      if (suffix) {
+        w = w.slice(0, -suffix.length);
+        if (w.endsWith("at") || w.endsWith("bl") || w.endsWith("iz")) {
+          w += "e";
+        } else if (endsDoubleConsonant(w) && !"lsz".includes(w[w.length - 1])) {
+          w = w.slice(0, -1);
+        } else if (measure(w) === 1 && endsCvc(w)) {
+          w += "e";
+        }
+      }
+    }
+
+    if (w.endsWith("y") && hasVowel(w.slice(0, -1))) {
+      w = w.slice(0, -1) + "i";
+    }
+
+    w = replaceSuffix(w, STEP_2, (s) => measure(s) > 0);
+    w = replaceSuffix(w, STEP_3, (s) => measure(s) > 0);
+
+    if (w.endsWith("ion")) {
+      const s = w.slice(0, -3);
+      if ((s.endsWith("s") || s.endsWith("t")) && measure(s) > 1) {
+        w = s;
+      }
+    } else {
+      w = replaceSuffix(w, STEP_4, (s) => measure(s) > 1);
+    }
+
+    if (w.endsWith("e")) {
+      const s = w.slice(0, -1);
+      const m = measure(s);
+      if (m > 1 || (m === 1 && !endsCvc(s))) {
+        w = s;
+      }
+    }
+    if (w.endsWith("l") && endsDoubleConsonant(w) && measure(w) > 1) {
+      w = w.slice(0, -1);
+    }
+    return w;
+  }
+
+  function tokenize(text, stemming) {
+    return text
+      .split(/[^\p{Alphabetic}\p{N}]+/u)
+      .map((w) => w.toLowerCase())
+      .filter((w) => {
+        const chars = [...w].length;
+        return chars >= 2 && chars <= MAX_WORD_CHARS && !STOP_WORDS.has(w);
+      })
+      .map((w) => (stemming ? stem(w) : w));
+  }
+
+  // The pages with every word typed in, best first. The last word is
+  // matched as the start of a word too, since it may not be finished yet.
+  function search(index, query) {
+    const words = tokenize(query, index.stemming);
+    if (words.length === 0) {
+      return [];
+    }
+    const finished = /[^\p{Alphabetic}\p{N}]$/u.test(query);
+
+    let scores = null;
+    words.forEach((word, i) => {
+      const found = new Map();
+      const add = (term) => {
+        const pairs = index.terms[term];
+        for (let p = 0; p < pairs.length; p += 2) {
+          found.set(pairs[p], (found.get(pairs[p]) || 0) + pairs[p + 1]);
+        }
+      };
+
+      if (i === words.length - 1 && !finished) {
+        Object.keys(index.terms).filter((term) => term.startsWith(word)).forEach(add);
+      } else if (Object.hasOwn(index.terms, word)) {
+        add(word);
+      }
+
+      if (scores === null) {
+        scores = found;
+      } else {
+        for (const doc of [...scores.keys()]) {
+          if (found.has(doc)) {
+            scores.set(doc, scores.get(doc) + found.get(doc));
+          } else {
+            scores.delete(doc);
+          }
+        }
+      }
+    });
+
+    return [...scores.entries()]
+      .sort((a, b) => b[1] - a[1] || a[0] - b[0])
+      .slice(0, MAX_RESULTS)
+      .map(([doc]) => index.docs[doc]);
+  }
+
+  function show(list, results, query) {
+    list.replaceChildren();
+    if (query.trim() !== "" && results.length === 0) {
+      const item = document.createElement("li");
+      item.textContent = "No results";
+      list.append(item);
+    }
+    for (const [url, title, summary] of results) {
+      const item = document.createElement("li");
+      const link = document.createElement("a");
+      link.href = url;
+      link.textContent = title;
+      item.append(link);
+      if (summary) {
+        const text = document.createElement("p");
+        text.textContent = summary;
+        item.append(text);
+      }
+      list.append(item);
+    }
+  }
+
+  function mount() {
+    const form = document.createElement("form");
+    form.className = "search";
+    form.setAttribute("role", "search");
+    const input = document.createElement("input");
+    input.type = "search";
+    input.placeholder = "Search";
+    input.setAttribute("aria-label", "Search");
+    const list = document.createElement("ol");
+    list.className = "search-results";
+    form.append(input, list);
+
+    const update = () => {
+      const query = input.value;
+      loadIndex()
+        .then((index) => {
+          // Answers can come back after more has been typed.
+          if (input.value === query) {
+            show(list, search(index, query), query);
+          }
+        })
+        .catch((error) => {
+          list.replaceChildren();
+          console.error("search:", error);
+        });
+    };
+    input.addEventListener("focus", loadIndex, { once: true });
+    input.addEventListener("input", update);
+    input.addEventListener("keydown", (event) => {
+      if (event.key === "Escape") {
+        input.value = "";
+        list.replaceChildren();
+      }
+    });
+    form.addEventListener("submit", (event) => {
+      event.preventDefault();
+      const first = list.querySelector("a");
+      if (first) {
+        window.location.href = first.href;
+      }
+    });
+
+    const place = document.getElementById("search");
+    if (place) {
+      place.append(form);
+    } else {
+      document.body.prepend(form);
+    }
+  }
+
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", mount);
+  } else {
+    mount();
+  }
+})();
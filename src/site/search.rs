@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::nodes::html_node::HTMLChildNode;
+
+use super::config::Config;
+use super::page::Page;
+use super::stem::stem;
+
+// Written to the root of the output when search is turned on.
+pub const INDEX_FILE: &str = "search_index.json";
+pub const SCRIPT_FILE: &str = "search.js";
+
+const SCRIPT: &str = include_str!("search.js");
+// Where the stop words go in the script.
+const STOP_WORDS_MARKER: &str = "[/* STOP_WORDS */]";
+
+// How much a word counts for where it is on the page. Words in the text
+// count once for every time they're there, up to TEXT_LIMIT, so a page that
+// says a word a lot doesn't beat one with it in the title.
+const TITLE_WEIGHT: u32 = 10;
+const HEADING_WEIGHT: u32 = 5;
+const TAG_WEIGHT: u32 = 5;
+const TEXT_LIMIT: u32 = 5;
+
+// Longer "words" are things like hashes that nobody types in.
+const MAX_WORD_CHARS: usize = 40;
+
+const HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+// Words too common to be worth looking for. The script leaves them out of
+// what's typed in too.
+pub const STOP_WORDS: [&str; 113] = [
+    "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are", "as",
+    "at", "be", "because", "been", "before", "being", "below", "between", "both", "but", "by",
+    "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for", "from",
+    "further", "had", "has", "have", "having", "he", "her", "here", "hers", "him", "his", "how",
+    "if", "in", "into", "is", "it", "its", "just", "me", "more", "most", "my", "no", "nor", "not",
+    "of", "off", "on", "once", "only", "or", "other", "our", "out", "over", "own", "same", "she",
+    "should", "so", "some", "such", "than", "that", "the", "their", "them", "then", "there",
+    "these", "they", "this", "those", "through", "to", "too", "under", "until", "up", "very",
+    "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom", "why", "will",
+    "with", "would", "you", "your", "yours",
+];
+
+// search_index.json, which search.js loads the first time it's used.
+//
+// Ex.
+// {"stemming":true,"docs":[["/docs/intro/","Intro","Getting started…"]],
+//  "terms":{"instal":[0,11],"start":[0,6]}}
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchIndex {
+    // Whether the words are stemmed, so the script knows to stem what's
+    // typed in.
+    pub stemming: bool,
+    // The url, title and summary of every page, results point at them by
+    // their place in the list.
+    pub docs: Vec<(String, String, String)>,
+    // Every word to the pages it's on and how much it counts on each, as
+    // pairs of numbers one after the other.
+    // Ex. "instal": [0, 11, 4, 1] is on page 0 with 11 and page 4 with 1
+    pub terms: BTreeMap<String, Vec<u32>>,
+}
+
+// Indexes the title, headings, taxonomy terms and text of every page, apart
+// from the ones with `search: false`.
+pub fn search_index(config: &Config, pages: &[Page]) -> SearchIndex {
+    let options = &config.search;
+    let mut index = SearchIndex {
+        stemming: options.stemming,
+        docs: vec![],
+        terms: BTreeMap::new(),
+    };
+
+    for page in pages
+        .iter()
+        .filter(|p| p.front_matter.search && p.is_published())
+    {
+        let mut scores: BTreeMap<String, u32> = BTreeMap::new();
+        let mut add = |text: &str, weight: u32| {
+            for word in tokenize(config, text) {
+                *scores.entry(word).or_default() += weight;
+            }
+        };
+
+        add(&page.title(), TITLE_WEIGHT);
+        let root = &page.document.root;
+        for node in root.descendants() {
+            if let HTMLChildNode::Parent(p) = node {
+                if HEADINGS.contains(&p.tag()) {
+                    add(&node.to_plain_text(), HEADING_WEIGHT);
+                }
+            }
+        }
+        for taxonomy in &config.taxonomies {
+            for term in page.front_matter.terms(&taxonomy.name) {
+                add(&term, TAG_WEIGHT);
+            }
+        }
+
+        let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+        for word in tokenize(config, &root.to_plain_text()) {
+            *counts.entry(word).or_default() += 1;
+        }
+        for (word, count) in counts {
+            *scores.entry(word).or_default() += count.min(TEXT_LIMIT);
+        }
+
+        let doc = index.docs.len() as u32;
+        for (word, score) in scores {
+            index.terms.entry(word).or_default().extend([doc, score]);
+        }
+
+        let summary = page
+            .front_matter
+            .summary
+            .clone()
+            .unwrap_or_else(|| page.summary(options.summary_words).text);
+        index.docs.push((page.url.clone(), page.title(), summary));
+    }
+
+    index
+}
+
+// Lowercase words, without stop words and stemmed when the config says so.
+// search.js splits up what's typed in the same way.
+// Ex. "Installing the CLI" -> ["instal", "cli"]
+pub fn tokenize(config: &Config, text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| (2..=MAX_WORD_CHARS).contains(&w.chars().count()))
+        .filter(|w| !config.search.stop_words || !STOP_WORDS.contains(&w.as_str()))
+        .map(|w| if config.search.stemming { stem(&w) } else { w })
+        .collect()
+}
+
+// search.js with the stop words the index was made with.
+pub fn search_script(config: &Config) -> String {
+    let stop_words: &[&str] = if config.search.stop_words {
+        &STOP_WORDS
+    } else {
+        &[]
+    };
+    let list = serde_json::to_string(stop_words).expect("strings are valid json");
+    SCRIPT.replacen(STOP_WORDS_MARKER, &list, 1)
+}
+
+impl SearchIndex {
+    // The index as json, no bigger than `max_bytes` unless it's 0. To fit,
+    // a word is dropped from the pages where it counts the least first, and
+    // of those from the words on the most pages. Pages are never dropped,
+    // their summaries go only when there are no words left to drop.
+    pub fn to_json(&self, max_bytes: usize) -> String {
+        let json = self.json();
+        if max_bytes == 0 || json.len() <= max_bytes {
+            return json;
+        }
+
+        // Every word on every page as its score there, how many pages it's
+        // on, the word and the page.
+        let mut postings: Vec<(u32, usize, &str, u32)> = self
+            .terms
+            .iter()
+            .flat_map(|(word, pairs)| {
+                pairs
+                    .chunks(2)
+                    .map(move |pair| (pair[1], pairs.len() / 2, word.as_str(), pair[0]))
+            })
+            .collect();
+        postings.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(b.1.cmp(&a.1))
+                .then(a.2.cmp(b.2))
+                .then(a.3.cmp(&b.3))
+        });
+
+        let without = |count: usize| {
+            let dropped: BTreeSet<(&str, u32)> =
+                postings[..count].iter().map(|p| (p.2, p.3)).collect();
+            let mut index = self.clone();
+            index.terms = self
+                .terms
+                .iter()
+                .filter_map(|(word, pairs)| {
+                    let kept: Vec<u32> = pairs
+                        .chunks(2)
+                        .filter(|pair| !dropped.contains(&(word.as_str(), pair[0])))
+                        .flatten()
+                        .copied()
+                        .collect();
+                    (!kept.is_empty()).then(|| (word.clone(), kept))
+                })
+                .collect();
+            index
+        };
+
+        // The fewest words to drop from pages for it to fit.
+        let (mut low, mut high) = (0, postings.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if without(middle).json().len() <= max_bytes {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        let mut index = without(low);
+        if index.json().len() > max_bytes {
+            for doc in &mut index.docs {
+                doc.2.clear();
+            }
+        }
+        index.json()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).expect("the index is valid json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn pages() -> Vec<Page> {
+        vec![
+            Page::new(
+                "docs/install.md",
+                "---\ntags: [Setup]\n---\n# Installing\n\nRun the installer.\n\n## On Linux\n\nUse the package.",
+            )
+            .unwrap(),
+            Page::new("docs/config.md", "# Config\n\nThe package reads config.yml.").unwrap(),
+            Page::new("docs/secret.md", "---\nsearch: false\n---\n# Secret").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let config = Config::default();
+        let test_cases = vec![
+            (
+                "Test stop words and stems",
+                "Installing the CLI",
+                vec!["instal", "cli"],
+            ),
+            (
+                "Test punctuation splits words",
+                "config.yml, don't",
+                vec!["config", "yml", "don"],
+            ),
+            ("Test single letters dropped", "a b c", vec![]),
+            (
+                "Test other scripts are lowercased only",
+                "Größe Über",
+                vec!["größe", "über"],
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                tokenize(&config, input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_index() {
+        let index = search_index(&Config::default(), &pages());
+        let urls: Vec<&str> = index.docs.iter().map(|d| d.0.as_str()).collect();
+        // Pages are sorted by the site, not here.
+        assert_eq!(urls, vec!["/docs/install/", "/docs/config/"]);
+
+        let test_cases = vec![
+            // The title, heading and text, the title is also the h1.
+            ("Test title counts most", "instal", vec![0, 10 + 5 + 2]),
+            ("Test heading", "linux", vec![0, 5 + 1]),
+            ("Test tag", "setup", vec![0, 5]),
+            ("Test word on two pages", "packag", vec![0, 1, 1, 1]),
+            ("Test stop words left out", "the", vec![]),
+            ("Test pages with search off left out", "secret", vec![]),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let postings = index.terms.get(*input).cloned().unwrap_or_default();
+            assert_eq!(
+                postings, *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title, input, expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_size_budget() {
+        let index = search_index(&Config::default(), &pages());
+        let full = index.to_json(0);
+        let test_cases = vec![
+            ("Test no limit", 0, "\"packag\":[0,1,1,1]"),
+            ("Test big enough", full.len(), "\"packag\":[0,1,1,1]"),
+            (
+                "Test word on the most pages goes first",
+                full.len() - 1,
+                "\"packag\":[1,1],\"read\":[1,1],\"run\":[0,1]",
+            ),
+            (
+                "Test words that count most are kept",
+                full.len() - 60,
+                "\"terms\":{\"config\":[1,17],\"instal\":[0,17],\"linux\":[0,6],\"setup\":[0,5]}",
+            ),
+            (
+                "Test summaries go last",
+                1,
+                "[\"/docs/config/\",\"Config\",\"\"]],\"terms\":{}",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            let json = index.to_json(*input);
+            assert!(
+                json.contains(expected) && (*input == 0 || *input == 1 || json.len() <= *input),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_script() {
+        let script = search_script(&Config::default());
+        assert!(script.contains("[\"about\",\"above\","));
+        assert!(!script.contains(STOP_WORDS_MARKER));
+
+        let mut config = Config::default();
+        config.search.stop_words = false;
+        assert!(search_script(&config).contains("new Set([])"));
+    }
+}
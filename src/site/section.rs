@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::front_matter::SortBy;
+use super::page::{sort_newest_first, Page};
+
+// A directory under `content`, with the pages right in it. Its `index.md` or
+// `_index.md` is the page at the section's url, the front matter of an
+// `_index.md` also picks how the pages are sorted.
+//
+// Ex. content/blog/_index.md, content/blog/hello.md -> the section "blog"
+#[derive(Debug)]
+pub struct Section<'a> {
+    // Relative to the content directory, "" for the top.
+    // Ex. blog/2024
+    pub path: String,
+    // Ex. /blog/2024/
+    pub url: String,
+    pub index: Option<&'a Page>,
+    // Sorted by the `sort_by` of the index, the index isn't one of them.
+    pub pages: Vec<&'a Page>,
+    // Paths of the sections right under this one, lowest weight first and
+    // then by path.
+    pub subsections: Vec<String>,
+}
+
+impl Section<'_> {
+    // The title of the index page, or else the directory name.
+    pub fn title(&self) -> String {
+        match self.index {
+            Some(index) => index.title(),
+            None if self.path.is_empty() => String::from("Home"),
+            None => String::from(self.path.rsplit('/').next().unwrap_or("")),
+        }
+    }
+
+    // Whether the section gets a listing page, pages named `index.md` take
+    // its place.
+    pub fn is_listed(&self) -> bool {
+        self.index.is_none_or(|index| index.is_section_index())
+    }
+}
+
+// A title and a url, for navigation in templates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NavLink {
+    pub title: String,
+    pub url: String,
+}
+
+impl NavLink {
+    pub fn page(page: &Page) -> NavLink {
+        NavLink {
+            title: page.title(),
+            url: page.url.clone(),
+        }
+    }
+
+    pub fn section(section: &Section) -> NavLink {
+        NavLink {
+            title: section.title(),
+            url: section.url.clone(),
+        }
+    }
+}
+
+// Where a page is in the site.
+#[derive(Debug, Default, PartialEq)]
+pub struct Navigation {
+    // The section the page is in, for an index it's the one above.
+    pub section: Option<NavLink>,
+    // From the top section down to the page itself.
+    pub breadcrumbs: Vec<NavLink>,
+    // Every page of the section in order, the page included.
+    pub siblings: Vec<NavLink>,
+    // The pages before and after it in `siblings`.
+    pub prev: Option<NavLink>,
+    pub next: Option<NavLink>,
+}
+
+// Every section of the site by path. Directories above a page are sections
+// even when nothing else is in them.
+#[derive(Debug)]
+pub struct Sections<'a> {
+    sections: BTreeMap<String, Section<'a>>,
+}
+
+impl<'a> Sections<'a> {
+    pub fn new(pages: &'a [Page]) -> Sections<'a> {
+        let mut sections: BTreeMap<String, Section> = BTreeMap::new();
+        sections.insert(String::new(), section(""));
+
+        for page in pages {
+            let path = page.section();
+            let mut dir = path;
+            while !dir.is_empty() && !sections.contains_key(dir) {
+                sections.insert(String::from(dir), section(dir));
+                dir = parent_path(dir);
+            }
+
+            let section = sections.get_mut(path).expect("added above");
+            if page.is_index() {
+                // `_index.md` wins when a directory has both.
+                if section.index.is_none_or(|i| !i.is_section_index()) {
+                    section.index = Some(page);
+                }
+            } else {
+                section.pages.push(page);
+            }
+        }
+
+        let paths: Vec<String> = sections.keys().skip(1).cloned().collect();
+        for path in paths {
+            let parent = sections.get_mut(parent_path(&path)).expect("added above");
+            parent.subsections.push(path);
+        }
+
+        let weights: BTreeMap<String, Option<i64>> = sections
+            .iter()
+            .map(|(path, s)| (path.clone(), s.index.and_then(|i| i.front_matter.weight)))
+            .collect();
+        for section in sections.values_mut() {
+            let sort_by = section
+                .index
+                .map(|i| i.front_matter.sort_by)
+                .unwrap_or(SortBy::Date);
+            sort_pages(&mut section.pages, sort_by);
+            section
+                .subsections
+                .sort_by_key(|path| (weights[path].is_none(), weights[path], path.clone()));
+        }
+
+        Sections { sections }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Section<'a>> {
+        self.sections.get(path)
+    }
+
+    // Sorted by path, the top section first.
+    pub fn iter(&self) -> impl Iterator<Item = &Section<'a>> {
+        self.sections.values()
+    }
+
+    // The sections from the top down to `path`, `path` included.
+    pub fn ancestors(&self, path: &str) -> Vec<&Section<'a>> {
+        let mut found = Vec::new();
+        let mut dir = path;
+        loop {
+            if let Some(section) = self.sections.get(dir) {
+                found.push(section);
+            }
+            if dir.is_empty() {
+                break;
+            }
+            dir = parent_path(dir);
+        }
+
+        found.reverse();
+        found
+    }
+
+    pub fn navigation(&self, page: &Page) -> Navigation {
+        let Some(section) = self.sections.get(page.section()) else {
+            return Navigation::default();
+        };
+        let mut breadcrumbs: Vec<NavLink> = self
+            .ancestors(&section.path)
+            .into_iter()
+            .map(NavLink::section)
+            .collect();
+
+        if page.is_index() {
+            let parent = (!section.path.is_empty())
+                .then(|| self.sections.get(parent_path(&section.path)))
+                .flatten();
+            return Navigation {
+                section: parent.map(NavLink::section),
+                breadcrumbs,
+                ..Navigation::default()
+            };
+        }
+
+        breadcrumbs.push(NavLink::page(page));
+        let position = section.pages.iter().position(|p| std::ptr::eq(*p, page));
+        let at = |offset: isize| {
+            position
+                .and_then(|i| i.checked_add_signed(offset))
+                .and_then(|i| section.pages.get(i))
+                .map(|p| NavLink::page(p))
+        };
+
+        Navigation {
+            section: Some(NavLink::section(section)),
+            breadcrumbs,
+            siblings: section.pages.iter().map(|p| NavLink::page(p)).collect(),
+            prev: at(-1),
+            next: at(1),
+        }
+    }
+}
+
+fn section(path: &str) -> Section<'_> {
+    Section {
+        path: String::from(path),
+        url: if path.is_empty() {
+            String::from("/")
+        } else {
+            format!("/{}/", path)
+        },
+        index: None,
+        pages: vec![],
+        subsections: vec![],
+    }
+}
+
+// Ex. blog/2024 -> blog, blog -> ""
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+fn sort_pages(pages: &mut [&Page], sort_by: SortBy) {
+    match sort_by {
+        SortBy::Date => sort_newest_first(pages),
+        SortBy::Weight => pages.sort_by_key(|p| {
+            let weight = p.front_matter.weight;
+            (weight.is_none(), weight, p.source.clone())
+        }),
+        SortBy::Title => pages.sort_by_cached_key(|p| (p.title().to_lowercase(), p.source.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn pages() -> Vec<Page> {
+        vec![
+            Page::new("index.md", "# Home").unwrap(),
+            Page::new("docs/_index.md", "---\ntitle: Docs\nsort_by: weight\n---\n").unwrap(),
+            Page::new("docs/setup.md", "---\nweight: 2\n---\n# Setup").unwrap(),
+            Page::new("docs/intro.md", "---\nweight: 1\n---\n# Intro").unwrap(),
+            Page::new("docs/faq.md", "# FAQ").unwrap(),
+            Page::new("docs/api/_index.md", "---\nweight: 1\n---\n# API").unwrap(),
+            Page::new("blog/2024/old.md", "---\ndate: 2024-01-01\n---\n# Old").unwrap(),
+            Page::new("blog/2024/new.md", "---\ndate: 2024-06-01\n---\n# New").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_sections() {
+        let pages = pages();
+        let sections = Sections::new(&pages);
+
+        let test_cases = vec![
+            (
+                "Test the top section",
+                "",
+                "Home",
+                vec![],
+                vec!["blog", "docs"],
+            ),
+            (
+                "Test sorted by weight",
+                "docs",
+                "Docs",
+                vec!["docs/intro.md", "docs/setup.md", "docs/faq.md"],
+                vec!["docs/api"],
+            ),
+            (
+                "Test sorted by date by default",
+                "blog/2024",
+                "2024",
+                vec!["blog/2024/new.md", "blog/2024/old.md"],
+                vec![],
+            ),
+            (
+                "Test directory without pages",
+                "blog",
+                "blog",
+                vec![],
+                vec!["blog/2024"],
+            ),
+        ];
+
+        for (title, input, expected_title, expected_pages, expected_subsections) in
+            test_cases.iter()
+        {
+            let section = sections.get(input).unwrap();
+            let pages: Vec<&str> = section.pages.iter().map(|p| p.source.as_str()).collect();
+            assert_eq!(
+                (
+                    section.title(),
+                    pages,
+                    section.subsections.iter().map(String::as_str).collect()
+                ),
+                (
+                    String::from(*expected_title),
+                    expected_pages.clone(),
+                    expected_subsections.clone()
+                ),
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected_title
+            );
+        }
+
+        let listed: Vec<&str> = sections
+            .iter()
+            .filter(|s| s.is_listed())
+            .map(|s| s.url.as_str())
+            .collect();
+        assert_eq!(
+            listed,
+            vec!["/blog/", "/blog/2024/", "/docs/", "/docs/api/"]
+        );
+    }
+
+    #[test]
+    fn test_navigation() {
+        let pages = pages();
+        let sections = Sections::new(&pages);
+        let link = |title: &str, url: &str| NavLink {
+            title: String::from(title),
+            url: String::from(url),
+        };
+        let page = |source: &str| pages.iter().find(|p| p.source == source).unwrap();
+
+        let test_cases = vec![
+            (
+                "Test page in the middle",
+                "docs/setup.md",
+                Navigation {
+                    section: Some(link("Docs", "/docs/")),
+                    breadcrumbs: vec![
+                        link("Home", "/"),
+                        link("Docs", "/docs/"),
+                        link("Setup", "/docs/setup/"),
+                    ],
+                    siblings: vec![
+                        link("Intro", "/docs/intro/"),
+                        link("Setup", "/docs/setup/"),
+                        link("FAQ", "/docs/faq/"),
+                    ],
+                    prev: Some(link("Intro", "/docs/intro/")),
+                    next: Some(link("FAQ", "/docs/faq/")),
+                },
+            ),
+            (
+                "Test section index",
+                "docs/api/_index.md",
+                Navigation {
+                    section: Some(link("Docs", "/docs/")),
+                    breadcrumbs: vec![
+                        link("Home", "/"),
+                        link("Docs", "/docs/"),
+                        link("API", "/docs/api/"),
+                    ],
+                    ..Navigation::default()
+                },
+            ),
+            (
+                "Test home page",
+                "index.md",
+                Navigation {
+                    breadcrumbs: vec![link("Home", "/")],
+                    ..Navigation::default()
+                },
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                sections.navigation(page(input)),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {:?}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
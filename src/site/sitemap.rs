@@ -0,0 +1,249 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use super::config::Config;
+use super::feed::escape_xml;
+
+// The most urls a single sitemap may list, bigger sites get split into
+// several sitemaps with a sitemap index pointing at them.
+pub const SITEMAP_LIMIT: usize = 50_000;
+
+#[derive(Debug, PartialEq)]
+pub struct SitemapEntry {
+    // Absolute.
+    pub url: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+// The sitemap files to write, as paths relative to the output directory and
+// their contents. A small site only has sitemap.xml, past the limit
+// sitemap.xml becomes an index of sitemap1.xml, sitemap2.xml and so on.
+pub fn sitemaps(config: &Config, entries: &[SitemapEntry]) -> Vec<(String, String)> {
+    split_sitemaps(config, entries, SITEMAP_LIMIT)
+}
+
+fn split_sitemaps(
+    config: &Config,
+    entries: &[SitemapEntry],
+    limit: usize,
+) -> Vec<(String, String)> {
+    if entries.len() <= limit {
+        return vec![(String::from("sitemap.xml"), urlset(entries))];
+    }
+
+    let mut files = Vec::new();
+    let mut index = String::new();
+    let _ = writeln!(index, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(
+        index,
+        "<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">"
+    );
+
+    for (i, chunk) in entries.chunks(limit).enumerate() {
+        let path = format!("sitemap{}.xml", i + 1);
+        let _ = writeln!(index, "<sitemap>");
+        let _ = writeln!(index, "<loc>{}</loc>", escape_xml(&config.permalink(&path)));
+        if let Some(lastmod) = chunk.iter().filter_map(|e| e.lastmod).max() {
+            let _ = writeln!(index, "<lastmod>{}</lastmod>", w3c_date(&lastmod));
+        }
+        let _ = writeln!(index, "</sitemap>");
+
+        files.push((path, urlset(chunk)));
+    }
+
+    let _ = writeln!(index, "</sitemapindex>");
+    files.insert(0, (String::from("sitemap.xml"), index));
+    files
+}
+
+fn urlset(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::new();
+
+    // Writing to a String can't fail.
+    let _ = writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(
+        xml,
+        "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">"
+    );
+    for entry in entries {
+        let _ = writeln!(xml, "<url>");
+        let _ = writeln!(xml, "<loc>{}</loc>", escape_xml(&entry.url));
+        if let Some(lastmod) = &entry.lastmod {
+            let _ = writeln!(xml, "<lastmod>{}</lastmod>", w3c_date(lastmod));
+        }
+        let _ = writeln!(xml, "</url>");
+    }
+    let _ = writeln!(xml, "</urlset>");
+
+    xml
+}
+
+// Ex. 2024-03-01T09:30:00Z
+fn w3c_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Ex.
+// User-agent: *
+// Disallow: /private/
+//
+// Sitemap: https://example.com/sitemap.xml
+pub fn robots_txt(config: &Config) -> String {
+    let mut txt = String::new();
+
+    for rule in &config.robots.rules {
+        let _ = writeln!(txt, "User-agent: {}", rule.user_agent);
+        for path in &rule.allow {
+            let _ = writeln!(txt, "Allow: {}", path);
+        }
+        for path in &rule.disallow {
+            let _ = writeln!(txt, "Disallow: {}", path);
+        }
+        // A group needs at least one rule, an empty disallow allows
+        // everything.
+        if rule.allow.is_empty() && rule.disallow.is_empty() {
+            let _ = writeln!(txt, "Disallow:");
+        }
+        let _ = writeln!(txt);
+    }
+
+    let _ = writeln!(txt, "Sitemap: {}", config.permalink("sitemap.xml"));
+    txt
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::TimeZone;
+
+    use crate::site::config::RobotsRule;
+
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            base_url: String::from("https://example.com"),
+            ..Config::default()
+        }
+    }
+
+    fn entries(count: usize) -> Vec<SitemapEntry> {
+        (1..=count)
+            .map(|i| SitemapEntry {
+                url: format!("https://example.com/{}/?a=1&b=2", i),
+                lastmod: Some(Utc.with_ymd_and_hms(2024, 3, i as u32, 0, 0, 0).unwrap()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sitemaps() {
+        let test_cases = vec![
+            (
+                "Test a single sitemap",
+                split_sitemaps(&config(), &entries(1), 2),
+                vec![(
+                    "sitemap.xml",
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">
+<url>
+<loc>https://example.com/1/?a=1&amp;b=2</loc>
+<lastmod>2024-03-01T00:00:00Z</lastmod>
+</url>
+</urlset>
+",
+                )],
+            ),
+            (
+                "Test a sitemap index past the limit",
+                split_sitemaps(&config(), &entries(3), 2)
+                    .into_iter()
+                    .take(1)
+                    .collect(),
+                vec![(
+                    "sitemap.xml",
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">
+<sitemap>
+<loc>https://example.com/sitemap1.xml</loc>
+<lastmod>2024-03-02T00:00:00Z</lastmod>
+</sitemap>
+<sitemap>
+<loc>https://example.com/sitemap2.xml</loc>
+<lastmod>2024-03-03T00:00:00Z</lastmod>
+</sitemap>
+</sitemapindex>
+",
+                )],
+            ),
+        ];
+
+        for (title, output, expected) in test_cases.iter() {
+            let output: Vec<(&str, &str)> = output
+                .iter()
+                .map(|(p, c)| (p.as_str(), c.as_str()))
+                .collect();
+            assert_eq!(
+                &output, expected,
+                "\"{}\" test failed and expexted: {:?}",
+                title, expected
+            );
+        }
+
+        let paths: Vec<String> = split_sitemaps(&config(), &entries(5), 2)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "sitemap.xml",
+                "sitemap1.xml",
+                "sitemap2.xml",
+                "sitemap3.xml"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_robots_txt() {
+        let mut custom = config();
+        custom.robots.rules = vec![
+            RobotsRule {
+                user_agent: String::from("*"),
+                allow: vec![String::from("/private/open/")],
+                disallow: vec![String::from("/private/")],
+            },
+            RobotsRule {
+                user_agent: String::from("BadBot"),
+                allow: vec![],
+                disallow: vec![String::from("/")],
+            },
+        ];
+
+        let test_cases = vec![
+            (
+                "Test default allows everything",
+                config(),
+                "User-agent: *\nDisallow:\n\nSitemap: https://example.com/sitemap.xml\n",
+            ),
+            (
+                "Test rules from the config",
+                custom,
+                "User-agent: *\nAllow: /private/open/\nDisallow: /private/\n\nUser-agent: BadBot\nDisallow: /\n\nSitemap: https://example.com/sitemap.xml\n",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                robots_txt(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Cuts an english word down to its stem with the Porter algorithm, so every
+// form of a word finds the others. Words with anything but a to z in them
+// are left as they are. search.js does the same to what's typed in, the two
+// have to stay in step.
+// Ex. connect, connected, connecting, connection -> connect
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return String::from(word);
+    }
+
+    let mut w = word.as_bytes().to_vec();
+    step_1a(&mut w);
+    step_1b(&mut w);
+    step_1c(&mut w);
+    replace_suffix(&mut w, &STEP_2, |stem| measure(stem) > 0);
+    replace_suffix(&mut w, &STEP_3, |stem| measure(stem) > 0);
+    step_4(&mut w);
+    step_5(&mut w);
+
+    String::from_utf8(w).expect("only a to z is stemmed")
+}
+
+const STEP_2: [(&str, &str); 20] = [
+    ("ational", "ate"),
+    ("tional", "tion"),
+    ("enci", "ence"),
+    ("anci", "ance"),
+    ("izer", "ize"),
+    ("abli", "able"),
+    ("alli", "al"),
+    ("entli", "ent"),
+    ("eli", "e"),
+    ("ousli", "ous"),
+    ("ization", "ize"),
+    ("ation", "ate"),
+    ("ator", "ate"),
+    ("alism", "al"),
+    ("iveness", "ive"),
+    ("fulness", "ful"),
+    ("ousness", "ous"),
+    ("aliti", "al"),
+    ("iviti", "ive"),
+    ("biliti", "ble"),
+];
+
+const STEP_3: [(&str, &str); 7] = [
+    ("icate", "ic"),
+    ("ative", ""),
+    ("alize", "al"),
+    ("iciti", "ic"),
+    ("ical", "ic"),
+    ("ful", ""),
+    ("ness", ""),
+];
+
+// `ion` is only dropped after an s or a t, so it's on its own in step_4.
+const STEP_4: [(&str, &str); 18] = [
+    ("al", ""),
+    ("ance", ""),
+    ("ence", ""),
+    ("er", ""),
+    ("ic", ""),
+    ("able", ""),
+    ("ible", ""),
+    ("ant", ""),
+    ("ement", ""),
+    ("ment", ""),
+    ("ent", ""),
+    ("ou", ""),
+    ("ism", ""),
+    ("ate", ""),
+    ("iti", ""),
+    ("ous", ""),
+    ("ive", ""),
+    ("ize", ""),
+];
+
+// Plurals.
+// Ex. caresses -> caress, ponies -> poni, cats -> cat
+fn step_1a(w: &mut Vec<u8>) {
+    replace_suffix(
+        w,
+        &[("sses", "ss"), ("ies", "i"), ("ss", "ss"), ("s", "")],
+        |_| true,
+    );
+}
+
+// Past tenses and -ing, tidying up what's left.
+// Ex. agreed -> agree, hopping -> hop, filing -> file
+fn step_1b(w: &mut Vec<u8>) {
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+        return;
+    }
+
+    let Some(suffix) = ["ed", "ing"]
+        .iter()
+        .find(|s| w.ends_with(s.as_bytes()) && has_vowel(&w[..w.len() - s.len()]))
+    else {
+        return;
+    };
+    w.truncate(w.len() - suffix.len());
+
+    if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+        w.push(b'e');
+    } else if ends_double_consonant(w) && !matches!(w.last(), Some(b'l' | b's' | b'z')) {
+        w.pop();
+    } else if measure(w) == 1 && ends_cvc(w) {
+        w.push(b'e');
+    }
+}
+
+// Ex. happy -> happi, sky -> sky
+fn step_1c(w: &mut [u8]) {
+    let n = w.len();
+    if w[n - 1] == b'y' && has_vowel(&w[..n - 1]) {
+        w[n - 1] = b'i';
+    }
+}
+
+fn step_4(w: &mut Vec<u8>) {
+    if w.ends_with(b"ion") {
+        let stem = &w[..w.len() - 3];
+        if matches!(stem.last(), Some(b's' | b't')) && measure(stem) > 1 {
+            w.truncate(w.len() - 3);
+        }
+        return;
+    }
+
+    replace_suffix(w, &STEP_4, |stem| measure(stem) > 1);
+}
+
+// A last e, and the second l of a double one.
+// Ex. probate -> probat, controll -> control
+fn step_5(w: &mut Vec<u8>) {
+    if w.last() == Some(&b'e') {
+        let stem = &w[..w.len() - 1];
+        let m = measure(stem);
+        if m > 1 || (m == 1 && !ends_cvc(stem)) {
+            w.pop();
+        }
+    }
+
+    if w.last() == Some(&b'l') && ends_double_consonant(w) && measure(w) > 1 {
+        w.pop();
+    }
+}
+
+// Swaps the first of the suffixes the word ends with for its replacement,
+// when what's before it passes `condition`. Only the first one that matches
+// is tried.
+fn replace_suffix(w: &mut Vec<u8>, rules: &[(&str, &str)], condition: impl Fn(&[u8]) -> bool) {
+    let Some((suffix, replacement)) = rules.iter().find(|(s, _)| w.ends_with(s.as_bytes())) else {
+        return;
+    };
+
+    let stem = w.len() - suffix.len();
+    if condition(&w[..stem]) {
+        w.truncate(stem);
+        w.extend_from_slice(replacement.as_bytes());
+    }
+}
+
+// A y is a consonant unless it comes after one.
+fn is_consonant(w: &[u8], i: usize) -> bool {
+    match w[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(w, i - 1),
+        _ => true,
+    }
+}
+
+// How many times vowels are followed by consonants.
+// Ex. tree 0, trouble 1, private 2
+fn measure(w: &[u8]) -> usize {
+    let mut m = 0;
+    let mut after_vowel = false;
+    for i in 0..w.len() {
+        if is_consonant(w, i) {
+            if after_vowel {
+                m += 1;
+            }
+            after_vowel = false;
+        } else {
+            after_vowel = true;
+        }
+    }
+    m
+}
+
+fn has_vowel(w: &[u8]) -> bool {
+    (0..w.len()).any(|i| !is_consonant(w, i))
+}
+
+fn ends_double_consonant(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 2 && w[n - 1] == w[n - 2] && is_consonant(w, n - 1)
+}
+
+// A consonant, a vowel and a consonant other than w, x or y at the end.
+// Ex. hop, fil
+fn ends_cvc(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 3
+        && is_consonant(w, n - 1)
+        && !is_consonant(w, n - 2)
+        && is_consonant(w, n - 3)
+        && !matches!(w[n - 1], b'w' | b'x' | b'y')
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_stem() {
+        let test_cases = vec![
+            ("Test plurals", "caresses", "caress"),
+            ("Test ies", "ponies", "poni"),
+            ("Test double s", "caress", "caress"),
+            ("Test eed", "agreed", "agre"),
+            ("Test eed without a vowel before", "feed", "feed"),
+            ("Test ed", "plastered", "plaster"),
+            ("Test ing", "motoring", "motor"),
+            ("Test ing without a vowel before", "sing", "sing"),
+            ("Test e put back", "conflated", "conflat"),
+            ("Test double consonant", "hopping", "hop"),
+            ("Test double l kept", "falling", "fall"),
+            ("Test e after cvc", "filing", "file"),
+            ("Test y", "happy", "happi"),
+            ("Test y after a consonant only", "sky", "sky"),
+            ("Test step 2", "relational", "relat"),
+            ("Test step 2 and 4", "conditional", "condit"),
+            ("Test every step", "generalization", "gener"),
+            ("Test step 3", "hopeful", "hope"),
+            ("Test step 4", "adjustable", "adjust"),
+            ("Test ion after t", "adoption", "adopt"),
+            ("Test double l", "controlling", "control"),
+            ("Test forms of a word", "installation", "instal"),
+            ("Test forms of a word again", "installing", "instal"),
+            ("Test short word", "is", "is"),
+            ("Test not ascii", "café", "café"),
+            ("Test numbers", "404s", "404s"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                stem(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use super::config::Config;
+use super::page::{sort_newest_first, Page};
+use super::urls::slugify;
+
+// A way of grouping pages, like tags, with every term used by a page.
+#[derive(Debug)]
+pub struct Taxonomy<'a> {
+    pub name: String,
+    // Ex. /tags/
+    pub url: String,
+    pub feed: bool,
+    // Sorted by slug.
+    pub terms: Vec<Term<'a>>,
+}
+
+#[derive(Debug)]
+pub struct Term<'a> {
+    // As it was first written in the front matter.
+    pub name: String,
+    pub slug: String,
+    // Ex. /tags/rust/
+    pub url: String,
+    // Newest first.
+    pub pages: Vec<&'a Page>,
+}
+
+// Groups the pages by every taxonomy in the config. Terms that only differ in
+// case or punctuation, like "Rust" and "rust", slug the same and end up as a
+// single term.
+pub fn taxonomies<'a>(config: &Config, pages: &'a [Page]) -> Vec<Taxonomy<'a>> {
+    config
+        .taxonomies
+        .iter()
+        .map(|taxonomy| {
+            let url = format!("/{}/", slugify(&taxonomy.name));
+            let mut terms: BTreeMap<String, Term> = BTreeMap::new();
+
+            for page in pages {
+                for name in page.front_matter.terms(&taxonomy.name) {
+                    let slug = slugify(&name);
+                    if slug.is_empty() {
+                        continue;
+                    }
+
+                    let term = terms.entry(slug.clone()).or_insert_with(|| Term {
+                        url: format!("{}{}/", url, slug),
+                        name,
+                        slug,
+                        pages: vec![],
+                    });
+                    // The same term twice on a page only counts once.
+                    if !term.pages.iter().any(|p| std::ptr::eq(*p, page)) {
+                        term.pages.push(page);
+                    }
+                }
+            }
+
+            let mut terms: Vec<Term> = terms.into_values().collect();
+            for term in &mut terms {
+                sort_newest_first(&mut term.pages);
+            }
+
+            Taxonomy {
+                name: taxonomy.name.clone(),
+                url,
+                feed: taxonomy.feed,
+                terms,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::site::config::TaxonomyConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_taxonomies() {
+        let pages = vec![
+            Page::new("a.md", "---\ntags: [Rust, web]\nauthors: Ann\n---\n").unwrap(),
+            Page::new(
+                "b.md",
+                "---\ndate: 2024-01-01\ntags: [rust, rust]\nauthors: [Ann, Bob]\n---\n",
+            )
+            .unwrap(),
+            Page::new("c.md", "No front matter").unwrap(),
+        ];
+        let config = Config {
+            taxonomies: vec![TaxonomyConfig::new("tags"), TaxonomyConfig::new("authors")],
+            ..Config::default()
+        };
+
+        let found = taxonomies(&config, &pages);
+        let urls: Vec<&str> = found.iter().map(|t| t.url.as_str()).collect();
+        let terms: Vec<(&str, &str, Vec<&str>)> = found
+            .iter()
+            .flat_map(|t| &t.terms)
+            .map(|term| {
+                (
+                    term.name.as_str(),
+                    term.url.as_str(),
+                    term.pages.iter().map(|p| p.source.as_str()).collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(urls, vec!["/tags/", "/authors/"]);
+        assert_eq!(
+            terms,
+            vec![
+                ("Rust", "/tags/rust/", vec!["b.md", "a.md"]),
+                ("web", "/tags/web/", vec!["a.md"]),
+                ("Ann", "/authors/ann/", vec!["b.md", "a.md"]),
+                ("Bob", "/authors/bob/", vec!["b.md"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use minijinja::{escape_formatter, AutoEscape, Environment, Error, ErrorKind};
+use serde::Serialize;
+
+use crate::nodes::html_node::{write_escaped, ToHtmlString};
+
+use super::config::Config;
+use super::page::{Page, Status};
+use super::search::{INDEX_FILE, SCRIPT_FILE};
+use super::section::{NavLink, Navigation, Section, Sections};
+use super::taxonomy::{Taxonomy, Term};
+use super::urls::slugify;
+use super::SiteError;
+
+// Templates are looked up in the `templates` directory of the site first,
+// anything that isn't there comes from the built in ones below.
+pub const TEMPLATES_DIR: &str = "templates";
+
+const BUILT_IN: [(&str, &str); 6] = [
+    ("base.html", include_str!("templates/base.html")),
+    ("page.html", include_str!("templates/page.html")),
+    ("pagination.html", include_str!("templates/pagination.html")),
+    ("section.html", include_str!("templates/section.html")),
+    ("taxonomy.html", include_str!("templates/taxonomy.html")),
+    ("term.html", include_str!("templates/term.html")),
+];
+
+// Jinja style templates.
+// Ex. <h1>{{ page.title }}</h1>{{ page.content | safe }}
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl std::fmt::Debug for Templates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<&str> = self.env.templates().map(|(name, _)| name).collect();
+        names.sort();
+        f.debug_struct("Templates").field("names", &names).finish()
+    }
+}
+
+impl Default for Templates {
+    fn default() -> Templates {
+        let mut env = Environment::new();
+        // Tags on their own line don't leave a blank line behind.
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_keep_trailing_newline(true);
+        env.add_filter("date", date_filter);
+        // Escape the same way the nodes do, minijinja's own escaping also
+        // turns every `/` in a url into `&#x2f;`.
+        env.set_formatter(|out, state, value| match value.as_str() {
+            Some(text) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => {
+                write_escaped(out, text).map_err(Error::from)
+            }
+            _ => escape_formatter(out, state, value),
+        });
+
+        for (name, source) in BUILT_IN {
+            env.add_template(name, source)
+                .expect("built in templates are valid");
+        }
+
+        Templates { env }
+    }
+}
+
+impl Templates {
+    // Adds every .html file in `dir` on top of the built in templates, a
+    // missing directory leaves only the built in ones.
+    pub fn load(dir: &Path) -> Result<Templates, SiteError> {
+        let mut templates = Templates::default();
+        if !dir.is_dir() {
+            return Ok(templates);
+        }
+
+        let entries = fs::read_dir(dir).map_err(|e| SiteError::Io(dir.to_path_buf(), e))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| SiteError::Io(dir.to_path_buf(), e))?
+                .path();
+            if path.extension().is_some_and(|e| e == "html") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let source = fs::read_to_string(&path).map_err(|e| SiteError::Io(path.clone(), e))?;
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            templates.add(&name, &source)?;
+        }
+
+        Ok(templates)
+    }
+
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), SiteError> {
+        self.env
+            .add_template_owned(String::from(name), String::from(source))
+            .map_err(|e| SiteError::Template(String::from(name), e))
+    }
+
+    pub fn render<S: Serialize>(&self, name: &str, context: S) -> Result<String, SiteError> {
+        self.env
+            .get_template(name)
+            .and_then(|t| t.render(context))
+            .map_err(|e| SiteError::Template(String::from(name), e))
+    }
+}
+
+// Formats the dates handed to templates, which are in rfc 3339.
+// Ex. {{ page.date | date("%B %-d, %Y") }} -> March 1, 2024
+fn date_filter(value: String, format: Option<String>) -> Result<String, Error> {
+    let date = DateTime::parse_from_rfc3339(&value).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid date {:?}", value),
+        )
+        .with_source(e)
+    })?;
+
+    Ok(date
+        .format(format.as_deref().unwrap_or("%Y-%m-%d"))
+        .to_string())
+}
+
+fn template_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// `site` in every template.
+#[derive(Debug, Serialize)]
+pub struct SiteContext {
+    pub title: String,
+    pub description: String,
+    pub base_url: String,
+    pub author: Option<String>,
+    // Urls of the feeds for the whole site, when they're turned on.
+    pub rss: Option<String>,
+    pub atom: Option<String>,
+    // Urls of search.js and the index it loads, when search is turned on.
+    pub search_script: Option<String>,
+    pub search_index: Option<String>,
+}
+
+impl SiteContext {
+    pub fn new(config: &Config) -> SiteContext {
+        SiteContext {
+            title: config.title.clone(),
+            description: config.description.clone(),
+            base_url: config.base_url.clone(),
+            author: config.author.clone(),
+            rss: config.feeds.rss.then(|| config.permalink("/rss.xml")),
+            atom: config.feeds.atom.then(|| config.permalink("/atom.xml")),
+            search_script: config.search.enabled.then(|| config.permalink(SCRIPT_FILE)),
+            search_index: config.search.enabled.then(|| config.permalink(INDEX_FILE)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TermLink {
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+}
+
+// `page` in page.html, and every page in a listing.
+#[derive(Debug, Serialize)]
+pub struct PageContext {
+    pub title: String,
+    pub url: String,
+    pub permalink: String,
+    pub date: Option<String>,
+    pub updated: Option<String>,
+    pub expiry_date: Option<String>,
+    // "published", or "draft" or "scheduled" for pages only in builds with
+    // `--drafts` or `--future`.
+    pub status: Status,
+    // The html of the page.
+    pub content: String,
+    // Html up to `<!-- more -->` or the first few blocks, `summary_text` is
+    // the same as one line of text, which is what meta descriptions need.
+    pub summary: String,
+    pub summary_text: String,
+    // There is more to the page than the summary.
+    pub truncated: bool,
+    pub plain_text: String,
+    // The page's terms by taxonomy name.
+    // Ex. page.taxonomies.tags
+    pub taxonomies: BTreeMap<String, Vec<TermLink>>,
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+    // Where the page is in its section, only page.html gets these.
+    // Ex. {% for link in page.breadcrumbs %}
+    pub section: Option<NavLink>,
+    pub breadcrumbs: Vec<NavLink>,
+    pub siblings: Vec<NavLink>,
+    pub prev: Option<NavLink>,
+    pub next: Option<NavLink>,
+}
+
+impl PageContext {
+    pub fn new(config: &Config, page: &Page) -> PageContext {
+        let root = &page.document.root;
+        let summary = page.summary(config.summary_words);
+
+        let mut taxonomies = BTreeMap::new();
+        for taxonomy in &config.taxonomies {
+            let terms: Vec<TermLink> = page
+                .front_matter
+                .terms(&taxonomy.name)
+                .into_iter()
+                .filter(|name| !slugify(name).is_empty())
+                .map(|name| {
+                    let slug = slugify(&name);
+                    TermLink {
+                        url: format!("/{}/{}/", slugify(&taxonomy.name), slug),
+                        name,
+                        slug,
+                    }
+                })
+                .collect();
+            if !terms.is_empty() {
+                taxonomies.insert(taxonomy.name.clone(), terms);
+            }
+        }
+
+        PageContext {
+            title: page.title(),
+            url: page.url.clone(),
+            permalink: config.permalink(&page.url),
+            date: page.front_matter.date.as_ref().map(template_date),
+            updated: page.front_matter.updated.as_ref().map(template_date),
+            expiry_date: page.front_matter.expiry_date.as_ref().map(template_date),
+            status: page.status,
+            content: root.children().iter().map(|c| c.into_html()).collect(),
+            summary: summary.html,
+            summary_text: page.front_matter.summary.clone().unwrap_or(summary.text),
+            truncated: summary.truncated,
+            plain_text: root.to_plain_text(),
+            taxonomies,
+            extra: page.front_matter.extra.clone(),
+            section: None,
+            breadcrumbs: vec![],
+            siblings: vec![],
+            prev: None,
+            next: None,
+        }
+    }
+
+    pub fn with_navigation(mut self, navigation: Navigation) -> PageContext {
+        self.section = navigation.section;
+        self.breadcrumbs = navigation.breadcrumbs;
+        self.siblings = navigation.siblings;
+        self.prev = navigation.prev;
+        self.next = navigation.next;
+        self
+    }
+}
+
+// `section` in section.html.
+#[derive(Debug, Serialize)]
+pub struct SectionContext {
+    pub title: String,
+    pub url: String,
+    pub permalink: String,
+    // The html of the `_index.md`, empty without one.
+    pub content: String,
+    // Only the pages on this page of the listing.
+    pub pages: Vec<PageContext>,
+    pub subsections: Vec<SectionSummary>,
+    // The section above, none for the top one.
+    pub parent: Option<NavLink>,
+    // From the top section down to this one.
+    pub breadcrumbs: Vec<NavLink>,
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SectionSummary {
+    pub title: String,
+    pub url: String,
+    // Pages right in the section.
+    pub count: usize,
+}
+
+impl SectionContext {
+    pub fn new(
+        config: &Config,
+        sections: &Sections,
+        section: &Section,
+        pages: &[&Page],
+    ) -> SectionContext {
+        let breadcrumbs: Vec<NavLink> = sections
+            .ancestors(&section.path)
+            .into_iter()
+            .map(NavLink::section)
+            .collect();
+
+        SectionContext {
+            title: section.title(),
+            url: section.url.clone(),
+            permalink: config.permalink(&section.url),
+            content: section
+                .index
+                .map(|i| {
+                    i.document
+                        .root
+                        .children()
+                        .iter()
+                        .map(|c| c.into_html())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            pages: pages.iter().map(|p| PageContext::new(config, p)).collect(),
+            subsections: section
+                .subsections
+                .iter()
+                .filter_map(|path| sections.get(path))
+                .map(|s| SectionSummary {
+                    title: s.title(),
+                    url: s.url.clone(),
+                    count: s.pages.len(),
+                })
+                .collect(),
+            parent: breadcrumbs.iter().rev().nth(1).cloned(),
+            breadcrumbs,
+            extra: section
+                .index
+                .map(|i| i.front_matter.extra.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// `taxonomy` in taxonomy.html.
+#[derive(Debug, Serialize)]
+pub struct TaxonomyContext {
+    pub name: String,
+    pub url: String,
+    pub terms: Vec<TermSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TermSummary {
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+    pub count: usize,
+}
+
+impl TaxonomyContext {
+    pub fn new(taxonomy: &Taxonomy) -> TaxonomyContext {
+        TaxonomyContext {
+            name: taxonomy.name.clone(),
+            url: taxonomy.url.clone(),
+            terms: taxonomy
+                .terms
+                .iter()
+                .map(|t| TermSummary {
+                    name: t.name.clone(),
+                    slug: t.slug.clone(),
+                    url: t.url.clone(),
+                    count: t.pages.len(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// `term` in term.html.
+#[derive(Debug, Serialize)]
+pub struct TermContext {
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+    pub taxonomy: String,
+    pub count: usize,
+    // Only the pages on this page of the listing.
+    pub pages: Vec<PageContext>,
+    pub rss: Option<String>,
+    pub atom: Option<String>,
+}
+
+impl TermContext {
+    pub fn new(config: &Config, taxonomy: &Taxonomy, term: &Term, pages: &[&Page]) -> TermContext {
+        let feed = |on: bool, file: &str| {
+            (taxonomy.feed && on).then(|| config.permalink(&format!("{}{}", term.url, file)))
+        };
+
+        TermContext {
+            name: term.name.clone(),
+            slug: term.slug.clone(),
+            url: term.url.clone(),
+            taxonomy: taxonomy.name.clone(),
+            count: term.pages.len(),
+            pages: pages.iter().map(|p| PageContext::new(config, p)).collect(),
+            rss: feed(config.feeds.rss, "rss.xml"),
+            atom: feed(config.feeds.atom, "atom.xml"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use minijinja::context;
+
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        let mut templates = Templates::default();
+        templates
+            .add(
+                "post.html",
+                "{{ page.title }} on {{ page.date | date(\"%B %-d, %Y\") }}\n{% for tag in page.taxonomies.tags %}\n[{{ tag.name }}]({{ tag.url }})\n{% endfor %}\n{{ page.summary_text }}",
+            )
+            .unwrap();
+
+        let config = Config::default();
+        let page = Page::new(
+            "blog/hi.md",
+            "---\ntitle: Hi <there>\ndate: 2024-03-01\ntags: [Rust Lang]\n---\nSome *text*.",
+        )
+        .unwrap();
+
+        let test_cases = vec![(
+            "Test page values and the date filter",
+            "post.html",
+            "Hi &lt;there&gt; on March 1, 2024\n[Rust Lang](/tags/rust-lang/)\nSome text.",
+        )];
+
+        for (title, name, expected) in test_cases.iter() {
+            let output = templates
+                .render(name, context! { page => PageContext::new(&config, &page) })
+                .unwrap();
+            assert_eq!(
+                output, *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title, name, expected
+            );
+        }
+
+        assert!(matches!(
+            templates.add("broken.html", "{% if %}"),
+            Err(SiteError::Template(_, _))
+        ));
+        assert!(matches!(
+            templates.render("missing.html", context! {}),
+            Err(SiteError::Template(_, _))
+        ));
+    }
+}
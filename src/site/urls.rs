@@ -0,0 +1,393 @@
+use crate::nodes::html_node::HTMLAttributes;
+use crate::nodes::leaf_node::LeafNode;
+use crate::nodes::parent_node::ParentNode;
+use crate::nodes::visit::{walk_parent_mut, VisitorMut};
+use crate::parser::html::expand_raw_html;
+
+// Attributes that hold a url.
+const URL_ATTRIBUTES: [&str; 4] = ["href", "src", "poster", "cite"];
+
+// Ex. https:, mailto: or data:
+fn has_scheme(url: &str) -> bool {
+    let end = url.find([':', '/', '?', '#']);
+    match end {
+        Some(i) if url[i..].starts_with(':') => {
+            i > 0
+                && url[..i]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        _ => false,
+    }
+}
+
+// Turns a url from a page into an absolute one. `base_url` is the root of the
+// site and `page` is the absolute url of the page the link is on.
+// Ex. with a page of https://example.com/blog/hello/
+// cat.png -> https://example.com/blog/hello/cat.png
+// ../ -> https://example.com/blog/
+// /about/ -> https://example.com/about/
+pub fn resolve_url(base_url: &str, page: &str, url: &str) -> String {
+    if url.is_empty() || has_scheme(url) {
+        return String::from(url);
+    }
+
+    if let Some(rest) = url.strip_prefix("//") {
+        let scheme = page.split_once("://").map(|(s, _)| s).unwrap_or("https");
+        return format!("{}://{}", scheme, rest);
+    }
+
+    if url.starts_with('/') {
+        return format!("{}{}", base_url.trim_end_matches('/'), url);
+    }
+
+    let page = page.split(['#', '?']).next().unwrap_or(page);
+    if url.starts_with(['#', '?']) {
+        return format!("{}{}", page, url);
+    }
+
+    // Everything up to the last `/` of the page, then the `.` and `..` parts
+    // of the link are walked off of it.
+    let (origin, path) = match page.find("://").map(|i| i + 3) {
+        Some(start) => match page[start..].find('/') {
+            Some(i) => page.split_at(start + i),
+            None => (page, "/"),
+        },
+        None => ("", page),
+    };
+
+    let dir = &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)];
+    let (link, suffix) = match url.find(['?', '#']) {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+
+    let mut segments: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    let parts: Vec<&str> = link.split('/').collect();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        match *part {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(part),
+        }
+        // A link ending in `.` or `..` is a directory.
+        if last && matches!(*part, "." | "..") {
+            segments.push("");
+        }
+    }
+
+    format!("{}/{}{}", origin, segments.join("/"), suffix)
+}
+
+// Makes every link and image in the tree absolute, for content that's read
+// away from the site like feeds. Raw html is parsed first so the links in it
+// get changed too.
+pub fn make_urls_absolute(root: &mut ParentNode, base_url: &str, page: &str) {
+    expand_raw_html(root.children_mut());
+    root.accept_mut(&mut AbsoluteUrls { base_url, page });
+}
+
+struct AbsoluteUrls<'a> {
+    base_url: &'a str,
+    page: &'a str,
+}
+
+impl AbsoluteUrls<'_> {
+    fn rewrite(&self, attributes: &mut HTMLAttributes) {
+        for name in URL_ATTRIBUTES {
+            if let Some(url) = attributes.get(name) {
+                let url = resolve_url(self.base_url, self.page, url);
+                attributes.set(name, &url);
+            }
+        }
+    }
+}
+
+impl VisitorMut for AbsoluteUrls<'_> {
+    fn visit_parent_mut(&mut self, node: &mut ParentNode) {
+        if node.attributes().is_some() {
+            self.rewrite(node.attributes_mut());
+        }
+        walk_parent_mut(self, node);
+    }
+
+    fn visit_leaf_mut(&mut self, node: &mut LeafNode) {
+        if let Some(attributes) = node.attributes.as_mut() {
+            self.rewrite(attributes);
+        }
+    }
+}
+
+// Lowercase letters and digits joined by `-`, for putting names in urls.
+// Ex. "Rust & WebAssembly" -> "rust-webassembly"
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let end = slug.trim_end_matches('-').len();
+    slug.truncate(end);
+    slug
+}
+
+// Letters with accents and letters of the Greek and Cyrillic alphabets
+// spelled in ascii, lowercased, so page slugs work anywhere. Anything else
+// is left as it is.
+// Ex. "Crème Brûlée" -> "creme brulee", "Привет" -> "privet"
+pub fn transliterate(text: &str) -> String {
+    let mut ascii = String::new();
+    for c in text.to_lowercase().chars() {
+        match ascii_letters(c) {
+            Some(letters) => ascii.push_str(letters),
+            None => ascii.push(c),
+        }
+    }
+    ascii
+}
+
+fn ascii_letters(c: char) -> Option<&'static str> {
+    let letters = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ð' | 'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ŋ' => "ng",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' | 'ơ' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' | 'ſ' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' | 'ư' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        // Greek.
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        // Cyrillic, the hard and soft signs aren't letters of their own.
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        // Ligatures that don't come apart when lowercased.
+        'ﬀ' => "ff",
+        'ﬁ' => "fi",
+        'ﬂ' => "fl",
+        _ => return None,
+    };
+    Some(letters)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::nodes::html_node::ToHtmlString;
+    use crate::parser::markdown_to_html_node;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_url() {
+        let base = "https://example.com";
+        let page = "https://example.com/blog/hello/";
+        let test_cases = vec![
+            (
+                "Test absolute url is kept",
+                "https://rust-lang.org/",
+                "https://rust-lang.org/",
+            ),
+            (
+                "Test mailto is kept",
+                "mailto:me@example.com",
+                "mailto:me@example.com",
+            ),
+            (
+                "Test relative file",
+                "cat.png",
+                "https://example.com/blog/hello/cat.png",
+            ),
+            (
+                "Test dot segments",
+                "./../other/?a=1#top",
+                "https://example.com/blog/other/?a=1#top",
+            ),
+            ("Test parent directory", "..", "https://example.com/blog/"),
+            (
+                "Test root relative",
+                "/about/",
+                "https://example.com/about/",
+            ),
+            (
+                "Test fragment only",
+                "#setup",
+                "https://example.com/blog/hello/#setup",
+            ),
+            (
+                "Test scheme relative",
+                "//cdn.example.com/a.js",
+                "https://cdn.example.com/a.js",
+            ),
+            (
+                "Test colon in a path isn't a scheme",
+                "a/b:c",
+                "https://example.com/blog/hello/a/b:c",
+            ),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                resolve_url(base, page, input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+
+        assert_eq!(
+            resolve_url(
+                "https://example.com/docs/",
+                "https://example.com/docs/a/",
+                "/b/"
+            ),
+            "https://example.com/docs/b/"
+        );
+    }
+
+    #[test]
+    fn test_make_urls_absolute() {
+        let mut root =
+            markdown_to_html_node("[Home](/) and ![cat](cat.png)\n\n<p><a href=\"../\">Up</a></p>")
+                .root;
+        make_urls_absolute(
+            &mut root,
+            "https://example.com",
+            "https://example.com/blog/hi/",
+        );
+
+        assert_eq!(
+            root.into_html(),
+            "<div><p><a href=\"https://example.com/\">Home</a> and <img src=\"https://example.com/blog/hi/cat.png\" alt=\"cat\"></p><p><a href=\"https://example.com/blog/\">Up</a></p></div>"
+        );
+    }
+
+    #[test]
+    fn test_slugify() {
+        let test_cases = vec![
+            ("Test words are joined", "Hello World", "hello-world"),
+            (
+                "Test symbols are dropped",
+                "Rust & WebAssembly!",
+                "rust-webassembly",
+            ),
+            ("Test unicode letters are kept", "Café Crème", "café-crème"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                slugify(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_transliterate() {
+        let test_cases = vec![
+            ("Test accents", "Crème Brûlée", "creme brulee"),
+            (
+                "Test letters spelled with two",
+                "Straße Æsir",
+                "strasse aesir",
+            ),
+            ("Test greek", "Σίσυφος", "sisyfos"),
+            ("Test cyrillic", "Щука и ёж", "shchuka i yozh"),
+            ("Test other scripts are kept", "東京 2024", "東京 2024"),
+        ];
+
+        for (title, input, expected) in test_cases.iter() {
+            assert_eq!(
+                transliterate(input),
+                *expected,
+                "\"{}\" test failed for input: {:?} and expexted: {}",
+                title,
+                input,
+                expected
+            );
+        }
+    }
+}
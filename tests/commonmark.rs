@@ -0,0 +1,299 @@
+// Runs the CommonMark spec examples, and the GitHub Flavored Markdown
+// extension examples, through the parser and reports the pass rate for each
+// section of the spec.
+//
+// Examples that are known to pass are listed in `fixtures/commonmark/passing.txt`,
+// the test fails when one of them stops passing. After improving the parser run
+// the tests with `UPDATE_COMMONMARK_BASELINE=1` to record the new passes.
+use std::collections::BTreeSet;
+use std::{env, fs, panic};
+
+use rust_ssg::nodes::html_node::ToHtmlString;
+use rust_ssg::parser::markdown_to_html_node;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/commonmark");
+
+const EXAMPLE_FENCE: &str = "````````````````````````````````";
+
+// The spec files to run, with the prefix used for their examples in the
+// baseline file.
+const SPECS: [(&str, &str); 2] = [("spec", "spec.txt"), ("gfm", "gfm.txt")];
+
+#[derive(Debug)]
+struct Example {
+    id: String,
+    section: String,
+    markdown: String,
+    html: String,
+}
+
+// Reads the examples out of a spec file. Examples are fenced with a line of
+// backticks followed by `example`, with a `.` line between the markdown and the
+// expected html. The spec writes tabs as `→`.
+fn parse_spec(prefix: &str, spec: &str) -> Vec<Example> {
+    let mut examples = Vec::new();
+    let mut section = String::new();
+    let mut lines = spec.lines();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with('#') {
+            section = String::from(line.trim_start_matches('#').trim());
+            continue;
+        }
+
+        if line != format!("{} example", EXAMPLE_FENCE) {
+            continue;
+        }
+
+        let mut markdown = String::new();
+        let mut html = String::new();
+        let mut in_html = false;
+
+        for line in lines.by_ref() {
+            if line == EXAMPLE_FENCE {
+                break;
+            }
+            if line == "." && !in_html {
+                in_html = true;
+                continue;
+            }
+
+            let target = if in_html { &mut html } else { &mut markdown };
+            target.push_str(&line.replace('→', "\t"));
+            target.push('\n');
+        }
+
+        examples.push(Example {
+            id: format!("{}:{}", prefix, examples.len() + 1),
+            section: section.clone(),
+            markdown,
+            html,
+        });
+    }
+
+    examples
+}
+
+// Makes html comparable the same way the spec's own test runner does, by
+// dropping whitespace between tags, self closing slashes and sorting the
+// attributes of each tag.
+fn normalize(html: &str) -> String {
+    let mut normalized = String::new();
+    let mut rest = html.trim();
+
+    while let Some(open) = rest.find('<') {
+        normalized.push_str(&rest[..open]);
+
+        let Some(close) = rest[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+
+        normalized.push_str(&normalize_tag(&rest[open + 1..close]));
+        rest = &rest[close + 1..];
+
+        // Whitespace between two tags isn't significant.
+        let trimmed = rest.trim_start();
+        if trimmed.starts_with('<') {
+            rest = trimmed;
+        }
+    }
+    normalized.push_str(rest);
+
+    normalized.trim().to_string()
+}
+
+fn normalize_tag(tag: &str) -> String {
+    let tag = tag.trim_end_matches('/').trim();
+
+    if tag.starts_with('/') || tag.starts_with('!') || tag.starts_with('?') {
+        return format!("<{}>", tag);
+    }
+
+    let mut parts = split_attributes(tag);
+    if parts.is_empty() {
+        return format!("<{}>", tag);
+    }
+
+    let name = parts.remove(0);
+    parts.sort();
+
+    if parts.is_empty() {
+        format!("<{}>", name)
+    } else {
+        format!("<{} {}>", name, parts.join(" "))
+    }
+}
+
+// Splits a tag on whitespace that isn't inside a quoted attribute value.
+fn split_attributes(tag: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+
+    for c in tag.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), _) if q == c => {
+                quote = None;
+                current.push(c);
+            }
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
+
+// Renders the example, the root div every page is wrapped in is removed.
+// Parser panics count as a failure rather than stopping the run.
+fn render(markdown: &str) -> Option<String> {
+    let markdown = markdown.to_string();
+    let html = panic::catch_unwind(|| markdown_to_html_node(&markdown).root.into_html()).ok()?;
+
+    Some(
+        html.strip_prefix("<div>")
+            .and_then(|h| h.strip_suffix("</div>"))
+            .unwrap_or(&html)
+            .to_string(),
+    )
+}
+
+fn load_examples() -> Vec<Example> {
+    SPECS
+        .iter()
+        .flat_map(|(prefix, file)| {
+            let spec = fs::read_to_string(format!("{}/{}", FIXTURES, file)).unwrap();
+            parse_spec(prefix, &spec)
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_spec() {
+    let spec = format!(
+        "# Tabs\n\n{f} example\n→foo\n.\n<pre><code>foo\n</code></pre>\n{f}\n\n## Lists\n\n{f} example\n- a\n.\n<ul>\n<li>a</li>\n</ul>\n{f}\n",
+        f = EXAMPLE_FENCE
+    );
+    let examples = parse_spec("spec", &spec);
+
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[0].id, "spec:1");
+    assert_eq!(examples[0].section, "Tabs");
+    assert_eq!(examples[0].markdown, "\tfoo\n");
+    assert_eq!(examples[1].section, "Lists");
+    assert_eq!(examples[1].html, "<ul>\n<li>a</li>\n</ul>\n");
+}
+
+#[test]
+fn test_normalize() {
+    let test_cases = vec![
+        (
+            "Test whitespace between tags is dropped",
+            "<ul>\n<li>a</li>\n</ul>\n",
+            "<ul><li>a</li></ul>",
+        ),
+        (
+            "Test attributes are sorted and self closing slashes removed",
+            "<p><img src=\"/url\" alt=\"foo bar\" /></p>",
+            "<p><img alt=\"foo bar\" src=\"/url\"></p>",
+        ),
+        (
+            "Test whitespace in code is kept",
+            "<pre><code>a\n\nb\n</code></pre>",
+            "<pre><code>a\n\nb\n</code></pre>",
+        ),
+    ];
+
+    for (title, input, expected) in test_cases.iter() {
+        assert_eq!(
+            &normalize(input),
+            expected,
+            "\"{}\" test failed for input: {:?} and expexted: {}",
+            title,
+            input,
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_commonmark_spec() {
+    let examples = load_examples();
+    let baseline_path = format!("{}/passing.txt", FIXTURES);
+    let baseline: BTreeSet<String> = fs::read_to_string(&baseline_path)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect();
+
+    // Silence the default hook so panicking examples don't flood the output.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut passing = BTreeSet::new();
+    let mut sections: Vec<(String, usize, usize)> = Vec::new();
+
+    for example in &examples {
+        let passed = render(&example.markdown)
+            .map(|html| normalize(&html) == normalize(&example.html))
+            .unwrap_or(false);
+
+        if passed {
+            passing.insert(example.id.clone());
+        }
+
+        match sections.last_mut() {
+            Some((section, total, passes)) if *section == example.section => {
+                *total += 1;
+                *passes += passed as usize;
+            }
+            _ => sections.push((example.section.clone(), 1, passed as usize)),
+        }
+    }
+
+    panic::set_hook(hook);
+
+    println!("{:<40} {:>7}", "section", "passed");
+    for (section, total, passes) in &sections {
+        println!("{:<40} {:>3}/{:<3}", section, passes, total);
+    }
+    println!(
+        "{:<40} {:>3}/{:<3} ({:.1}%)",
+        "total",
+        passing.len(),
+        examples.len(),
+        passing.len() as f64 * 100.0 / examples.len() as f64
+    );
+
+    if env::var("UPDATE_COMMONMARK_BASELINE").is_ok() {
+        let ids: Vec<&str> = passing.iter().map(|id| id.as_str()).collect();
+        fs::write(&baseline_path, ids.join("\n") + "\n").unwrap();
+        return;
+    }
+
+    let regressions: Vec<&String> = baseline.difference(&passing).collect();
+    let improvements = passing.difference(&baseline).count();
+    if improvements > 0 {
+        println!(
+            "{} examples pass that are not in the baseline, run with UPDATE_COMMONMARK_BASELINE=1 to record them",
+            improvements
+        );
+    }
+
+    assert!(
+        regressions.is_empty(),
+        "examples that used to pass now fail: {:?}",
+        regressions
+    );
+}